@@ -0,0 +1,15 @@
+CREATE TABLE "users" (
+  "username" varchar PRIMARY KEY,
+  "hashed_password" varchar NOT NULL,
+  "full_name" varchar NOT NULL,
+  "email" varchar UNIQUE NOT NULL,
+  "password_changed_at" timestamptz NOT NULL DEFAULT ('0001-01-01 00:00:00Z'),
+  "created_at" timestamptz NOT NULL DEFAULT (now())
+);
+
+-- Accounts created before users existed get a placeholder user that cannot log in.
+INSERT INTO "users" ("username", "hashed_password", "full_name", "email")
+SELECT DISTINCT "owner", '', "owner", "owner" || '@users.invalid' FROM "accounts"
+ON CONFLICT DO NOTHING;
+
+ALTER TABLE "accounts" ADD FOREIGN KEY ("owner") REFERENCES "users" ("username");
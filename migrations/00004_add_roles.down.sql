@@ -0,0 +1,5 @@
+ALTER TABLE "accounts" DROP COLUMN IF EXISTS "frozen";
+
+ALTER TABLE "users" DROP COLUMN IF EXISTS "role";
+
+DROP TYPE IF EXISTS "user_role";
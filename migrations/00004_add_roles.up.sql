@@ -0,0 +1,5 @@
+CREATE TYPE "user_role" AS ENUM ('banker', 'depositor');
+
+ALTER TABLE "users" ADD COLUMN "role" user_role NOT NULL DEFAULT 'depositor';
+
+ALTER TABLE "accounts" ADD COLUMN "frozen" boolean NOT NULL DEFAULT false;
@@ -0,0 +1,7 @@
+DROP TRIGGER IF EXISTS "entries_balanced" ON "entries";
+
+DROP TRIGGER IF EXISTS "transfers_balanced" ON "transfers";
+
+DROP FUNCTION IF EXISTS "check_transfer_entries"();
+
+ALTER TABLE "entries" DROP COLUMN IF EXISTS "transfer_id";
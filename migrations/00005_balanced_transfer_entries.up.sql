@@ -0,0 +1,52 @@
+ALTER TABLE "entries" ADD COLUMN "transfer_id" bigint;
+
+ALTER TABLE "entries" ADD FOREIGN KEY ("transfer_id") REFERENCES "transfers" ("id");
+
+CREATE INDEX ON "entries" ("transfer_id");
+
+-- Every transfer must be booked as exactly one debit of the sending account
+-- and one credit of the receiving account, so its entries sum to zero.
+-- Entries without a transfer (deposits, adjustments) are not constrained.
+CREATE FUNCTION "check_transfer_entries"() RETURNS trigger AS $$
+DECLARE
+  t "transfers"%ROWTYPE;
+  debits integer;
+  credits integer;
+  total_entries integer;
+  total_amount bigint;
+BEGIN
+  IF TG_TABLE_NAME = 'transfers' THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."id";
+  ELSIF NEW."transfer_id" IS NOT NULL THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."transfer_id";
+  ELSE
+    RETURN NULL;
+  END IF;
+
+  SELECT
+    count(*) FILTER (WHERE "account_id" = t."from_account_id" AND "amount" = -t."amount"),
+    count(*) FILTER (WHERE "account_id" = t."to_account_id" AND "amount" = t."amount"),
+    count(*),
+    coalesce(sum("amount"), 0)
+  INTO debits, credits, total_entries, total_amount
+  FROM "entries"
+  WHERE "transfer_id" = t."id";
+
+  IF debits <> 1 OR credits <> 1 OR total_entries <> 2 OR total_amount <> 0 THEN
+    RAISE EXCEPTION 'entries of transfer % do not balance', t."id"
+      USING ERRCODE = 'check_violation', CONSTRAINT = 'transfer_entries_balanced';
+  END IF;
+
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+CREATE CONSTRAINT TRIGGER "transfers_balanced"
+  AFTER INSERT ON "transfers"
+  DEFERRABLE INITIALLY DEFERRED
+  FOR EACH ROW EXECUTE FUNCTION "check_transfer_entries"();
+
+CREATE CONSTRAINT TRIGGER "entries_balanced"
+  AFTER INSERT OR UPDATE ON "entries"
+  DEFERRABLE INITIALLY DEFERRED
+  FOR EACH ROW EXECUTE FUNCTION "check_transfer_entries"();
@@ -0,0 +1,13 @@
+CREATE TABLE "idempotency_keys" (
+  "scope" varchar NOT NULL,
+  "key" varchar NOT NULL,
+  "request_hash" varchar NOT NULL,
+  -- NULL until the first request with this key has completed.
+  "response_status" smallint,
+  "response_content_type" varchar,
+  "response_body" bytea,
+  "created_at" timestamptz NOT NULL DEFAULT (now()),
+  PRIMARY KEY ("scope", "key")
+);
+
+CREATE INDEX ON "idempotency_keys" ("created_at");
@@ -0,0 +1,5 @@
+ALTER TABLE "accounts" DROP CONSTRAINT IF EXISTS "accounts_currency_fkey";
+
+ALTER TABLE "accounts" ALTER COLUMN "currency" TYPE varchar;
+
+DROP TABLE IF EXISTS "currencies";
@@ -0,0 +1,24 @@
+CREATE TABLE "currencies" (
+  "code" varchar(3) PRIMARY KEY CHECK ("code" ~ '^[A-Z]{3}$'),
+  "numeric_code" smallint NOT NULL UNIQUE CHECK ("numeric_code" BETWEEN 0 AND 999),
+  -- ISO 4217 minor unit: digits after the decimal separator.
+  "minor_units" smallint NOT NULL CHECK ("minor_units" BETWEEN 0 AND 4),
+  "enabled" boolean NOT NULL DEFAULT true
+);
+
+INSERT INTO "currencies" ("code", "numeric_code", "minor_units") VALUES
+  ('AUD', 36, 2),
+  ('CAD', 124, 2),
+  ('CHF', 756, 2),
+  ('CNY', 156, 2),
+  ('EUR', 978, 2),
+  ('GBP', 826, 2),
+  ('JPY', 392, 0),
+  ('KRW', 410, 0),
+  ('KWD', 414, 3),
+  ('USD', 840, 2);
+
+ALTER TABLE "accounts" ALTER COLUMN "currency" TYPE varchar(3);
+
+ALTER TABLE "accounts" ADD CONSTRAINT "accounts_currency_fkey"
+  FOREIGN KEY ("currency") REFERENCES "currencies" ("code");
@@ -0,0 +1,65 @@
+CREATE OR REPLACE FUNCTION "check_transfer_entries"() RETURNS trigger AS $$
+DECLARE
+  t "transfers"%ROWTYPE;
+  debits integer;
+  credits integer;
+  total_entries integer;
+  total_amount bigint;
+BEGIN
+  IF TG_TABLE_NAME = 'transfers' THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."id";
+  ELSIF NEW."transfer_id" IS NOT NULL THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."transfer_id";
+  ELSE
+    RETURN NULL;
+  END IF;
+
+  SELECT
+    count(*) FILTER (WHERE "account_id" = t."from_account_id" AND "amount" = -t."amount"),
+    count(*) FILTER (WHERE "account_id" = t."to_account_id" AND "amount" = t."amount"),
+    count(*),
+    coalesce(sum("amount"), 0)
+  INTO debits, credits, total_entries, total_amount
+  FROM "entries"
+  WHERE "transfer_id" = t."id";
+
+  IF debits <> 1 OR credits <> 1 OR total_entries <> 2 OR total_amount <> 0 THEN
+    RAISE EXCEPTION 'entries of transfer % do not balance', t."id"
+      USING ERRCODE = 'check_violation', CONSTRAINT = 'transfer_entries_balanced';
+  END IF;
+
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
+
+ALTER TABLE "transfers" DROP CONSTRAINT IF EXISTS "transfers_fx_check";
+
+ALTER TABLE "transfers" DROP COLUMN IF EXISTS "fx_residue";
+
+ALTER TABLE "transfers" DROP COLUMN IF EXISTS "fx_spread";
+
+ALTER TABLE "transfers" DROP COLUMN IF EXISTS "fx_rate";
+
+ALTER TABLE "transfers" DROP COLUMN IF EXISTS "exchange_rate_id";
+
+ALTER TABLE "transfers" DROP COLUMN IF EXISTS "to_amount";
+
+-- Position accounts go negative, which the schema before this migration
+-- cannot hold. Rolling back discards the position history: their entries,
+-- the accounts and their owner are deleted. The customer legs of past
+-- conversions are kept as booked.
+DELETE FROM "entries" e
+USING "accounts" a
+WHERE e."account_id" = a."id" AND a."owner" = 'fx-position';
+
+DELETE FROM "accounts" WHERE "owner" = 'fx-position';
+
+DROP INDEX IF EXISTS "accounts_fx_position_key";
+
+DELETE FROM "users" WHERE "username" = 'fx-position';
+
+ALTER TABLE "accounts" DROP CONSTRAINT IF EXISTS "accounts_balance_check";
+
+ALTER TABLE "accounts" ADD CONSTRAINT "accounts_balance_check" CHECK ("balance" >= -"overdraft_limit");
+
+DROP TABLE IF EXISTS "exchange_rates";
@@ -0,0 +1,104 @@
+CREATE TABLE "exchange_rates" (
+  "id" BIGSERIAL PRIMARY KEY,
+  "base_currency" varchar(3) NOT NULL REFERENCES "currencies" ("code"),
+  "quote_currency" varchar(3) NOT NULL REFERENCES "currencies" ("code"),
+  -- Mid-market units of the quote currency per unit of the base currency.
+  "rate" numeric(18,10) NOT NULL CHECK ("rate" > 0),
+  -- Margin kept on each conversion, in basis points of the converted amount.
+  "spread_bps" integer NOT NULL DEFAULT 0 CHECK ("spread_bps" BETWEEN 0 AND 10000),
+  "effective_from" timestamptz NOT NULL,
+  "created_at" timestamptz NOT NULL DEFAULT (now()),
+  CONSTRAINT "exchange_rates_pair_check" CHECK ("base_currency" <> "quote_currency"),
+  CONSTRAINT "exchange_rates_pair_effective_from_key"
+    UNIQUE ("base_currency", "quote_currency", "effective_from")
+);
+
+-- Converted amounts are booked against a position account per currency, so
+-- that the entries of every currency still sum to zero. Position accounts
+-- belong to a placeholder user that cannot log in and may go negative.
+INSERT INTO "users" ("username", "hashed_password", "full_name", "email")
+VALUES ('fx-position', '', 'FX position', 'fx-position@users.invalid');
+
+CREATE UNIQUE INDEX "accounts_fx_position_key" ON "accounts" ("currency")
+  WHERE "owner" = 'fx-position';
+
+INSERT INTO "accounts" ("owner", "balance", "currency")
+SELECT 'fx-position', 0, "code" FROM "currencies";
+
+ALTER TABLE "accounts" DROP CONSTRAINT "accounts_balance_check";
+
+ALTER TABLE "accounts" ADD CONSTRAINT "accounts_balance_check"
+  CHECK ("balance" >= -"overdraft_limit" OR "owner" = 'fx-position');
+
+-- A transfer credits `to_amount` in the receiver's currency. For a
+-- conversion it also records the rate used, the spread kept and the fraction
+-- of a minor unit lost to rounding, all in the receiver's currency.
+ALTER TABLE "transfers" ADD COLUMN "to_amount" bigint;
+
+UPDATE "transfers" SET "to_amount" = "amount";
+
+ALTER TABLE "transfers" ALTER COLUMN "to_amount" SET NOT NULL;
+
+ALTER TABLE "transfers" ADD COLUMN "exchange_rate_id" bigint REFERENCES "exchange_rates" ("id");
+
+ALTER TABLE "transfers" ADD COLUMN "fx_rate" numeric(18,10);
+
+ALTER TABLE "transfers" ADD COLUMN "fx_spread" bigint NOT NULL DEFAULT 0;
+
+ALTER TABLE "transfers" ADD COLUMN "fx_residue" numeric(18,10) NOT NULL DEFAULT 0;
+
+ALTER TABLE "transfers" ADD CONSTRAINT "transfers_fx_check" CHECK (
+  CASE WHEN "exchange_rate_id" IS NULL
+    THEN "fx_rate" IS NULL AND "to_amount" = "amount" AND "fx_spread" = 0 AND "fx_residue" = 0
+    ELSE "fx_rate" IS NOT NULL
+  END
+);
+
+-- A transfer is booked as one debit of the sender and one credit of the
+-- receiver and, if it converts, one entry in each currency's position
+-- account. The entries of each currency sum to zero.
+CREATE OR REPLACE FUNCTION "check_transfer_entries"() RETURNS trigger AS $$
+DECLARE
+  t "transfers"%ROWTYPE;
+  debits integer;
+  credits integer;
+  total_entries integer;
+  expected_entries integer;
+  unbalanced_currencies integer;
+BEGIN
+  IF TG_TABLE_NAME = 'transfers' THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."id";
+  ELSIF NEW."transfer_id" IS NOT NULL THEN
+    SELECT * INTO t FROM "transfers" WHERE "id" = NEW."transfer_id";
+  ELSE
+    RETURN NULL;
+  END IF;
+
+  SELECT
+    count(*) FILTER (WHERE "account_id" = t."from_account_id" AND "amount" = -t."amount"),
+    count(*) FILTER (WHERE "account_id" = t."to_account_id" AND "amount" = t."to_amount"),
+    count(*)
+  INTO debits, credits, total_entries
+  FROM "entries"
+  WHERE "transfer_id" = t."id";
+
+  SELECT count(*) INTO unbalanced_currencies
+  FROM (
+    SELECT a."currency"
+    FROM "entries" e JOIN "accounts" a ON a."id" = e."account_id"
+    WHERE e."transfer_id" = t."id"
+    GROUP BY a."currency"
+    HAVING sum(e."amount") <> 0
+  ) unbalanced;
+
+  expected_entries := CASE WHEN t."exchange_rate_id" IS NULL THEN 2 ELSE 4 END;
+
+  IF debits <> 1 OR credits <> 1 OR total_entries <> expected_entries
+    OR unbalanced_currencies <> 0 THEN
+    RAISE EXCEPTION 'entries of transfer % do not balance', t."id"
+      USING ERRCODE = 'check_violation', CONSTRAINT = 'transfer_entries_balanced';
+  END IF;
+
+  RETURN NULL;
+END;
+$$ LANGUAGE plpgsql;
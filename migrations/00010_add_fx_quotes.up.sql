@@ -0,0 +1,15 @@
+-- A quote locks the exchange rate in effect when it was issued. It can be
+-- used by one transfer of its owner until it expires.
+CREATE TABLE "fx_quotes" (
+  "id" uuid PRIMARY KEY,
+  "owner" varchar NOT NULL REFERENCES "users" ("username"),
+  "exchange_rate_id" bigint NOT NULL REFERENCES "exchange_rates" ("id"),
+  "expires_at" timestamptz NOT NULL,
+  -- Set in the same transaction as the transfer that used the quote.
+  "transfer_id" bigint UNIQUE REFERENCES "transfers" ("id"),
+  "used_at" timestamptz,
+  "created_at" timestamptz NOT NULL DEFAULT (now()),
+  CONSTRAINT "fx_quotes_used_check" CHECK (("transfer_id" IS NULL) = ("used_at" IS NULL))
+);
+
+CREATE INDEX ON "fx_quotes" ("owner");
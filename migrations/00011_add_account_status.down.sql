@@ -0,0 +1,11 @@
+DROP TABLE IF EXISTS "account_status_changes";
+
+ALTER TABLE "accounts" DROP CONSTRAINT IF EXISTS "accounts_closed_balance_check";
+
+ALTER TABLE "accounts" ADD COLUMN "frozen" boolean NOT NULL DEFAULT false;
+
+UPDATE "accounts" SET "frozen" = true WHERE "status" IN ('frozen', 'closed');
+
+ALTER TABLE "accounts" DROP COLUMN IF EXISTS "status";
+
+DROP TYPE IF EXISTS "account_status";
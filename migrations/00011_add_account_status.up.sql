@@ -0,0 +1,25 @@
+-- pending -> active | closed, active -> frozen | closed, frozen -> active | closed.
+-- Closed is final. Accounts are never deleted, since entries and transfers
+-- reference them.
+CREATE TYPE "account_status" AS ENUM ('pending', 'active', 'frozen', 'closed');
+
+ALTER TABLE "accounts" ADD COLUMN "status" account_status NOT NULL DEFAULT 'active';
+
+UPDATE "accounts" SET "status" = 'frozen' WHERE "frozen";
+
+ALTER TABLE "accounts" DROP COLUMN "frozen";
+
+ALTER TABLE "accounts" ADD CONSTRAINT "accounts_closed_balance_check"
+  CHECK ("status" <> 'closed' OR "balance" = 0);
+
+CREATE TABLE "account_status_changes" (
+  "id" BIGSERIAL PRIMARY KEY,
+  "account_id" bigint NOT NULL REFERENCES "accounts" ("id"),
+  "from_status" account_status NOT NULL,
+  "to_status" account_status NOT NULL,
+  "reason" varchar NOT NULL,
+  "changed_by" varchar NOT NULL REFERENCES "users" ("username"),
+  "created_at" timestamptz NOT NULL DEFAULT (now())
+);
+
+CREATE INDEX ON "account_status_changes" ("account_id");
@@ -1,2 +1,12 @@
+pub mod auth;
+pub mod authz;
+pub mod idempotency;
+pub mod json;
+pub mod metrics;
+pub mod policy;
+pub mod request_id;
+pub mod response_mapper;
 pub mod router;
 pub mod server;
+pub mod shutdown;
+pub mod state;
@@ -0,0 +1,51 @@
+use crate::{
+    auth::token::{Payload, TokenMaker, TokenType},
+    prelude::*,
+};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::{header::AUTHORIZATION, request::Parts},
+};
+use std::sync::Arc;
+
+const AUTHORIZATION_TYPE_BEARER: &str = "bearer";
+
+/// Extracts and verifies the bearer access token of a request. Handlers that
+/// take this extractor reject unauthenticated requests with a 401.
+#[derive(Debug, Clone)]
+pub struct AuthPayload(pub Payload);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for AuthPayload
+where
+    Arc<TokenMaker>: FromRef<S>,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> ServerResult<Self> {
+        let header = parts
+            .headers
+            .get(AUTHORIZATION)
+            .and_then(|value| value.to_str().ok())
+            .ok_or(ServerError::Unauthorized(
+                "authorization header is not provided",
+            ))?;
+
+        let (auth_type, token) = header.split_once(' ').ok_or(ServerError::Unauthorized(
+            "invalid authorization header format",
+        ))?;
+
+        if !auth_type.eq_ignore_ascii_case(AUTHORIZATION_TYPE_BEARER) {
+            return Err(ServerError::Unauthorized("unsupported authorization type"));
+        }
+
+        let token_maker = Arc::<TokenMaker>::from_ref(state);
+        let payload = token_maker
+            .verify_token(token.trim(), TokenType::Access)
+            .map_err(|err| ServerError::Unauthorized(err.as_str()))?;
+
+        Ok(AuthPayload(payload))
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{
+    auth::token::Payload,
+    db::store::Store,
+    models::{Account, FxQuote, Role, Transfer},
+    prelude::*,
+};
+
+/// Fails with `Forbidden` unless the authenticated user owns `account`.
+/// Required for anything that changes the account or moves its funds.
+pub fn authorize_account_owner(auth: &Payload, account: &Account) -> ServerResult<()> {
+    if account.owner != auth.username {
+        return Err(ServerError::ClientError(ClientError::Forbidden));
+    }
+    Ok(())
+}
+
+/// Fails with `Forbidden` unless the authenticated user requested `quote`.
+pub fn authorize_fx_quote_owner(auth: &Payload, quote: &FxQuote) -> ServerResult<()> {
+    if quote.owner != auth.username {
+        return Err(ServerError::ClientError(ClientError::Forbidden));
+    }
+    Ok(())
+}
+
+/// Owners may view their own accounts; bankers may view any account.
+pub fn authorize_account_view(auth: &Payload, account: &Account) -> ServerResult<()> {
+    if auth.role == Role::Banker {
+        return Ok(());
+    }
+    authorize_account_owner(auth, account)
+}
+
+/// A transfer is visible to the owners of either side and to bankers.
+pub async fn authorize_transfer_view<S: Store>(
+    store: &S,
+    auth: &Payload,
+    transfer: &Transfer,
+) -> ServerResult<()> {
+    let from_account = store.get_account(transfer.from_account_id).await?;
+    if authorize_account_view(auth, &from_account).is_ok() {
+        return Ok(());
+    }
+
+    let to_account = store.get_account(transfer.to_account_id).await?;
+    authorize_account_view(auth, &to_account)
+}
+
+/// Loads an account and checks that the authenticated user owns it.
+pub async fn get_owned_account<S: Store>(
+    store: &S,
+    auth: &Payload,
+    id: i64,
+) -> ServerResult<Account> {
+    let account = store.get_account(id).await?;
+    authorize_account_owner(auth, &account)?;
+    Ok(account)
+}
+
+/// Loads an account and checks that the authenticated user may view it.
+pub async fn get_viewable_account<S: Store>(
+    store: &S,
+    auth: &Payload,
+    id: i64,
+) -> ServerResult<Account> {
+    let account = store.get_account(id).await?;
+    authorize_account_view(auth, &account)?;
+    Ok(account)
+}
+
+mod tests {
+    use super::*;
+    use crate::auth::token::{TokenMaker, TokenType};
+    use crate::{models::AccountStatus, money::Money, utils::*};
+    use chrono::{Duration, Utc};
+
+    fn payload_for(username: &str, role: Role) -> Payload {
+        let maker = TokenMaker::new(&random_string(32)).unwrap();
+        let (_, payload) = maker
+            .create_token(username, role, TokenType::Access, Duration::minutes(1))
+            .unwrap();
+        payload
+    }
+
+    fn account_for(owner: &str) -> Account {
+        let currency = random_currency();
+        Account {
+            id: 1,
+            owner: owner.to_string(),
+            balance: Money::new(random_money(), currency),
+            currency,
+            created_at: Utc::now(),
+            status: AccountStatus::Active,
+            overdraft_limit: Money::zero(currency),
+        }
+    }
+
+    #[test]
+    fn test_authorize_account_owner() {
+        let account = account_for(&random_owner());
+
+        let owner = payload_for(&account.owner, Role::Depositor);
+        assert!(authorize_account_owner(&owner, &account).is_ok());
+
+        let other = payload_for(&random_owner(), Role::Depositor);
+        let err = authorize_account_owner(&other, &account).unwrap_err();
+        assert_eq!(err.client_error(), ClientError::Forbidden);
+
+        let banker = payload_for(&random_owner(), Role::Banker);
+        let err = authorize_account_owner(&banker, &account).unwrap_err();
+        assert_eq!(err.client_error(), ClientError::Forbidden);
+    }
+
+    #[test]
+    fn test_authorize_account_view() {
+        let account = account_for(&random_owner());
+
+        let owner = payload_for(&account.owner, Role::Depositor);
+        assert!(authorize_account_view(&owner, &account).is_ok());
+
+        let banker = payload_for(&random_owner(), Role::Banker);
+        assert!(authorize_account_view(&banker, &account).is_ok());
+
+        let other = payload_for(&random_owner(), Role::Depositor);
+        let err = authorize_account_view(&other, &account).unwrap_err();
+        assert_eq!(err.client_error(), ClientError::Forbidden);
+    }
+}
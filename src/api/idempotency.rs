@@ -0,0 +1,300 @@
+use super::{auth::AuthPayload, state::AppState};
+use crate::{
+    db::{
+        idempotency_sql::{CompleteIdempotencyKeyParams, CreateIdempotencyKeyParams},
+        store::Store,
+    },
+    models::IdempotencyKey,
+    prelude::*,
+};
+use axum::{
+    body::{to_bytes, Body, Bytes},
+    extract::{Request, State},
+    http::{header::CONTENT_TYPE, HeaderName, HeaderValue, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use sha2::{Digest, Sha256};
+use std::sync::Arc;
+
+pub static IDEMPOTENCY_KEY: HeaderName = HeaderName::from_static("idempotency-key");
+pub static IDEMPOTENT_REPLAYED: HeaderName = HeaderName::from_static("idempotent-replayed");
+
+const MAX_KEY_LENGTH: usize = 255;
+const MAX_BODY_BYTES: usize = 1024 * 1024;
+
+/// Makes a POST endpoint safe to retry. The first request carrying an
+/// `Idempotency-Key` claims the key for the caller and route; its response is
+/// stored and replayed verbatim to later requests with the same key and body
+/// until the key expires. Reusing a key for a different body, or while the
+/// first request is still running, is rejected with a 409. A request that is
+/// cancelled before its handler returns, e.g. by the request timeout, releases
+/// the key so it can be retried.
+///
+/// Requests without the header, or without a valid access token (the handler
+/// rejects those anyway), pass straight through.
+pub async fn idempotency<S: Store>(
+    State(state): State<AppState<S>>,
+    auth: ServerResult<AuthPayload>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let Some(key) = req.headers().get(&IDEMPOTENCY_KEY) else {
+        return next.run(req).await;
+    };
+    let Ok(AuthPayload(auth)) = auth else {
+        return next.run(req).await;
+    };
+    let key = match key.to_str() {
+        Ok(key) if !key.is_empty() && key.len() <= MAX_KEY_LENGTH => key.to_string(),
+        _ => {
+            return ServerError::validation(
+                "Idempotency-Key",
+                format!("must be 1 to {MAX_KEY_LENGTH} visible ASCII characters"),
+            )
+            .into_response()
+        }
+    };
+    let scope = format!("{} {} {}", auth.username, req.method(), req.uri().path());
+
+    let (parts, body) = req.into_parts();
+    let body = match to_bytes(body, MAX_BODY_BYTES).await {
+        Ok(body) => body,
+        Err(_) => return ClientError::BadRequest.into_response(),
+    };
+    let request_hash = format!("{:x}", Sha256::digest(&body));
+
+    match claim_key(&state, &scope, &key, &request_hash).await {
+        Ok(None) => {}
+        Ok(Some(existing)) => return replay(existing, &request_hash),
+        Err(err) => return err.into_response(),
+    }
+
+    let claim = Claim {
+        store: state.store.clone(),
+        scope,
+        key,
+        released: false,
+    };
+    let res = next.run(Request::from_parts(parts, Body::from(body))).await;
+    let (scope, key) = claim.keep();
+    store_response(&state, scope, key, res).await
+}
+
+/// A claimed key whose request is still running. Dropped before the handler
+/// returns, it releases the key so the request can be retried; a transaction
+/// the handler had not committed yet is rolled back along with it.
+struct Claim<S: Store> {
+    store: Arc<S>,
+    scope: String,
+    key: String,
+    released: bool,
+}
+
+impl<S: Store> Claim<S> {
+    /// The handler returned; its response decides what happens to the key.
+    fn keep(mut self) -> (String, String) {
+        self.released = true;
+        (
+            std::mem::take(&mut self.scope),
+            std::mem::take(&mut self.key),
+        )
+    }
+}
+
+impl<S: Store> Drop for Claim<S> {
+    fn drop(&mut self) {
+        if self.released {
+            return;
+        }
+        let store = self.store.clone();
+        let scope = std::mem::take(&mut self.scope);
+        let key = std::mem::take(&mut self.key);
+        tokio::spawn(async move {
+            if let Err(err) = store.delete_idempotency_key(&scope, &key).await {
+                tracing::error!(error = %err, "failed to release idempotency key");
+            } else {
+                tracing::info!("released idempotency key of a cancelled request");
+            }
+        });
+    }
+}
+
+/// Returns `None` once the key is claimed for this request, or the row of the
+/// earlier request that holds it.
+async fn claim_key<S: Store>(
+    state: &AppState<S>,
+    scope: &str,
+    key: &str,
+    request_hash: &str,
+) -> ServerResult<Option<IdempotencyKey>> {
+    let params = CreateIdempotencyKeyParams {
+        scope: scope.to_string(),
+        key: key.to_string(),
+        request_hash: request_hash.to_string(),
+    };
+    if state
+        .store
+        .create_idempotency_key(params.clone())
+        .await?
+        .is_some()
+    {
+        return Ok(None);
+    }
+
+    // An expired key is released and claimed afresh.
+    let expired_before = Utc::now() - state.idempotency_key_ttl;
+    if state
+        .store
+        .delete_expired_idempotency_key(scope, key, expired_before)
+        .await?
+        && state.store.create_idempotency_key(params).await?.is_some()
+    {
+        return Ok(None);
+    }
+
+    Ok(Some(state.store.get_idempotency_key(scope, key).await?))
+}
+
+fn replay(existing: IdempotencyKey, request_hash: &str) -> Response {
+    if existing.request_hash != request_hash {
+        return ClientError::IdempotencyKeyReused.into_response();
+    }
+    let (Some(status), Some(body)) = (existing.response_status, existing.response_body) else {
+        return ClientError::IdempotencyKeyInProgress.into_response();
+    };
+
+    let status = u16::try_from(status)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+    let mut res = (status, body).into_response();
+    if let Some(content_type) = existing
+        .response_content_type
+        .and_then(|content_type| HeaderValue::from_str(&content_type).ok())
+    {
+        res.headers_mut().insert(CONTENT_TYPE, content_type);
+    }
+    res.headers_mut().insert(
+        IDEMPOTENT_REPLAYED.clone(),
+        HeaderValue::from_static("true"),
+    );
+    res
+}
+
+/// Persists a final response for replay. Server errors and transaction
+/// conflicts are transient, so the key is released and the client may retry.
+///
+/// The handler has already run, so its response is returned even if it cannot
+/// be stored; the key then stays in progress and retries get a 409 rather than
+/// repeating the request.
+async fn store_response<S: Store>(
+    state: &AppState<S>,
+    scope: String,
+    key: String,
+    res: Response,
+) -> Response {
+    let transient = res.status().is_server_error()
+        || res
+            .extensions()
+            .get::<ServerError>()
+            .is_some_and(|err| err.client_error() == ClientError::TransactionConflict);
+    if transient {
+        if let Err(err) = state.store.delete_idempotency_key(&scope, &key).await {
+            tracing::error!(error = %err, "failed to release idempotency key");
+        }
+        return res;
+    }
+
+    let (parts, body) = res.into_parts();
+    let body: Bytes = match to_bytes(body, usize::MAX).await {
+        Ok(body) => body,
+        Err(err) => return ServerError::Internal(err.to_string()).into_response(),
+    };
+
+    let params = CompleteIdempotencyKeyParams {
+        scope,
+        key,
+        response_status: parts.status.as_u16() as i16,
+        response_content_type: parts
+            .headers
+            .get(CONTENT_TYPE)
+            .and_then(|value| value.to_str().ok())
+            .map(str::to_string),
+        response_body: body.to_vec(),
+    };
+    if let Err(err) = state.store.complete_idempotency_key(params).await {
+        tracing::error!(error = %err, "failed to store idempotent response");
+    }
+
+    Response::from_parts(parts, Body::from(body))
+}
+
+mod tests {
+    use super::*;
+    use crate::{models::Role, utils::*};
+    use axum::{http::Method, middleware, routing::post, Router};
+    use std::{
+        sync::atomic::{AtomicUsize, Ordering},
+        time::Duration,
+    };
+
+    #[tokio::test]
+    async fn test_cancelled_request_releases_key() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+
+        // The first call never finishes; later ones answer at once.
+        let calls = Arc::new(AtomicUsize::new(0));
+        let handler = {
+            let calls = calls.clone();
+            move || async move {
+                if calls.fetch_add(1, Ordering::SeqCst) == 0 {
+                    std::future::pending::<()>().await;
+                }
+                axum::Json("done")
+            }
+        };
+        let router = Router::new().route(
+            "/slow",
+            post(handler).layer(middleware::from_fn_with_state(
+                state.clone(),
+                idempotency::<_>,
+            )),
+        );
+        let request = || {
+            let mut req = json_request(Method::POST, "/slow", Some(&token), None);
+            req.headers_mut()
+                .insert(IDEMPOTENCY_KEY.clone(), "retry-1".parse().unwrap());
+            req
+        };
+
+        // Dropped mid-flight, as the request timeout does.
+        let cancelled =
+            tokio::time::timeout(Duration::from_millis(50), send(router.clone(), request())).await;
+        assert!(cancelled.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+
+        // The key is released in the background.
+        let scope = format!("{} POST /slow", user.username);
+        for _ in 0..100 {
+            if state
+                .store
+                .get_idempotency_key(&scope, "retry-1")
+                .await
+                .is_err()
+            {
+                break;
+            }
+            tokio::time::sleep(Duration::from_millis(5)).await;
+        }
+
+        let (status, _, _) = send(router, request()).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+}
@@ -0,0 +1,78 @@
+use crate::prelude::*;
+use axum::{
+    extract::FromRequest,
+    response::{IntoResponse, Response},
+};
+use std::ops::{Deref, DerefMut};
+
+/// `axum::Json`, except that a body which cannot be deserialized is rejected
+/// with a problem-details `ServerError` like every other API error.
+#[derive(Debug, Clone, Copy, Default, FromRequest)]
+#[from_request(via(axum::Json), rejection(ServerError))]
+pub struct Json<T>(pub T);
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+impl<T: serde::Serialize> IntoResponse for Json<T> {
+    fn into_response(self) -> Response {
+        axum::Json(self.0).into_response()
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::{api::router::routes, models::Role, utils::*};
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_rejections_are_problem_details() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+
+        let req = json_request(
+            Method::POST,
+            "/accounts",
+            Some(&token),
+            Some(json!({ "currency": "XTS" })),
+        );
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], json!("validation_failed"));
+        assert_eq!(body["errors"][0]["field"], json!("currency"));
+        assert_eq!(
+            body["errors"][0]["message"],
+            json!("unsupported currency XTS")
+        );
+
+        let req = json_request(Method::POST, "/accounts", Some(&token), Some(json!({})));
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("body"));
+        assert_eq!(
+            body["errors"][0]["message"],
+            json!("missing field `currency`")
+        );
+
+        let mut req = json_request(Method::POST, "/accounts", Some(&token), None);
+        *req.body_mut() = "{".into();
+        let (status, _, body) = send(routes(state), req).await;
+        assert_eq!(status, StatusCode::BAD_REQUEST);
+        assert_eq!(body["code"], json!("bad_request"));
+    }
+}
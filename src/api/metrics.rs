@@ -0,0 +1,84 @@
+use super::state::AppState;
+use crate::{db::store::Store, metrics::METRICS};
+use axum::{
+    extract::{MatchedPath, Request, State},
+    http::header::CONTENT_TYPE,
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use prometheus::TEXT_FORMAT;
+use std::time::Instant;
+
+/// Counts requests and records their latency by method, route and status.
+/// The route is the matched path template (`/accounts/:id`), so it must run as
+/// a route layer; unmatched requests never reach it.
+pub async fn track_metrics(req: Request, next: Next) -> Response {
+    let method = req.method().to_string();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| req.uri().path().to_string());
+    let started = Instant::now();
+
+    let res = next.run(req).await;
+
+    let status = res.status().as_u16().to_string();
+    let labels = [method.as_str(), route.as_str(), status.as_str()];
+    METRICS.http_requests_total.with_label_values(&labels).inc();
+    METRICS
+        .http_request_duration_seconds
+        .with_label_values(&labels)
+        .observe(started.elapsed().as_secs_f64());
+
+    res
+}
+
+pub async fn metrics_handler<S: Store>(State(state): State<AppState<S>>) -> impl IntoResponse {
+    if let Some(stats) = state.store.pool_stats() {
+        METRICS.observe_pool(stats);
+    }
+
+    ([(CONTENT_TYPE, TEXT_FORMAT)], METRICS.encode())
+}
+
+mod tests {
+    use super::*;
+    use crate::{api::router::routes, utils::*};
+    use axum::{
+        body::Body,
+        http::{Method, StatusCode},
+    };
+
+    #[tokio::test]
+    async fn test_metrics_handler() {
+        let state = mem_app_state();
+        let router = routes(state.clone());
+
+        let req = json_request(Method::GET, "/accounts/1", None, None);
+        let (status, _, _) = send(router.clone(), req).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let counter =
+            METRICS
+                .http_requests_total
+                .with_label_values(&["GET", "/accounts/:id", "401"]);
+        assert!(counter.get() >= 1);
+
+        let res = tower::ServiceExt::oneshot(
+            router,
+            Request::get("/metrics").body(Body::empty()).unwrap(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(res.status(), StatusCode::OK);
+        let body = axum::body::to_bytes(res.into_body(), usize::MAX)
+            .await
+            .unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(
+            r#"simplebank_http_requests_total{method="GET",route="/accounts/:id",status="401"}"#
+        ));
+        assert!(body.contains("simplebank_http_request_duration_seconds_bucket"));
+    }
+}
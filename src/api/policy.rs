@@ -0,0 +1,53 @@
+use super::auth::AuthPayload;
+use crate::{auth::token::Payload, models::Role, prelude::*};
+use axum::{
+    async_trait,
+    extract::{FromRef, FromRequestParts},
+    http::request::Parts,
+};
+use std::marker::PhantomData;
+
+/// A set of roles allowed through a `RequireRole` extractor.
+pub trait RolePolicy {
+    fn allows(role: Role) -> bool;
+}
+
+/// Bank staff only.
+pub struct Banker;
+
+impl RolePolicy for Banker {
+    fn allows(role: Role) -> bool {
+        role == Role::Banker
+    }
+}
+
+/// Authenticates the request like `AuthPayload` and additionally rejects it
+/// with a 403 unless the caller's role satisfies `P`.
+#[derive(Debug)]
+pub struct RequireRole<P> {
+    pub payload: Payload,
+    policy: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequireRole<P>
+where
+    AuthPayload: FromRequestParts<S, Rejection = ServerError>,
+    P: RolePolicy,
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> ServerResult<Self> {
+        let AuthPayload(payload) = AuthPayload::from_request_parts(parts, state).await?;
+
+        if !P::allows(payload.role) {
+            return Err(ServerError::ClientError(ClientError::Forbidden));
+        }
+
+        Ok(RequireRole {
+            payload,
+            policy: PhantomData,
+        })
+    }
+}
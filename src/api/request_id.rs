@@ -0,0 +1,92 @@
+use crate::telemetry::remote_context;
+use axum::{
+    extract::Request,
+    http::{HeaderName, HeaderValue},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use tracing::Instrument;
+use tracing_opentelemetry::OpenTelemetrySpanExt;
+use uuid::Uuid;
+
+pub static X_REQUEST_ID: HeaderName = HeaderName::from_static("x-request-id");
+
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+/// Identifies a request in logs, traces and error bodies.
+#[derive(Debug, Clone, PartialEq)]
+pub struct RequestId(pub String);
+
+/// Takes the caller's `X-Request-Id`, or generates one, and runs the request
+/// inside a span carrying it, so every log line it causes can be correlated.
+/// The id is echoed back in the `X-Request-Id` response header. A W3C
+/// `traceparent` header makes the span a child of the caller's trace.
+pub async fn request_id(mut req: Request, next: Next) -> Response {
+    let request_id = req
+        .headers()
+        .get(&X_REQUEST_ID)
+        .and_then(|value| value.to_str().ok())
+        .filter(|value| !value.is_empty() && value.len() <= MAX_REQUEST_ID_LENGTH)
+        .map(str::to_string)
+        .unwrap_or_else(|| Uuid::new_v4().to_string());
+    req.extensions_mut().insert(RequestId(request_id.clone()));
+
+    let span = tracing::info_span!(
+        "request",
+        otel.kind = "server",
+        request_id = %request_id,
+        method = %req.method(),
+        path = %req.uri().path(),
+    );
+    span.set_parent(remote_context(req.headers()));
+    let started = Instant::now();
+
+    let mut res = async move {
+        let res = next.run(req).await;
+        tracing::info!(
+            status = res.status().as_u16(),
+            latency_ms = started.elapsed().as_millis() as u64,
+            "request completed"
+        );
+        res
+    }
+    .instrument(span)
+    .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        res.headers_mut().insert(X_REQUEST_ID.clone(), value);
+    }
+    res
+}
+
+mod tests {
+    use super::*;
+    use crate::{api::router::routes, utils::*};
+    use axum::http::{Method, StatusCode};
+    use tower::ServiceExt;
+
+    #[tokio::test]
+    async fn test_request_id_is_propagated() {
+        let mut req = json_request(Method::GET, "/accounts/1", None, None);
+        req.headers_mut()
+            .insert(X_REQUEST_ID.clone(), HeaderValue::from_static("req-123"));
+
+        let (status, headers, body) = send(routes(mem_app_state()), req).await;
+
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+        assert_eq!(headers[&X_REQUEST_ID], "req-123");
+        assert_eq!(body["request_id"], "req-123");
+    }
+
+    #[tokio::test]
+    async fn test_request_id_is_generated() {
+        let req = json_request(Method::GET, "/healthz", None, None);
+
+        let (status, headers, _) = send(routes(mem_app_state()), req).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let request_id = headers[&X_REQUEST_ID].to_str().unwrap();
+        assert!(Uuid::parse_str(request_id).is_ok());
+    }
+}
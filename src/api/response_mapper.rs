@@ -0,0 +1,22 @@
+use super::request_id::RequestId;
+use crate::prelude::*;
+use axum::{
+    response::{IntoResponse, Response},
+    Extension,
+};
+
+/// For failed requests, renders the `ServerError` left in the response
+/// extensions as a problem-details body carrying the request id.
+pub async fn main_response_mapper(
+    request_id: Option<Extension<RequestId>>,
+    res: Response,
+) -> Response {
+    let Some(server_error) = res.extensions().get::<ServerError>() else {
+        return res;
+    };
+
+    let request_id = request_id.map(|Extension(RequestId(id))| id);
+    let mut mapped = server_error.to_problem(request_id).into_response();
+    mapped.extensions_mut().insert(server_error.clone());
+    mapped
+}
@@ -1,12 +1,93 @@
-use crate::handlers::account::create_account_handler;
+use super::{
+    idempotency::idempotency,
+    metrics::{metrics_handler, track_metrics},
+    request_id::request_id,
+    response_mapper::main_response_mapper,
+    state::AppState,
+};
+use crate::{
+    db::store::Store,
+    handlers::{
+        account::{
+            close_account_handler, create_account_handler, freeze_account_handler,
+            get_account_handler, list_accounts_handler, unfreeze_account_handler,
+            update_overdraft_limit_handler,
+        },
+        entry::{get_entry_handler, list_entries_handler},
+        fx::{create_exchange_rate_handler, create_fx_quote_handler, get_exchange_rate_handler},
+        health::{healthz_handler, readyz_handler, version_handler},
+        report::{balance_report_handler, transfer_volume_report_handler},
+        session::{list_sessions_handler, revoke_session_handler},
+        token::renew_access_token_handler,
+        transfer::{
+            create_transfer_handler, get_transfer_handler, list_all_transfers_handler,
+            list_transfers_handler,
+        },
+        user::{create_user_handler, login_user_handler},
+    },
+};
 use axum::{
-    routing::{get, post},
+    middleware,
+    routing::{get, post, put},
     Router,
 };
-use sqlx::PgPool;
 
-pub fn routes(pool: PgPool) -> Router {
+pub fn routes<S: Store>(state: AppState<S>) -> Router {
+    let idempotency = middleware::from_fn_with_state(state.clone(), idempotency::<S>);
+
     Router::new()
-        .route("/accounts", post(create_account_handler))
-        .with_state(pool)
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler::<S>))
+        .route("/version", get(version_handler))
+        .route("/metrics", get(metrics_handler::<S>))
+        .route("/users", post(create_user_handler::<S>))
+        .route("/users/login", post(login_user_handler::<S>))
+        .route(
+            "/tokens/renew_access",
+            post(renew_access_token_handler::<S>),
+        )
+        .route("/sessions", get(list_sessions_handler::<S>))
+        .route("/sessions/:id/revoke", post(revoke_session_handler::<S>))
+        .route(
+            "/accounts",
+            post(create_account_handler::<S>)
+                .layer(idempotency.clone())
+                .get(list_accounts_handler::<S>),
+        )
+        .route("/accounts/:id", get(get_account_handler::<S>))
+        .route("/accounts/:id/freeze", post(freeze_account_handler::<S>))
+        .route(
+            "/accounts/:id/unfreeze",
+            post(unfreeze_account_handler::<S>),
+        )
+        .route("/accounts/:id/close", post(close_account_handler::<S>))
+        .route(
+            "/accounts/:id/overdraft_limit",
+            put(update_overdraft_limit_handler::<S>),
+        )
+        .route("/accounts/:id/entries", get(list_entries_handler::<S>))
+        .route("/accounts/:id/transfers", get(list_transfers_handler::<S>))
+        .route("/entries/:id", get(get_entry_handler::<S>))
+        .route(
+            "/transfers",
+            post(create_transfer_handler::<S>)
+                .layer(idempotency)
+                .get(list_all_transfers_handler::<S>),
+        )
+        .route("/transfers/:id", get(get_transfer_handler::<S>))
+        .route("/fx/rates", post(create_exchange_rate_handler::<S>))
+        .route(
+            "/fx/rates/:base/:quote",
+            get(get_exchange_rate_handler::<S>),
+        )
+        .route("/fx/quotes", post(create_fx_quote_handler::<S>))
+        .route("/reports/balances", get(balance_report_handler::<S>))
+        .route(
+            "/reports/transfer_volume",
+            get(transfer_volume_report_handler::<S>),
+        )
+        .route_layer(middleware::from_fn(track_metrics))
+        .layer(middleware::map_response(main_response_mapper))
+        .layer(middleware::from_fn(request_id))
+        .with_state(state)
 }
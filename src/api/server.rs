@@ -1,14 +1,27 @@
-use axum::Router;
-use sqlx::PgPool;
-use tokio::net::TcpListener;
+use super::shutdown::{shutdown_signal, track_in_flight, Shutdown};
+use axum::{middleware, Router};
+use std::{future::IntoFuture, net::SocketAddr, sync::Arc, time::Duration};
+use tokio::{net::TcpListener, time::Instant};
+use tower_http::timeout::TimeoutLayer;
+
+const DEFAULT_BIND_ADDRESS: &str = "127.0.0.1:3000";
+const DEFAULT_DRAIN_TIMEOUT: Duration = Duration::from_secs(30);
 
 pub struct Server {
     listener: TcpListener,
     router: Router,
+    shutdown: Arc<Shutdown>,
+    pre_stop_delay: Duration,
+    drain_timeout: Duration,
 }
 
 pub struct ServerBuilder {
     listener: Option<TcpListener>,
+    bind_address: Option<SocketAddr>,
+    request_timeout: Option<Duration>,
+    pre_stop_delay: Option<Duration>,
+    drain_timeout: Option<Duration>,
+    shutdown: Option<Arc<Shutdown>>,
     router: Option<Router>,
 }
 
@@ -16,14 +29,85 @@ impl Server {
     pub fn builder() -> ServerBuilder {
         ServerBuilder {
             listener: None,
+            bind_address: None,
+            request_timeout: None,
+            pre_stop_delay: None,
+            drain_timeout: None,
+            shutdown: None,
             router: None,
         }
     }
 
-    pub async fn run(self) {
-        axum::serve(self.listener, self.router)
-            .await
-            .expect("Failed to start server");
+    pub fn local_addr(&self) -> SocketAddr {
+        self.listener
+            .local_addr()
+            .expect("listener has a local address")
+    }
+
+    /// Serves until SIGINT/SIGTERM or `Shutdown::begin_drain`. Readiness then
+    /// fails at once, but connections are still accepted for the pre-stop
+    /// delay so load balancers can notice. After that the listener closes and
+    /// in-flight requests get up to the drain timeout to finish; any still
+    /// running are abandoned. Returns what is left of the drain timeout.
+    pub async fn run(self) -> Duration {
+        let shutdown = self.shutdown;
+        let pre_stop_delay = self.pre_stop_delay;
+        let app = self
+            .router
+            .layer(middleware::from_fn_with_state(
+                shutdown.clone(),
+                track_in_flight,
+            ))
+            .into_make_service_with_connect_info::<SocketAddr>();
+
+        let signal = {
+            let shutdown = shutdown.clone();
+            async move {
+                tokio::select! {
+                    _ = shutdown_signal() => shutdown.begin_drain(),
+                    _ = shutdown.draining() => {},
+                }
+                if !pre_stop_delay.is_zero() {
+                    tracing::info!(
+                        ?pre_stop_delay,
+                        "not ready, waiting before closing the listener"
+                    );
+                    tokio::time::sleep(pre_stop_delay).await;
+                }
+                tracing::info!(
+                    in_flight = shutdown.in_flight(),
+                    "shutting down, draining in-flight requests"
+                );
+            }
+        };
+        let serve = axum::serve(self.listener, app)
+            .with_graceful_shutdown(signal)
+            .into_future();
+        tokio::pin!(serve);
+
+        // The server only stops by itself once draining has begun.
+        tokio::select! {
+            res = &mut serve => {
+                res.expect("Failed to start server");
+                return self.drain_timeout;
+            }
+            _ = shutdown.draining() => {}
+        }
+
+        let deadline = Instant::now() + pre_stop_delay + self.drain_timeout;
+        tokio::select! {
+            res = &mut serve => {
+                res.expect("Failed to start server");
+                tracing::info!("all requests drained");
+            }
+            _ = tokio::time::sleep_until(deadline) => {
+                tracing::warn!(
+                    in_flight = shutdown.in_flight(),
+                    "drain timeout elapsed, abandoning in-flight requests"
+                );
+            }
+        }
+        deadline.saturating_duration_since(Instant::now())
     }
 }
 
@@ -33,22 +117,174 @@ impl ServerBuilder {
         self
     }
 
+    /// Address to bind when no `listener` is given.
+    pub fn bind_address(mut self, bind_address: SocketAddr) -> Self {
+        self.bind_address = Some(bind_address);
+        self
+    }
+
+    /// Requests running longer than `timeout` are answered with 408.
+    pub fn request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = Some(timeout);
+        self
+    }
+
+    /// How long connections are still accepted once shutdown begins, while
+    /// readiness already fails. Zero by default.
+    pub fn pre_stop_delay(mut self, delay: Duration) -> Self {
+        self.pre_stop_delay = Some(delay);
+        self
+    }
+
+    /// How long in-flight requests may run once the listener has closed.
+    pub fn drain_timeout(mut self, timeout: Duration) -> Self {
+        self.drain_timeout = Some(timeout);
+        self
+    }
+
+    /// Shares the shutdown state, e.g. with readiness checks or tests.
+    pub fn shutdown(mut self, shutdown: Arc<Shutdown>) -> Self {
+        self.shutdown = Some(shutdown);
+        self
+    }
+
     pub fn router(mut self, router: Router) -> Self {
         self.router = Some(router);
         self
     }
 
     pub async fn build(self) -> Server {
-        let listener = match self.listener {
-            Some(listener) => listener,
-            None => TcpListener::bind("127.0.0.1:3000")
+        let listener = match (self.listener, self.bind_address) {
+            (Some(listener), _) => listener,
+            (None, Some(bind_address)) => TcpListener::bind(bind_address)
+                .await
+                .expect("Failed to bind to address"),
+            (None, None) => TcpListener::bind(DEFAULT_BIND_ADDRESS)
                 .await
                 .expect("Failed to bind to address"),
         };
 
+        let mut router = self.router.expect("router is required");
+        if let Some(timeout) = self.request_timeout {
+            router = router.layer(TimeoutLayer::new(timeout));
+        }
+
         Server {
             listener,
-            router: self.router.expect("router is required"),
+            router,
+            shutdown: self.shutdown.unwrap_or_else(Shutdown::new),
+            pre_stop_delay: self.pre_stop_delay.unwrap_or_default(),
+            drain_timeout: self.drain_timeout.unwrap_or(DEFAULT_DRAIN_TIMEOUT),
+        }
+    }
+}
+
+mod tests {
+    use super::*;
+    use axum::routing::get;
+    use std::time::Instant;
+    use tokio::{
+        io::{AsyncReadExt, AsyncWriteExt},
+        net::TcpStream,
+    };
+
+    async fn spawn_server(
+        delay: Duration,
+        pre_stop_delay: Duration,
+        drain_timeout: Duration,
+    ) -> (SocketAddr, Arc<Shutdown>, tokio::task::JoinHandle<Duration>) {
+        let router = Router::new().route(
+            "/slow",
+            get(move || async move {
+                tokio::time::sleep(delay).await;
+                "done"
+            }),
+        );
+        let shutdown = Shutdown::new();
+        let server = Server::builder()
+            .listener(TcpListener::bind("127.0.0.1:0").await.unwrap())
+            .router(router)
+            .shutdown(shutdown.clone())
+            .pre_stop_delay(pre_stop_delay)
+            .drain_timeout(drain_timeout)
+            .build()
+            .await;
+        let addr = server.local_addr();
+        (addr, shutdown, tokio::spawn(server.run()))
+    }
+
+    async fn start_request(addr: SocketAddr, shutdown: &Shutdown) -> TcpStream {
+        let mut stream = TcpStream::connect(addr).await.unwrap();
+        stream
+            .write_all(b"GET /slow HTTP/1.1\r\nhost: localhost\r\nconnection: close\r\n\r\n")
+            .await
+            .unwrap();
+        while shutdown.in_flight() == 0 {
+            tokio::time::sleep(Duration::from_millis(5)).await;
         }
+        stream
+    }
+
+    #[tokio::test]
+    async fn test_drain_completes_in_flight_requests() {
+        let (addr, shutdown, server) = spawn_server(
+            Duration::from_millis(200),
+            Duration::ZERO,
+            Duration::from_secs(5),
+        )
+        .await;
+        let mut stream = start_request(addr, &shutdown).await;
+
+        shutdown.begin_drain();
+        let remaining = server.await.unwrap();
+        assert!(remaining > Duration::ZERO && remaining <= Duration::from_secs(5));
+
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+        assert!(response.ends_with("done"));
+        assert_eq!(shutdown.in_flight(), 0);
+        assert!(TcpStream::connect(addr).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_drain_timeout_abandons_slow_requests() {
+        let (addr, shutdown, server) = spawn_server(
+            Duration::from_secs(60),
+            Duration::ZERO,
+            Duration::from_millis(100),
+        )
+        .await;
+        let _stream = start_request(addr, &shutdown).await;
+
+        let started = Instant::now();
+        shutdown.begin_drain();
+        let remaining = server.await.unwrap();
+
+        assert!(started.elapsed() < Duration::from_secs(5));
+        assert_eq!(remaining, Duration::ZERO);
+        assert_eq!(shutdown.in_flight(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_pre_stop_delay_keeps_accepting_connections() {
+        let (addr, shutdown, server) = spawn_server(
+            Duration::from_millis(50),
+            Duration::from_millis(300),
+            Duration::from_secs(5),
+        )
+        .await;
+
+        let started = Instant::now();
+        shutdown.begin_drain();
+        // Draining has begun, but the listener stays open for the delay.
+        let mut stream = start_request(addr, &shutdown).await;
+        let mut response = String::new();
+        stream.read_to_string(&mut response).await.unwrap();
+        assert!(response.starts_with("HTTP/1.1 200 OK"));
+
+        server.await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(300));
+        assert!(TcpStream::connect(addr).await.is_err());
     }
 }
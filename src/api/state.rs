@@ -0,0 +1,45 @@
+use super::shutdown::Shutdown;
+use crate::{auth::token::TokenMaker, db::store::Store};
+use axum::extract::FromRef;
+use chrono::Duration;
+use std::sync::Arc;
+
+pub struct AppState<S> {
+    pub store: Arc<S>,
+    pub token_maker: Arc<TokenMaker>,
+    pub access_token_duration: Duration,
+    pub refresh_token_duration: Duration,
+    /// How long a stored `Idempotency-Key` response is replayed.
+    pub idempotency_key_ttl: Duration,
+    /// How long a quote from `POST /fx/quotes` locks its exchange rate.
+    pub fx_quote_ttl: Duration,
+    /// Shared with the server so readiness fails once draining starts.
+    pub shutdown: Arc<Shutdown>,
+}
+
+// Not derived: the derive would require `S: Clone`, but only the `Arc` is cloned.
+impl<S> Clone for AppState<S> {
+    fn clone(&self) -> Self {
+        Self {
+            store: self.store.clone(),
+            token_maker: self.token_maker.clone(),
+            access_token_duration: self.access_token_duration,
+            refresh_token_duration: self.refresh_token_duration,
+            idempotency_key_ttl: self.idempotency_key_ttl,
+            fx_quote_ttl: self.fx_quote_ttl,
+            shutdown: self.shutdown.clone(),
+        }
+    }
+}
+
+impl<S: Store> FromRef<AppState<S>> for Arc<S> {
+    fn from_ref(state: &AppState<S>) -> Self {
+        state.store.clone()
+    }
+}
+
+impl<S> FromRef<AppState<S>> for Arc<TokenMaker> {
+    fn from_ref(state: &AppState<S>) -> Self {
+        state.token_maker.clone()
+    }
+}
@@ -0,0 +1,49 @@
+use crate::prelude::*;
+use argon2::{
+    password_hash::{rand_core::OsRng, PasswordHash, PasswordHasher, PasswordVerifier, SaltString},
+    Argon2,
+};
+
+/// Hashes `password` with Argon2id and a random salt, returning the PHC string.
+pub fn hash_password(password: &str) -> Result<String> {
+    let salt = SaltString::generate(&mut OsRng);
+    let hashed_password = Argon2::default()
+        .hash_password(password.as_bytes(), &salt)?
+        .to_string();
+    Ok(hashed_password)
+}
+
+/// Returns `false` for a wrong password as well as for a malformed hash.
+pub fn verify_password(password: &str, hashed_password: &str) -> bool {
+    match PasswordHash::new(hashed_password) {
+        Ok(parsed) => Argon2::default()
+            .verify_password(password.as_bytes(), &parsed)
+            .is_ok(),
+        Err(_) => false,
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::utils::random_string;
+
+    #[test]
+    fn test_password() {
+        let password = random_string(6);
+
+        let hashed_password = hash_password(&password).unwrap();
+        assert_ne!(hashed_password, password);
+        assert!(verify_password(&password, &hashed_password));
+
+        let wrong_password = random_string(7);
+        assert!(!verify_password(&wrong_password, &hashed_password));
+
+        let hashed_password2 = hash_password(&password).unwrap();
+        assert_ne!(hashed_password, hashed_password2);
+    }
+
+    #[test]
+    fn test_verify_malformed_hash() {
+        assert!(!verify_password("secret", ""));
+    }
+}
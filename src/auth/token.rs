@@ -0,0 +1,220 @@
+use crate::{models::Role, prelude::*};
+use chrono::{DateTime, Duration, Utc};
+use jsonwebtoken::{errors::ErrorKind, Algorithm, DecodingKey, EncodingKey, Header, Validation};
+use serde::{Deserialize, Serialize};
+use uuid::Uuid;
+
+const MIN_SECRET_KEY_SIZE: usize = 32;
+
+/// Access tokens authenticate requests; refresh tokens only renew access
+/// tokens. The type is signed into the token so one cannot stand in for the other.
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TokenType {
+    Access,
+    Refresh,
+}
+
+/// Claims carried by a token.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Payload {
+    #[serde(rename = "jti")]
+    pub id: Uuid,
+    #[serde(rename = "sub")]
+    pub username: String,
+    pub role: Role,
+    #[serde(rename = "typ")]
+    pub token_type: TokenType,
+    #[serde(rename = "iat", with = "chrono::serde::ts_seconds")]
+    pub issued_at: DateTime<Utc>,
+    #[serde(rename = "exp", with = "chrono::serde::ts_seconds")]
+    pub expired_at: DateTime<Utc>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum TokenError {
+    Expired,
+    Invalid,
+}
+
+impl TokenError {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            TokenError::Expired => "token has expired",
+            TokenError::Invalid => "token is invalid",
+        }
+    }
+}
+
+impl core::fmt::Display for TokenError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{}", self.as_str())
+    }
+}
+
+impl std::error::Error for TokenError {}
+
+/// Issues and verifies HS256-signed JWT access tokens.
+pub struct TokenMaker {
+    encoding_key: EncodingKey,
+    decoding_key: DecodingKey,
+    validation: Validation,
+}
+
+impl TokenMaker {
+    pub fn new(secret_key: &str) -> Result<Self> {
+        if secret_key.len() < MIN_SECRET_KEY_SIZE {
+            return Err(format!(
+                "invalid key size: must be at least {MIN_SECRET_KEY_SIZE} characters"
+            )
+            .into());
+        }
+
+        let mut validation = Validation::new(Algorithm::HS256);
+        validation.leeway = 0;
+
+        Ok(Self {
+            encoding_key: EncodingKey::from_secret(secret_key.as_bytes()),
+            decoding_key: DecodingKey::from_secret(secret_key.as_bytes()),
+            validation,
+        })
+    }
+
+    pub fn create_token(
+        &self,
+        username: &str,
+        role: Role,
+        token_type: TokenType,
+        duration: Duration,
+    ) -> Result<(String, Payload)> {
+        let issued_at = Utc::now();
+        let payload = Payload {
+            id: Uuid::new_v4(),
+            username: username.to_string(),
+            role,
+            token_type,
+            issued_at,
+            expired_at: issued_at + duration,
+        };
+
+        let token = jsonwebtoken::encode(&Header::default(), &payload, &self.encoding_key)?;
+        Ok((token, payload))
+    }
+
+    pub fn verify_token(
+        &self,
+        token: &str,
+        token_type: TokenType,
+    ) -> std::result::Result<Payload, TokenError> {
+        let payload = jsonwebtoken::decode::<Payload>(token, &self.decoding_key, &self.validation)
+            .map(|data| data.claims)
+            .map_err(|err| match err.kind() {
+                ErrorKind::ExpiredSignature => TokenError::Expired,
+                _ => TokenError::Invalid,
+            })?;
+
+        if payload.token_type != token_type {
+            return Err(TokenError::Invalid);
+        }
+        Ok(payload)
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::utils::{random_owner, random_string};
+
+    #[test]
+    fn test_token_maker() {
+        let maker = TokenMaker::new(&random_string(32)).unwrap();
+        let username = random_owner();
+        let duration = Duration::minutes(1);
+
+        let (token, payload) = maker
+            .create_token(&username, Role::Depositor, TokenType::Access, duration)
+            .unwrap();
+        assert!(!token.is_empty());
+
+        let verified = maker.verify_token(&token, TokenType::Access).unwrap();
+        assert_eq!(verified.token_type, TokenType::Access);
+        assert_eq!(verified.id, payload.id);
+        assert_eq!(verified.username, username);
+        assert_eq!(verified.role, Role::Depositor);
+        assert_eq!(
+            verified.issued_at.timestamp(),
+            payload.issued_at.timestamp()
+        );
+        assert_eq!(
+            verified.expired_at.timestamp(),
+            payload.expired_at.timestamp()
+        );
+    }
+
+    #[test]
+    fn test_expired_token() {
+        let maker = TokenMaker::new(&random_string(32)).unwrap();
+
+        let (token, _) = maker
+            .create_token(
+                &random_owner(),
+                Role::Depositor,
+                TokenType::Access,
+                -Duration::minutes(1),
+            )
+            .unwrap();
+
+        assert_eq!(
+            maker.verify_token(&token, TokenType::Access),
+            Err(TokenError::Expired)
+        );
+    }
+
+    #[test]
+    fn test_invalid_token() {
+        let maker = TokenMaker::new(&random_string(32)).unwrap();
+        let other = TokenMaker::new(&random_string(32)).unwrap();
+
+        let (token, _) = other
+            .create_token(
+                &random_owner(),
+                Role::Depositor,
+                TokenType::Access,
+                Duration::minutes(1),
+            )
+            .unwrap();
+
+        assert_eq!(
+            maker.verify_token(&token, TokenType::Access),
+            Err(TokenError::Invalid)
+        );
+        assert_eq!(
+            maker.verify_token("not-a-token", TokenType::Access),
+            Err(TokenError::Invalid)
+        );
+    }
+
+    #[test]
+    fn test_token_type_mismatch() {
+        let maker = TokenMaker::new(&random_string(32)).unwrap();
+
+        let (token, _) = maker
+            .create_token(
+                &random_owner(),
+                Role::Depositor,
+                TokenType::Refresh,
+                Duration::minutes(1),
+            )
+            .unwrap();
+
+        assert_eq!(
+            maker.verify_token(&token, TokenType::Access),
+            Err(TokenError::Invalid)
+        );
+        assert!(maker.verify_token(&token, TokenType::Refresh).is_ok());
+    }
+
+    #[test]
+    fn test_short_secret_key() {
+        assert!(TokenMaker::new(&random_string(31)).is_err());
+    }
+}
@@ -0,0 +1,97 @@
+use std::path::PathBuf;
+
+pub const USAGE: &str = "\
+Usage:
+    simplebank [--config <file>] [serve] [--migrate]  Start the API server; --migrate first applies pending migrations
+    simplebank [--config <file>] migrate up           Apply pending migrations
+    simplebank [--config <file>] migrate down         Revert the newest applied migration
+    simplebank [--config <file>] migrate status       List migrations and whether they are applied
+
+Settings are read from the TOML --config file, if any, then overridden by environment variables.";
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Cli {
+    pub config: Option<PathBuf>,
+    pub command: Command,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Command {
+    Serve { migrate: bool },
+    Migrate(MigrateCommand),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum MigrateCommand {
+    Up,
+    Down,
+    Status,
+}
+
+/// Parses the command line, without the program name.
+pub fn parse<I>(args: I) -> Result<Cli, String>
+where
+    I: IntoIterator,
+    I::Item: AsRef<str>,
+{
+    let args: Vec<I::Item> = args.into_iter().collect();
+    let mut args: Vec<&str> = args.iter().map(AsRef::as_ref).collect();
+
+    let config = match args.iter().position(|arg| *arg == "--config") {
+        Some(i) if i + 1 < args.len() => {
+            let path = PathBuf::from(args[i + 1]);
+            args.drain(i..=i + 1);
+            Some(path)
+        }
+        Some(_) => return Err("--config expects a file path".to_string()),
+        None => None,
+    };
+
+    Ok(Cli {
+        config,
+        command: parse_command(&args)?,
+    })
+}
+
+fn parse_command(args: &[&str]) -> Result<Command, String> {
+    match args {
+        [] | ["serve"] => Ok(Command::Serve { migrate: false }),
+        ["--migrate"] | ["serve", "--migrate"] => Ok(Command::Serve { migrate: true }),
+        ["migrate", "up"] => Ok(Command::Migrate(MigrateCommand::Up)),
+        ["migrate", "down"] => Ok(Command::Migrate(MigrateCommand::Down)),
+        ["migrate", "status"] => Ok(Command::Migrate(MigrateCommand::Status)),
+        ["migrate", ..] => Err("migrate expects one of: up, down, status".to_string()),
+        _ => Err(format!("unrecognized arguments: {}", args.join(" "))),
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse() {
+        let no_args: [&str; 0] = [];
+        assert_eq!(
+            parse(no_args),
+            Ok(Cli {
+                config: None,
+                command: Command::Serve { migrate: false }
+            })
+        );
+        assert_eq!(
+            parse(["serve", "--migrate"]).unwrap().command,
+            Command::Serve { migrate: true }
+        );
+        assert_eq!(
+            parse(["--config", "bank.toml", "migrate", "status"]),
+            Ok(Cli {
+                config: Some(PathBuf::from("bank.toml")),
+                command: Command::Migrate(MigrateCommand::Status)
+            })
+        );
+        assert!(parse(["migrate", "up", "--config"]).is_err());
+        assert!(parse(["migrate"]).is_err());
+        assert!(parse(["migrate", "sideways"]).is_err());
+        assert!(parse(["serve", "--verbose"]).is_err());
+    }
+}
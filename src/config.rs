@@ -0,0 +1,611 @@
+use crate::db::IsolationLevel;
+use serde::Deserialize;
+use std::{net::SocketAddr, path::Path, str::FromStr, time::Duration};
+use tracing_subscriber::EnvFilter;
+
+/// Minimum length of `token.symmetric_key`, as required by `TokenMaker`.
+const MIN_SYMMETRIC_KEY_LENGTH: usize = 32;
+/// Upper bound of token lifetimes and other TTLs: one year.
+const MAX_TTL_SECS: i64 = 365 * 24 * 60 * 60;
+/// Upper bound of request and shutdown timeouts: one hour.
+const MAX_TIMEOUT_SECS: u64 = 60 * 60;
+
+/// Runtime configuration. Loaded from an optional TOML file, then overridden
+/// by environment variables, then validated as a whole.
+#[derive(Debug, Clone, Default, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub database: DatabaseConfig,
+    pub token: TokenConfig,
+    pub log: LogConfig,
+    pub telemetry: TelemetryConfig,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct ServerConfig {
+    pub bind_address: SocketAddr,
+    pub request_timeout_secs: u64,
+    /// How long a stored `Idempotency-Key` response is replayed.
+    pub idempotency_key_ttl_secs: i64,
+    /// How long a quote from `POST /fx/quotes` locks its exchange rate.
+    pub fx_quote_ttl_secs: i64,
+    /// How long connections are still accepted after a shutdown signal, with
+    /// /readyz already failing, so load balancers can stop routing here.
+    pub shutdown_pre_stop_delay_secs: u64,
+    /// How long in-flight requests may run once the listener has closed,
+    /// including closing the database pool.
+    pub shutdown_drain_timeout_secs: u64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct DatabaseConfig {
+    pub url: String,
+    pub max_connections: u32,
+    pub acquire_timeout_secs: u64,
+    pub isolation_level: IsolationLevel,
+    /// Retries of transactions that fail with a serialization failure or deadlock.
+    pub max_tx_retries: u32,
+    /// Apply pending migrations before the server starts.
+    pub run_migrations: bool,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TokenConfig {
+    pub symmetric_key: String,
+    pub access_token_duration_secs: i64,
+    pub refresh_token_duration_secs: i64,
+}
+
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct LogConfig {
+    pub format: LogFormat,
+    /// `tracing_subscriber::EnvFilter` directives, e.g. `info,simplebank=debug`.
+    pub filter: String,
+}
+
+/// OpenTelemetry trace export over OTLP/HTTP.
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct TelemetryConfig {
+    /// Collector base URL, e.g. `http://localhost:4318`; empty disables export.
+    pub otlp_endpoint: String,
+    /// Fraction of new traces to sample, from 0.0 to 1.0. Requests that carry
+    /// a `traceparent` follow the caller's sampling decision.
+    pub sampling_ratio: f64,
+    pub service_name: String,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    Text,
+    #[default]
+    Json,
+}
+
+#[derive(Debug)]
+pub enum ConfigError {
+    Read { path: String, message: String },
+    Parse { path: String, message: String },
+    Env { var: &'static str, message: String },
+    Invalid(Vec<String>),
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            bind_address: SocketAddr::from(([127, 0, 0, 1], 3000)),
+            request_timeout_secs: 30,
+            idempotency_key_ttl_secs: 24 * 60 * 60,
+            fx_quote_ttl_secs: 30,
+            shutdown_pre_stop_delay_secs: 5,
+            shutdown_drain_timeout_secs: 30,
+        }
+    }
+}
+
+impl Default for DatabaseConfig {
+    fn default() -> Self {
+        Self {
+            url: String::new(),
+            max_connections: 10,
+            acquire_timeout_secs: 30,
+            isolation_level: IsolationLevel::default(),
+            max_tx_retries: 5,
+            run_migrations: false,
+        }
+    }
+}
+
+impl Default for TokenConfig {
+    fn default() -> Self {
+        Self {
+            symmetric_key: String::new(),
+            access_token_duration_secs: 15 * 60,
+            refresh_token_duration_secs: 24 * 60 * 60,
+        }
+    }
+}
+
+impl Default for LogConfig {
+    fn default() -> Self {
+        Self {
+            format: LogFormat::default(),
+            filter: "info".to_string(),
+        }
+    }
+}
+
+impl Default for TelemetryConfig {
+    fn default() -> Self {
+        Self {
+            otlp_endpoint: String::new(),
+            sampling_ratio: 1.0,
+            service_name: env!("CARGO_PKG_NAME").to_string(),
+        }
+    }
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format \"{s}\", expected text or json")),
+        }
+    }
+}
+
+impl Config {
+    /// Reads `path` if given, applies environment overrides and validates the result.
+    pub fn load(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let config = Config::read(path)?;
+        config.validate()?;
+        Ok(config)
+    }
+
+    /// Like `load`, but only validates `[database]`, which is all migrations
+    /// need; server-only settings such as the token key may be missing.
+    pub fn load_database(path: Option<&Path>) -> Result<DatabaseConfig, ConfigError> {
+        let config = Config::read(path)?;
+        let mut errors = vec![];
+        config.database.validate(&mut errors);
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        Ok(config.database)
+    }
+
+    fn read(path: Option<&Path>) -> Result<Config, ConfigError> {
+        let mut config = match path {
+            Some(path) => Config::from_file(path)?,
+            None => Config::default(),
+        };
+        config.apply_env(|var| std::env::var(var).ok())?;
+        Ok(config)
+    }
+
+    pub fn from_file(path: &Path) -> Result<Config, ConfigError> {
+        let display = path.display().to_string();
+        let contents = std::fs::read_to_string(path).map_err(|err| ConfigError::Read {
+            path: display.clone(),
+            message: err.to_string(),
+        })?;
+        toml::from_str(&contents).map_err(|err| ConfigError::Parse {
+            path: display,
+            message: err.message().to_string(),
+        })
+    }
+
+    /// Overrides settings from the environment variables that `lookup` finds.
+    pub fn apply_env(
+        &mut self,
+        lookup: impl Fn(&str) -> Option<String>,
+    ) -> Result<(), ConfigError> {
+        fn set<T: FromStr>(
+            lookup: &impl Fn(&str) -> Option<String>,
+            var: &'static str,
+            target: &mut T,
+        ) -> Result<(), ConfigError>
+        where
+            T::Err: std::fmt::Display,
+        {
+            if let Some(value) = lookup(var) {
+                *target = value
+                    .trim()
+                    .parse()
+                    .map_err(|err: T::Err| ConfigError::Env {
+                        var,
+                        message: err.to_string(),
+                    })?;
+            }
+            Ok(())
+        }
+
+        set(&lookup, "BIND_ADDRESS", &mut self.server.bind_address)?;
+        set(
+            &lookup,
+            "REQUEST_TIMEOUT_SECS",
+            &mut self.server.request_timeout_secs,
+        )?;
+        set(
+            &lookup,
+            "IDEMPOTENCY_KEY_TTL_SECS",
+            &mut self.server.idempotency_key_ttl_secs,
+        )?;
+        set(
+            &lookup,
+            "FX_QUOTE_TTL_SECS",
+            &mut self.server.fx_quote_ttl_secs,
+        )?;
+        set(
+            &lookup,
+            "SHUTDOWN_PRE_STOP_DELAY_SECS",
+            &mut self.server.shutdown_pre_stop_delay_secs,
+        )?;
+        set(
+            &lookup,
+            "SHUTDOWN_DRAIN_TIMEOUT_SECS",
+            &mut self.server.shutdown_drain_timeout_secs,
+        )?;
+        set(&lookup, "DATABASE_URL", &mut self.database.url)?;
+        set(
+            &lookup,
+            "DB_MAX_CONNECTIONS",
+            &mut self.database.max_connections,
+        )?;
+        set(
+            &lookup,
+            "DB_ACQUIRE_TIMEOUT_SECS",
+            &mut self.database.acquire_timeout_secs,
+        )?;
+        set(
+            &lookup,
+            "DB_ISOLATION_LEVEL",
+            &mut self.database.isolation_level,
+        )?;
+        set(
+            &lookup,
+            "DB_MAX_TX_RETRIES",
+            &mut self.database.max_tx_retries,
+        )?;
+        set(&lookup, "RUN_MIGRATIONS", &mut self.database.run_migrations)?;
+        set(
+            &lookup,
+            "TOKEN_SYMMETRIC_KEY",
+            &mut self.token.symmetric_key,
+        )?;
+        set(
+            &lookup,
+            "ACCESS_TOKEN_DURATION_SECS",
+            &mut self.token.access_token_duration_secs,
+        )?;
+        set(
+            &lookup,
+            "REFRESH_TOKEN_DURATION_SECS",
+            &mut self.token.refresh_token_duration_secs,
+        )?;
+        set(&lookup, "LOG_FORMAT", &mut self.log.format)?;
+        set(&lookup, "RUST_LOG", &mut self.log.filter)?;
+        set(
+            &lookup,
+            "OTEL_EXPORTER_OTLP_ENDPOINT",
+            &mut self.telemetry.otlp_endpoint,
+        )?;
+        set(
+            &lookup,
+            "OTEL_TRACES_SAMPLER_ARG",
+            &mut self.telemetry.sampling_ratio,
+        )?;
+        set(
+            &lookup,
+            "OTEL_SERVICE_NAME",
+            &mut self.telemetry.service_name,
+        )?;
+        Ok(())
+    }
+
+    /// Checks every setting and reports all problems at once.
+    pub fn validate(&self) -> Result<(), ConfigError> {
+        let mut errors = vec![];
+
+        check_range(
+            &mut errors,
+            "server.request_timeout_secs",
+            self.server.request_timeout_secs,
+            1..=MAX_TIMEOUT_SECS,
+        );
+        check_range(
+            &mut errors,
+            "server.idempotency_key_ttl_secs",
+            self.server.idempotency_key_ttl_secs,
+            1..=MAX_TTL_SECS,
+        );
+        check_range(
+            &mut errors,
+            "server.fx_quote_ttl_secs",
+            self.server.fx_quote_ttl_secs,
+            1..=MAX_TTL_SECS,
+        );
+        check_range(
+            &mut errors,
+            "server.shutdown_pre_stop_delay_secs",
+            self.server.shutdown_pre_stop_delay_secs,
+            0..=MAX_TIMEOUT_SECS,
+        );
+        check_range(
+            &mut errors,
+            "server.shutdown_drain_timeout_secs",
+            self.server.shutdown_drain_timeout_secs,
+            1..=MAX_TIMEOUT_SECS,
+        );
+
+        self.database.validate(&mut errors);
+
+        if self.token.symmetric_key.len() < MIN_SYMMETRIC_KEY_LENGTH {
+            errors.push(format!(
+                "token.symmetric_key (TOKEN_SYMMETRIC_KEY) must be at least {MIN_SYMMETRIC_KEY_LENGTH} characters"
+            ));
+        }
+        check_range(
+            &mut errors,
+            "token.access_token_duration_secs",
+            self.token.access_token_duration_secs,
+            1..=MAX_TTL_SECS,
+        );
+        check_range(
+            &mut errors,
+            "token.refresh_token_duration_secs",
+            self.token.refresh_token_duration_secs,
+            1..=MAX_TTL_SECS,
+        );
+        if self.token.refresh_token_duration_secs <= self.token.access_token_duration_secs {
+            errors.push(
+                "token.refresh_token_duration_secs must be longer than token.access_token_duration_secs"
+                    .to_string(),
+            );
+        }
+
+        if let Err(err) = EnvFilter::try_new(&self.log.filter) {
+            errors.push(format!("log.filter (RUST_LOG) is invalid: {err}"));
+        }
+
+        if !self.telemetry.otlp_endpoint.is_empty()
+            && !self.telemetry.otlp_endpoint.starts_with("http://")
+            && !self.telemetry.otlp_endpoint.starts_with("https://")
+        {
+            errors.push("telemetry.otlp_endpoint must be an http:// or https:// URL".to_string());
+        }
+        if !(0.0..=1.0).contains(&self.telemetry.sampling_ratio) {
+            errors.push("telemetry.sampling_ratio must be between 0.0 and 1.0".to_string());
+        }
+        if self.telemetry.service_name.is_empty() {
+            errors.push("telemetry.service_name must not be empty".to_string());
+        }
+
+        if !errors.is_empty() {
+            return Err(ConfigError::Invalid(errors));
+        }
+        Ok(())
+    }
+}
+
+/// Reports `value` unless it lies in `range`. Larger durations would overflow
+/// `chrono::Duration` or date arithmetic at runtime.
+fn check_range<T>(
+    errors: &mut Vec<String>,
+    name: &str,
+    value: T,
+    range: std::ops::RangeInclusive<T>,
+) where
+    T: PartialOrd + std::fmt::Display,
+{
+    if !range.contains(&value) {
+        errors.push(format!(
+            "{name} must be between {} and {}",
+            range.start(),
+            range.end()
+        ));
+    }
+}
+
+impl ServerConfig {
+    pub fn request_timeout(&self) -> Duration {
+        Duration::from_secs(self.request_timeout_secs)
+    }
+
+    pub fn shutdown_pre_stop_delay(&self) -> Duration {
+        Duration::from_secs(self.shutdown_pre_stop_delay_secs)
+    }
+
+    pub fn shutdown_drain_timeout(&self) -> Duration {
+        Duration::from_secs(self.shutdown_drain_timeout_secs)
+    }
+}
+
+impl DatabaseConfig {
+    pub fn acquire_timeout(&self) -> Duration {
+        Duration::from_secs(self.acquire_timeout_secs)
+    }
+
+    fn validate(&self, errors: &mut Vec<String>) {
+        if self.url.is_empty() {
+            errors.push("database.url (DATABASE_URL) must be set".to_string());
+        } else if !self.url.starts_with("postgres://") && !self.url.starts_with("postgresql://") {
+            errors.push("database.url must be a postgres:// or postgresql:// URL".to_string());
+        }
+        if self.max_connections == 0 {
+            errors.push("database.max_connections must be at least 1".to_string());
+        }
+        if self.acquire_timeout_secs == 0 {
+            errors.push("database.acquire_timeout_secs must be positive".to_string());
+        }
+    }
+}
+
+impl core::fmt::Display for ConfigError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        match self {
+            ConfigError::Read { path, message } => {
+                write!(fmt, "cannot read config file {path}: {message}")
+            }
+            ConfigError::Parse { path, message } => {
+                write!(fmt, "invalid config file {path}: {message}")
+            }
+            ConfigError::Env { var, message } => write!(fmt, "invalid {var}: {message}"),
+            ConfigError::Invalid(errors) => {
+                write!(fmt, "invalid configuration:")?;
+                for error in errors {
+                    write!(fmt, "\n  - {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for ConfigError {}
+
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn valid_config() -> Config {
+        let mut config = Config::default();
+        config.database.url = "postgres://localhost/simplebankdb".to_string();
+        config.token.symmetric_key = "k".repeat(MIN_SYMMETRIC_KEY_LENGTH);
+        config
+    }
+
+    #[test]
+    fn test_parse_file() {
+        let config: Config = toml::from_str(
+            r#"
+            [server]
+            bind_address = "0.0.0.0:8080"
+
+            [database]
+            url = "postgres://db/simplebankdb"
+            max_connections = 20
+            isolation_level = "repeatable_read"
+
+            [log]
+            format = "text"
+            "#,
+        )
+        .unwrap();
+
+        assert_eq!(config.server.bind_address.port(), 8080);
+        assert_eq!(config.server.request_timeout_secs, 30);
+        assert_eq!(config.database.max_connections, 20);
+        assert_eq!(
+            config.database.isolation_level,
+            IsolationLevel::RepeatableRead
+        );
+        assert_eq!(config.log.format, LogFormat::Text);
+        assert_eq!(config.log.filter, "info");
+    }
+
+    #[test]
+    fn test_unknown_fields_are_rejected() {
+        let err = toml::from_str::<Config>("[database]\nmax_conections = 5\n").unwrap_err();
+
+        assert!(err.message().contains("max_conections"));
+    }
+
+    #[test]
+    fn test_env_overrides() {
+        let env = HashMap::from([
+            ("DB_MAX_CONNECTIONS", "3"),
+            ("DB_ISOLATION_LEVEL", "read_committed"),
+            ("LOG_FORMAT", "text"),
+            ("RUST_LOG", "warn,simplebank=debug"),
+        ]);
+        let mut config = valid_config();
+
+        config
+            .apply_env(|var| env.get(var).map(|value| value.to_string()))
+            .unwrap();
+
+        assert_eq!(config.database.max_connections, 3);
+        assert_eq!(
+            config.database.isolation_level,
+            IsolationLevel::ReadCommitted
+        );
+        assert_eq!(config.log.format, LogFormat::Text);
+        assert_eq!(config.log.filter, "warn,simplebank=debug");
+
+        let err = config
+            .apply_env(|var| (var == "BIND_ADDRESS").then(|| "localhost".to_string()))
+            .unwrap_err();
+        assert!(err.to_string().starts_with("invalid BIND_ADDRESS:"));
+    }
+
+    #[test]
+    fn test_validate() {
+        assert!(valid_config().validate().is_ok());
+
+        let mut config = valid_config();
+        config.database.url = String::new();
+        config.database.max_connections = 0;
+        config.token.symmetric_key = "short".to_string();
+        config.log.filter = "simplebank=loud".to_string();
+        config.telemetry.sampling_ratio = 1.5;
+
+        match config.validate() {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(errors.len(), 5),
+            other => panic!("expected invalid config, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_validate_upper_bounds() {
+        let mut config = valid_config();
+        config.server.idempotency_key_ttl_secs = i64::MAX;
+        config.server.request_timeout_secs = MAX_TIMEOUT_SECS + 1;
+        config.token.access_token_duration_secs = MAX_TTL_SECS + 1;
+        config.token.refresh_token_duration_secs = i64::MAX;
+
+        match config.validate() {
+            Err(ConfigError::Invalid(errors)) => assert_eq!(
+                errors,
+                vec![
+                    "server.request_timeout_secs must be between 1 and 3600",
+                    "server.idempotency_key_ttl_secs must be between 1 and 31536000",
+                    "token.access_token_duration_secs must be between 1 and 31536000",
+                    "token.refresh_token_duration_secs must be between 1 and 31536000",
+                ]
+            ),
+            other => panic!("expected invalid config, got {other:?}"),
+        }
+    }
+
+    #[test]
+    fn test_load_database_ignores_server_settings() {
+        let path = std::env::temp_dir().join(format!("simplebank-{}.toml", std::process::id()));
+        std::fs::write(
+            &path,
+            "[database]\nurl = \"postgres://localhost/simplebankdb\"\nmax_connections = 2\n\n[token]\naccess_token_duration_secs = 0\n",
+        )
+        .unwrap();
+
+        let database = Config::load_database(Some(&path));
+        let config = Config::load(Some(&path));
+        std::fs::remove_file(&path).unwrap();
+
+        assert_eq!(database.unwrap().max_connections, 2);
+        match config {
+            Err(ConfigError::Invalid(errors)) => assert!(errors
+                .iter()
+                .any(|error| error.starts_with("token.access_token_duration_secs"))),
+            other => panic!("expected invalid config, got {other:?}"),
+        }
+    }
+}
@@ -0,0 +1,240 @@
+use crate::models::CurrencyInfo;
+use lazy_static::lazy_static;
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use std::{collections::HashMap, fmt, str::FromStr, sync::RwLock};
+
+/// ISO 4217 code, numeric code and minor units of the currencies seeded by
+/// the `add_currencies` migration. Used until `register` loads the table.
+const ISO_4217: [(&str, i16, i16); 10] = [
+    ("AUD", 36, 2),
+    ("CAD", 124, 2),
+    ("CHF", 756, 2),
+    ("CNY", 156, 2),
+    ("EUR", 978, 2),
+    ("GBP", 826, 2),
+    ("JPY", 392, 0),
+    ("KRW", 410, 0),
+    ("KWD", 414, 3),
+    ("USD", 840, 2),
+];
+
+lazy_static! {
+    /// The `currencies` table, keyed by code.
+    static ref REGISTRY: RwLock<HashMap<Currency, CurrencyInfo>> =
+        RwLock::new(index(iso_4217()));
+}
+
+/// An ISO 4217 alphabetic currency code such as `USD`.
+///
+/// Parsing only checks the format. Deserializing a request additionally
+/// requires the currency to be known and enabled.
+#[derive(Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Currency([u8; 3]);
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum CurrencyError {
+    InvalidCode(String),
+    Unknown(Currency),
+    Disabled(Currency),
+}
+
+impl Currency {
+    pub fn as_str(&self) -> &str {
+        // Only ever built from three ASCII uppercase letters.
+        std::str::from_utf8(&self.0).expect("currency code is ASCII")
+    }
+
+    pub fn info(self) -> Option<CurrencyInfo> {
+        REGISTRY.read().unwrap().get(&self).copied()
+    }
+
+    /// Checks that the currency is in the table and enabled.
+    pub fn validate(self) -> Result<Currency, CurrencyError> {
+        match self.info() {
+            None => Err(CurrencyError::Unknown(self)),
+            Some(info) if !info.enabled => Err(CurrencyError::Disabled(self)),
+            Some(_) => Ok(self),
+        }
+    }
+
+    /// Digits after the decimal separator. Stored codes are constrained to
+    /// the table, so the ISO default of 2 only applies to unregistered codes.
+    pub fn minor_units(self) -> u32 {
+        self.info().map_or(2, |info| info.minor_units as u32)
+    }
+
+    /// Renders an amount in minor units as a decimal string, e.g. `-12.05`
+    /// for -1205 USD or `1205` for 1205 JPY.
+    pub fn format_amount(self, amount: i64) -> String {
+        let minor_units = self.minor_units();
+        if minor_units == 0 {
+            return amount.to_string();
+        }
+        let scale = 10_u64.pow(minor_units);
+        let sign = if amount < 0 { "-" } else { "" };
+        let abs = amount.unsigned_abs();
+        format!(
+            "{sign}{}.{:0width$}",
+            abs / scale,
+            abs % scale,
+            width = minor_units as usize
+        )
+    }
+}
+
+/// Replaces the registry with the rows of the `currencies` table.
+pub fn register(currencies: Vec<CurrencyInfo>) {
+    *REGISTRY.write().unwrap() = index(currencies);
+}
+
+/// Every registered currency, ordered by code.
+pub fn currencies() -> Vec<CurrencyInfo> {
+    let mut currencies: Vec<_> = REGISTRY.read().unwrap().values().copied().collect();
+    currencies.sort_by_key(|info| info.code);
+    currencies
+}
+
+/// The currencies the migration seeds, all enabled.
+pub fn iso_4217() -> Vec<CurrencyInfo> {
+    ISO_4217
+        .iter()
+        .map(|&(code, numeric_code, minor_units)| CurrencyInfo {
+            code: code.parse().expect("valid ISO 4217 code"),
+            numeric_code,
+            minor_units,
+            enabled: true,
+        })
+        .collect()
+}
+
+fn index(currencies: Vec<CurrencyInfo>) -> HashMap<Currency, CurrencyInfo> {
+    currencies
+        .into_iter()
+        .map(|info| (info.code, info))
+        .collect()
+}
+
+impl FromStr for Currency {
+    type Err = CurrencyError;
+
+    fn from_str(code: &str) -> Result<Self, Self::Err> {
+        match code.as_bytes() {
+            &[a, b, c] if code.bytes().all(|byte| byte.is_ascii_uppercase()) => {
+                Ok(Currency([a, b, c]))
+            }
+            _ => Err(CurrencyError::InvalidCode(code.to_string())),
+        }
+    }
+}
+
+impl fmt::Display for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+impl fmt::Debug for Currency {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{:?}", self.as_str())
+    }
+}
+
+impl fmt::Display for CurrencyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CurrencyError::InvalidCode(code) => {
+                write!(f, "{code:?} is not a three-letter ISO 4217 code")
+            }
+            CurrencyError::Unknown(currency) => write!(f, "unsupported currency {currency}"),
+            CurrencyError::Disabled(currency) => write!(f, "currency {currency} is disabled"),
+        }
+    }
+}
+
+impl std::error::Error for CurrencyError {}
+
+impl Serialize for Currency {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(self.as_str())
+    }
+}
+
+impl<'de> Deserialize<'de> for Currency {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let code = String::deserialize(deserializer)?;
+        code.parse::<Currency>()
+            .and_then(Currency::validate)
+            .map_err(de::Error::custom)
+    }
+}
+
+impl Type<Postgres> for Currency {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Currency {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <&str as Encode<Postgres>>::encode(self.as_str(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Currency {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn currency(code: &str) -> Currency {
+        code.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_currency() {
+        assert_eq!(currency("USD").as_str(), "USD");
+        for code in ["", "US", "usd", "USDT", "U$D"] {
+            assert_eq!(
+                code.parse::<Currency>(),
+                Err(CurrencyError::InvalidCode(code.to_string()))
+            );
+        }
+    }
+
+    #[test]
+    fn test_deserialize_currency() {
+        let usd: Currency = serde_json::from_str(r#""USD""#).unwrap();
+        assert_eq!(usd, currency("USD"));
+
+        let err = serde_json::from_str::<Currency>(r#""XTS""#).unwrap_err();
+        assert!(err.to_string().starts_with("unsupported currency XTS"));
+        let err = serde_json::from_str::<Currency>(r#""usd""#).unwrap_err();
+        assert!(err.to_string().contains("ISO 4217"));
+    }
+
+    #[test]
+    fn test_format_amount() {
+        assert_eq!(currency("USD").format_amount(0), "0.00");
+        assert_eq!(currency("USD").format_amount(1205), "12.05");
+        assert_eq!(currency("USD").format_amount(-5), "-0.05");
+        assert_eq!(currency("JPY").format_amount(1205), "1205");
+        assert_eq!(currency("KWD").format_amount(-1205), "-1.205");
+        assert_eq!(
+            currency("EUR").format_amount(i64::MIN),
+            "-92233720368547758.08"
+        );
+    }
+}
@@ -1,12 +1,27 @@
-use crate::prelude::*;
+use crate::{config::DatabaseConfig, metrics::METRICS, prelude::*};
 use futures::future::BoxFuture;
+use rand::Rng;
+use serde::Deserialize;
 use sqlx::postgres::{PgPool, PgPoolOptions};
+use std::time::Duration;
+use tracing::instrument;
 
 pub mod account_sql;
+pub mod currency_sql;
 pub mod entry_sql;
+pub mod exchange_rate_sql;
+pub mod fx_quote_sql;
+pub mod idempotency_sql;
+pub mod mem_store;
+pub mod migrate;
+pub mod report_sql;
+pub mod session_sql;
 pub mod store;
 pub mod transfer_sql;
+pub mod user_sql;
 
+/// Pool over `DATABASE_URL`, for tests. The server connects through [`connect`].
+#[instrument]
 pub(crate) async fn create_connection_pool(max_conn: Option<u32>) -> Result<PgPool> {
     let max_conn = max_conn.unwrap_or(5);
     let database_url = std::env::var("DATABASE_URL")?;
@@ -17,15 +32,149 @@ pub(crate) async fn create_connection_pool(max_conn: Option<u32>) -> Result<PgPo
     Ok(pool)
 }
 
-async fn _tx_exec<F, T>(pool: &PgPool, f: F) -> Result<T>
+/// Pool configured by the `[database]` settings.
+#[instrument(skip_all)]
+pub async fn connect(config: &DatabaseConfig) -> Result<PgPool> {
+    let pool = PgPoolOptions::new()
+        .max_connections(config.max_connections)
+        .acquire_timeout(config.acquire_timeout())
+        .connect(&config.url)
+        .await?;
+    Ok(pool)
+}
+
+/// Isolation level a transaction run by [`tx_exec`] starts with.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum IsolationLevel {
+    ReadCommitted,
+    RepeatableRead,
+    #[default]
+    Serializable,
+}
+
+impl IsolationLevel {
+    fn as_sql(&self) -> &'static str {
+        match self {
+            IsolationLevel::ReadCommitted => "READ COMMITTED",
+            IsolationLevel::RepeatableRead => "REPEATABLE READ",
+            IsolationLevel::Serializable => "SERIALIZABLE",
+        }
+    }
+}
+
+impl std::str::FromStr for IsolationLevel {
+    type Err = String;
+
+    fn from_str(s: &str) -> std::result::Result<Self, Self::Err> {
+        match s {
+            "read_committed" => Ok(IsolationLevel::ReadCommitted),
+            "repeatable_read" => Ok(IsolationLevel::RepeatableRead),
+            "serializable" => Ok(IsolationLevel::Serializable),
+            _ => Err(format!(
+                "unknown isolation level \"{s}\", expected read_committed, repeatable_read or serializable"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct TxOptions {
+    pub isolation_level: IsolationLevel,
+    /// Retries after the first attempt fails with a serialization failure or deadlock.
+    pub max_retries: u32,
+    pub base_backoff: Duration,
+    pub max_backoff: Duration,
+}
+
+impl Default for TxOptions {
+    fn default() -> Self {
+        Self {
+            isolation_level: IsolationLevel::default(),
+            max_retries: 5,
+            base_backoff: Duration::from_millis(10),
+            max_backoff: Duration::from_millis(500),
+        }
+    }
+}
+
+impl TxOptions {
+    pub fn with_isolation_level(self, isolation_level: IsolationLevel) -> Self {
+        Self {
+            isolation_level,
+            ..self
+        }
+    }
+
+    /// Full-jitter exponential backoff before retry number `attempt` (from 1).
+    fn backoff(&self, attempt: u32) -> Duration {
+        let ceiling = self
+            .base_backoff
+            .saturating_mul(2_u32.saturating_pow(attempt - 1))
+            .min(self.max_backoff);
+        ceiling.mul_f64(rand::thread_rng().gen())
+    }
+}
+
+/// Postgres SQLSTATEs for which rerunning the whole transaction can succeed:
+/// serialization_failure and deadlock_detected.
+fn is_retryable(err: &Error) -> bool {
+    match err.downcast_ref::<sqlx::Error>() {
+        Some(sqlx::Error::Database(db_err)) => {
+            matches!(db_err.code().as_deref(), Some("40001") | Some("40P01"))
+        }
+        _ => false,
+    }
+}
+
+/// Runs `f` in a transaction with the given isolation level, committing on
+/// `Ok` and rolling back on `Err`. Serialization failures and deadlocks,
+/// whether raised by a statement or by the commit, rerun `f` in a fresh
+/// transaction up to `opts.max_retries` times, so `f` must not have side
+/// effects outside the transaction.
+#[instrument(skip(pool, f))]
+pub async fn tx_exec<F, T>(pool: &PgPool, opts: TxOptions, mut f: F) -> Result<T>
 where
-    F: for<'c> FnOnce(&'c mut sqlx::Transaction<'_, sqlx::Postgres>) -> BoxFuture<'c, Result<T>>,
+    F: for<'c> FnMut(&'c mut sqlx::Transaction<'_, sqlx::Postgres>) -> BoxFuture<'c, Result<T>>,
 {
+    let mut attempt = 0;
+    loop {
+        let err = match try_tx(pool, opts.isolation_level, &mut f).await {
+            Ok(result) => return Ok(result),
+            Err(err) => err,
+        };
+        if attempt >= opts.max_retries || !is_retryable(&err) {
+            return Err(err);
+        }
+        METRICS.db_tx_retries_total.inc();
+        attempt += 1;
+        tokio::time::sleep(opts.backoff(attempt)).await;
+    }
+}
+
+#[instrument(skip(pool, f))]
+async fn try_tx<F, T>(pool: &PgPool, isolation_level: IsolationLevel, f: &mut F) -> Result<T>
+where
+    F: for<'c> FnMut(&'c mut sqlx::Transaction<'_, sqlx::Postgres>) -> BoxFuture<'c, Result<T>>,
+{
+    let acquire_timer = METRICS.db_pool_acquire_duration_seconds.start_timer();
     let mut tx = pool.begin().await?;
-    let res = f(&mut tx).await;
+    acquire_timer.observe_duration();
+    let isolation = format!(
+        "SET TRANSACTION ISOLATION LEVEL {};",
+        isolation_level.as_sql()
+    );
+    let res = match sqlx::query(&isolation).execute(&mut *tx).await {
+        Ok(_) => f(&mut tx).await,
+        Err(err) => Err(err.into()),
+    };
     match res {
-        Ok(result) => Ok(result),
+        Ok(result) => {
+            tx.commit().await?;
+            Ok(result)
+        }
         Err(err) => {
+            METRICS.db_tx_rollbacks_total.inc();
             tx.rollback().await?;
             Err(err)
         }
@@ -39,23 +188,29 @@ mod tests {
             create_connection_pool,
             entry_sql::{create_entry, CreateEntryParams},
         },
+        money::Money,
         utils::*,
     };
+    use std::sync::{
+        atomic::{AtomicU32, Ordering},
+        Arc,
+    };
 
     #[tokio::test]
     async fn test_tx_exec() {
         dotenv::dotenv().ok();
         let pool = create_connection_pool(Some(10)).await.unwrap();
         let account = random_account(&pool).await.unwrap();
-        let amount = random_money();
+        let amount = Money::new(random_money(), account.currency);
 
-        let result = _tx_exec(&pool, |tx| {
+        let result = tx_exec(&pool, TxOptions::default(), |tx| {
             Box::pin(async move {
                 let entry = create_entry(
                     tx,
                     CreateEntryParams {
                         account_id: account.id,
                         amount,
+                        transfer_id: None,
                     },
                 )
                 .await?;
@@ -68,4 +223,74 @@ mod tests {
         assert_eq!(result.account_id, account.id);
         assert_eq!(result.amount, amount);
     }
+
+    /// Fails with SQLSTATE 40001 on the first `failures` attempts.
+    async fn serialization_failure_until(
+        pool: &PgPool,
+        opts: TxOptions,
+        failures: u32,
+    ) -> (Result<String>, u32) {
+        let attempts = Arc::new(AtomicU32::new(0));
+        let counter = attempts.clone();
+        let result = tx_exec(pool, opts, |tx| {
+            let counter = counter.clone();
+            Box::pin(async move {
+                if counter.fetch_add(1, Ordering::SeqCst) < failures {
+                    sqlx::query(
+                        "DO $$ BEGIN RAISE EXCEPTION USING ERRCODE = 'serialization_failure'; END $$;",
+                    )
+                    .execute(&mut **tx)
+                    .await?;
+                }
+                let isolation: String = sqlx::query_scalar("SHOW transaction_isolation;")
+                    .fetch_one(&mut **tx)
+                    .await?;
+                Ok(isolation)
+            })
+        })
+        .await;
+        (result, attempts.load(Ordering::SeqCst))
+    }
+
+    #[tokio::test]
+    async fn test_tx_exec_retries_serialization_failure() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let opts = TxOptions::default().with_isolation_level(IsolationLevel::RepeatableRead);
+        let retries = METRICS.db_tx_retries_total.get();
+        let rollbacks = METRICS.db_tx_rollbacks_total.get();
+
+        let (result, attempts) = serialization_failure_until(&pool, opts, 2).await;
+
+        assert_eq!(result.unwrap(), "repeatable read");
+        assert_eq!(attempts, 3);
+        // Other tests share the counters, so only a lower bound holds.
+        assert!(METRICS.db_tx_retries_total.get() >= retries + 2);
+        assert!(METRICS.db_tx_rollbacks_total.get() >= rollbacks + 2);
+    }
+
+    #[tokio::test]
+    async fn test_tx_exec_gives_up_after_max_retries() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let opts = TxOptions {
+            max_retries: 2,
+            ..TxOptions::default()
+        };
+
+        let (result, attempts) = serialization_failure_until(&pool, opts, u32::MAX).await;
+
+        let err: ServerError = result.unwrap_err().into();
+        assert_eq!(err.client_error(), ClientError::TransactionConflict);
+        assert_eq!(attempts, 3);
+    }
+
+    #[test]
+    fn test_backoff_is_capped() {
+        let opts = TxOptions::default();
+
+        for attempt in 1..20 {
+            assert!(opts.backoff(attempt) <= opts.max_backoff);
+        }
+    }
 }
@@ -1,52 +1,69 @@
-use crate::models::Account;
+use crate::currency::Currency;
+use crate::db::{tx_exec, TxOptions};
+use crate::fx::FX_POSITION_OWNER;
+use crate::models::{Account, AccountStatus};
+use crate::money::Money;
 use crate::prelude::*;
+use tracing::instrument;
 
 #[derive(Debug, Clone)]
 pub struct CreateAccountParams {
     pub owner: String,
-    pub balance: i64,
-    pub currency: String,
+    /// The opening balance, in the currency of the account.
+    pub balance: Money,
 }
 
-pub async fn create_account(pool: &sqlx::PgPool, arg: CreateAccountParams) -> Result<Account> {
-    let mut tx: sqlx::Transaction<'_, sqlx::Postgres> = pool.begin().await?;
-
-    let res = sqlx::query_as!(
-        Account,
-        "INSERT INTO accounts (owner, balance, currency) VALUES ($1, $2, $3) RETURNING *;",
-        arg.owner,
-        arg.balance,
-        arg.currency
-    )
-    .fetch_one(&mut *tx)
-    .await;
-
-    match res {
-        Ok(account) => {
-            tx.commit().await?;
+#[instrument(skip(pool))]
+pub async fn create_account(
+    pool: &sqlx::PgPool,
+    opts: TxOptions,
+    arg: CreateAccountParams,
+) -> Result<Account> {
+    tx_exec(pool, opts, |tx| {
+        let arg = arg.clone();
+        Box::pin(async move {
+            let account = sqlx::query_as!(
+                Account,
+                r#"INSERT INTO accounts (owner, balance, currency) VALUES ($1, $2, $3)
+                RETURNING id, owner, (balance, currency) AS "balance!: Money",
+                    currency AS "currency: Currency", created_at, status AS "status: AccountStatus",
+                    (overdraft_limit, currency) AS "overdraft_limit!: Money";"#,
+                arg.owner,
+                arg.balance.minor_units,
+                arg.balance.currency.as_str()
+            )
+            .fetch_one(&mut **tx)
+            .await?;
             Ok(account)
-        }
-        Err(err) => {
-            tx.rollback().await?;
-            Err(err.into())
-        }
-    }
+        })
+    })
+    .await
 }
 
+#[instrument(skip(pool))]
 pub async fn get_account(pool: &sqlx::PgPool, id: i64) -> Result<Account> {
-    let account = sqlx::query_as!(Account, "SELECT * FROM accounts WHERE id = $1 LIMIT 1;", id)
-        .fetch_one(pool)
-        .await?;
+    let account = sqlx::query_as!(
+        Account,
+        r#"SELECT id, owner, (balance, currency) AS "balance!: Money", currency AS "currency: Currency",
+            created_at, status AS "status: AccountStatus", (overdraft_limit, currency) AS "overdraft_limit!: Money"
+        FROM accounts WHERE id = $1 LIMIT 1;"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
     Ok(account)
 }
 
+#[instrument(skip(transaction))]
 pub async fn get_account_for_update(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     id: i64,
 ) -> Result<Account> {
     let account = sqlx::query_as!(
         Account,
-        "SELECT * FROM accounts WHERE id = $1 LIMIT 1 FOR NO KEY UPDATE;",
+        r#"SELECT id, owner, (balance, currency) AS "balance!: Money", currency AS "currency: Currency",
+            created_at, status AS "status: AccountStatus", (overdraft_limit, currency) AS "overdraft_limit!: Money"
+        FROM accounts WHERE id = $1 LIMIT 1 FOR NO KEY UPDATE;"#,
         id
     )
     .fetch_one(&mut **transaction)
@@ -54,40 +71,81 @@ pub async fn get_account_for_update(
     Ok(account)
 }
 
+/// Id of the FX position account in `currency`, opened on first use for
+/// currencies added after the `add_exchange_rates` migration.
+#[instrument(skip(transaction))]
+pub async fn get_fx_position_account_id(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    currency: Currency,
+) -> Result<i64> {
+    let id = sqlx::query_scalar!(
+        // The conflict target has to repeat the index predicate literally.
+        r#"WITH opened AS (
+            INSERT INTO accounts (owner, balance, currency) VALUES ($1, 0, $2)
+            ON CONFLICT (currency) WHERE owner = 'fx-position' DO NOTHING
+            RETURNING id
+        )
+        SELECT id AS "id!" FROM opened
+        UNION ALL
+        SELECT id FROM accounts WHERE owner = $1 AND currency = $2
+        LIMIT 1;"#,
+        FX_POSITION_OWNER,
+        currency.as_str()
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(id)
+}
+
 #[derive(Debug, Clone)]
 pub struct AddAccountBalanceParams {
     pub id: i64,
-    pub amount: i64,
+    /// Must be in the currency of the account.
+    pub amount: Money,
 }
 
+#[instrument(skip(transaction))]
 pub async fn add_account_balance(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     arg: AddAccountBalanceParams,
 ) -> Result<Account> {
     let account = sqlx::query_as!(
         Account,
-        "UPDATE accounts
+        r#"UPDATE accounts
         SET balance = balance + $2
         WHERE id = $1
-        RETURNING *;",
+        RETURNING id, owner, (balance, currency) AS "balance!: Money",
+            currency AS "currency: Currency", created_at, status AS "status: AccountStatus",
+            (overdraft_limit, currency) AS "overdraft_limit!: Money";"#,
         arg.id,
-        arg.amount
+        arg.amount.minor_units
     )
     .fetch_one(&mut **transaction)
     .await?;
+    // Rolls the update back with the caller's transaction.
+    account.balance.same_currency(arg.amount)?;
     Ok(account)
 }
 
 #[derive(Debug, Clone)]
 pub struct ListAccountsParams {
+    /// `None` lists the accounts of every owner.
+    pub owner: Option<String>,
     pub limit: i64,
     pub offset: i64,
 }
 
+#[instrument(skip(pool))]
 pub async fn list_accounts(pool: &sqlx::PgPool, arg: ListAccountsParams) -> Result<Vec<Account>> {
     let accounts = sqlx::query_as!(
         Account,
-        "SELECT * FROM accounts ORDER BY id LIMIT $1 OFFSET $2;",
+        r#"SELECT id, owner, (balance, currency) AS "balance!: Money", currency AS "currency: Currency",
+            created_at, status AS "status: AccountStatus", (overdraft_limit, currency) AS "overdraft_limit!: Money"
+        FROM accounts
+        WHERE ($1::varchar IS NULL OR owner = $1)
+        ORDER BY id
+        LIMIT $2 OFFSET $3;"#,
+        arg.owner,
         arg.limit,
         arg.offset
     )
@@ -96,68 +154,123 @@ pub async fn list_accounts(pool: &sqlx::PgPool, arg: ListAccountsParams) -> Resu
     Ok(accounts)
 }
 
-pub async fn update_account(pool: &sqlx::PgPool, id: i64, balance: i64) -> Result<Account> {
-    let mut tx = pool.begin().await?;
+/// Sets the balance directly, outside the ledger: no transfer or entry
+/// records the change. Only for setting up balances in tests; money moves
+/// through `transfer_tx`.
+#[instrument(skip(pool))]
+pub async fn update_account(
+    pool: &sqlx::PgPool,
+    opts: TxOptions,
+    id: i64,
+    balance: Money,
+) -> Result<Account> {
+    tx_exec(pool, opts, |tx| {
+        Box::pin(async move { update_account_tx(tx, id, balance).await })
+    })
+    .await
+}
 
-    let res = sqlx::query_as!(
+#[instrument(skip(transaction))]
+pub async fn update_account_tx(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: i64,
+    balance: Money,
+) -> Result<Account> {
+    let account = sqlx::query_as!(
         Account,
-        "UPDATE accounts SET balance = $2 WHERE id = $1 RETURNING *;",
+        r#"UPDATE accounts SET balance = $2 WHERE id = $1
+        RETURNING id, owner, (balance, currency) AS "balance!: Money",
+            currency AS "currency: Currency", created_at, status AS "status: AccountStatus",
+            (overdraft_limit, currency) AS "overdraft_limit!: Money";"#,
         id,
-        balance
+        balance.minor_units
     )
-    .fetch_one(&mut *tx)
-    .await;
-
-    match res {
-        Ok(account) => {
-            tx.commit().await?;
-            Ok(account)
-        }
-        Err(err) => {
-            tx.rollback().await?;
-            Err(err.into())
-        }
-    }
+    .fetch_one(&mut **transaction)
+    .await?;
+    account.balance.same_currency(balance)?;
+    Ok(account)
 }
 
-pub async fn update_account_tx(
+#[instrument(skip(transaction))]
+pub async fn update_account_status_tx(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     id: i64,
-    balance: i64,
+    status: AccountStatus,
 ) -> Result<Account> {
     let account = sqlx::query_as!(
         Account,
-        "UPDATE accounts SET balance = $2 WHERE id = $1 RETURNING *;",
+        r#"UPDATE accounts SET status = $2 WHERE id = $1
+        RETURNING id, owner, (balance, currency) AS "balance!: Money",
+            currency AS "currency: Currency", created_at, status AS "status: AccountStatus",
+            (overdraft_limit, currency) AS "overdraft_limit!: Money";"#,
         id,
-        balance
+        status as AccountStatus
     )
     .fetch_one(&mut **transaction)
     .await?;
     Ok(account)
 }
 
-pub async fn delete_account(pool: &sqlx::PgPool, id: i64) -> Result<()> {
-    let mut tx = pool.begin().await?;
-
-    let res = sqlx::query!("DELETE FROM accounts WHERE id = $1;", id)
-        .execute(&mut *tx)
-        .await;
+#[derive(Debug, Clone)]
+pub struct CreateAccountStatusChangeParams {
+    pub account_id: i64,
+    pub from_status: AccountStatus,
+    pub to_status: AccountStatus,
+    pub reason: String,
+    pub changed_by: String,
+}
 
-    match res {
-        Ok(_) => {
-            tx.commit().await?;
-        }
-        Err(err) => {
-            tx.rollback().await?;
-            return Err(err.into());
-        }
-    }
+/// Records who changed the status of an account, and why.
+#[instrument(skip(transaction))]
+pub async fn create_account_status_change(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    arg: CreateAccountStatusChangeParams,
+) -> Result<()> {
+    sqlx::query!(
+        "INSERT INTO account_status_changes
+            (account_id, from_status, to_status, reason, changed_by)
+        VALUES ($1, $2, $3, $4, $5);",
+        arg.account_id,
+        arg.from_status as AccountStatus,
+        arg.to_status as AccountStatus,
+        arg.reason,
+        arg.changed_by
+    )
+    .execute(&mut **transaction)
+    .await?;
     Ok(())
 }
 
+#[instrument(skip(pool))]
+pub async fn update_overdraft_limit(
+    pool: &sqlx::PgPool,
+    opts: TxOptions,
+    id: i64,
+    overdraft_limit: Money,
+) -> Result<Account> {
+    tx_exec(pool, opts, |tx| {
+        Box::pin(async move {
+            let account = sqlx::query_as!(
+                Account,
+                r#"UPDATE accounts SET overdraft_limit = $2 WHERE id = $1
+                RETURNING id, owner, (balance, currency) AS "balance!: Money",
+                    currency AS "currency: Currency", created_at, status AS "status: AccountStatus",
+                    (overdraft_limit, currency) AS "overdraft_limit!: Money";"#,
+                id,
+                overdraft_limit.minor_units
+            )
+            .fetch_one(&mut **tx)
+            .await?;
+            account.overdraft_limit.same_currency(overdraft_limit)?;
+            Ok(account)
+        })
+    })
+    .await
+}
+
 mod tests {
     use super::*;
-    use crate::{db::create_connection_pool, utils::*};
+    use crate::{db::create_connection_pool, money::MoneyError, utils::*};
 
     #[tokio::test]
     async fn test_create_account() {
@@ -166,16 +279,19 @@ mod tests {
             .await
             .expect("Failed to create connection pool");
 
+        let user = random_user(&db).await.unwrap();
         let arg = CreateAccountParams {
-            owner: random_owner(),
-            balance: random_money(),
-            currency: random_currency(),
+            owner: user.username,
+            balance: Money::new(random_money(), random_currency()),
         };
 
-        let account = create_account(&db, arg.clone()).await.unwrap();
+        let account = create_account(&db, TxOptions::default(), arg.clone())
+            .await
+            .unwrap();
         assert_eq!(account.owner, arg.owner);
         assert_eq!(account.balance, arg.balance);
-        assert_eq!(account.currency, arg.currency);
+        assert_eq!(account.currency, arg.balance.currency);
+        assert_eq!(account.overdraft_limit, Money::zero(account.currency));
 
         assert_ne!(account.id, 0);
     }
@@ -201,8 +317,10 @@ mod tests {
             .expect("Failed to create connection pool");
 
         let account = random_account(&db).await.unwrap();
-        let new_balance = random_money();
-        let account2 = update_account(&db, account.id, new_balance).await.unwrap();
+        let new_balance = Money::new(random_money(), account.currency);
+        let account2 = update_account(&db, TxOptions::default(), account.id, new_balance)
+            .await
+            .unwrap();
 
         assert_eq!(account2.balance, new_balance);
         assert_eq!(account2.id, account.id);
@@ -210,18 +328,25 @@ mod tests {
     }
 
     #[tokio::test]
-    async fn test_delete_account() {
+    async fn test_update_account_currency_mismatch() {
         dotenv::dotenv().ok();
         let db = create_connection_pool(Some(10))
             .await
             .expect("Failed to create connection pool");
 
-        let account = random_account(&db).await.unwrap();
-        delete_account(&db, account.id).await.unwrap();
+        let account = random_account_in(&db, "USD".parse().unwrap())
+            .await
+            .unwrap();
+        let balance = Money::new(account.balance.minor_units + 1, "EUR".parse().unwrap());
 
-        let account2 = get_account(&db, account.id).await;
-        assert!(account2.is_err());
-        assert!(account2.unwrap_err().is::<sqlx::Error>());
+        let err = update_account(&db, TxOptions::default(), account.id, balance)
+            .await
+            .unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<MoneyError>(),
+            Some(MoneyError::CurrencyMismatch { .. })
+        ));
+        assert_eq!(get_account(&db, account.id).await.unwrap(), account);
     }
 
     #[tokio::test]
@@ -231,17 +356,125 @@ mod tests {
             .await
             .expect("Failed to create connection pool");
 
-        let limit = 10;
-        let offset = 0;
-        for _ in 0..limit {
-            random_account(&db).await.unwrap();
+        let user = random_user(&db).await.unwrap();
+        for _ in 0..10 {
+            random_account_for_owner(&db, &user.username).await.unwrap();
         }
+        random_account(&db).await.unwrap();
+
+        let accounts = list_accounts(
+            &db,
+            ListAccountsParams {
+                owner: Some(user.username.clone()),
+                limit: 5,
+                offset: 5,
+            },
+        )
+        .await
+        .unwrap();
+
+        assert_eq!(accounts.len(), 5);
+        for account in accounts {
+            assert_eq!(account.owner, user.username);
+        }
+    }
+
+    #[tokio::test]
+    async fn test_update_account_status_tx() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let account = random_account(&db).await.unwrap();
+        assert_eq!(account.status, AccountStatus::Active);
+
+        let mut tx = db.begin().await.unwrap();
+        let account2 = update_account_status_tx(&mut tx, account.id, AccountStatus::Frozen)
+            .await
+            .unwrap();
+        create_account_status_change(
+            &mut tx,
+            CreateAccountStatusChangeParams {
+                account_id: account.id,
+                from_status: account.status,
+                to_status: account2.status,
+                reason: "suspected fraud".to_string(),
+                changed_by: account.owner.clone(),
+            },
+        )
+        .await
+        .unwrap();
+        tx.commit().await.unwrap();
+        assert_eq!(account2.status, AccountStatus::Frozen);
+        assert_eq!(account2.balance, account.balance);
+
+        let reason = sqlx::query_scalar!(
+            "SELECT reason FROM account_status_changes WHERE account_id = $1;",
+            account.id
+        )
+        .fetch_one(&db)
+        .await
+        .unwrap();
+        assert_eq!(reason, "suspected fraud");
+    }
 
-        let accounts = list_accounts(&db, ListAccountsParams { limit, offset })
+    #[tokio::test]
+    async fn test_closed_account_balance_check() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let account = random_account(&db).await.unwrap();
+        let account = update_account(
+            &db,
+            TxOptions::default(),
+            account.id,
+            Money::new(100, account.currency),
+        )
+        .await
+        .unwrap();
+
+        let mut tx = db.begin().await.unwrap();
+        let err: ServerError = update_account_status_tx(&mut tx, account.id, AccountStatus::Closed)
+            .await
+            .unwrap_err()
+            .into();
+        assert_eq!(err.client_error(), ClientError::ConstraintViolation);
+    }
+
+    #[tokio::test]
+    async fn test_update_overdraft_limit() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let account = random_account(&db).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, account.currency);
+        let account = update_overdraft_limit(&db, TxOptions::default(), account.id, money(500))
             .await
             .unwrap();
+        assert_eq!(account.overdraft_limit, money(500));
 
-        assert_eq!(accounts.len(), limit as usize);
+        let account = update_account(&db, TxOptions::default(), account.id, money(-500))
+            .await
+            .unwrap();
+        assert_eq!(account.balance, money(-500));
+
+        let err: ServerError = update_account(&db, TxOptions::default(), account.id, money(-501))
+            .await
+            .unwrap_err()
+            .into();
+        assert_eq!(err.client_error(), ClientError::ConstraintViolation);
+
+        let err: ServerError =
+            update_overdraft_limit(&db, TxOptions::default(), account.id, money(100))
+                .await
+                .unwrap_err()
+                .into();
+        assert_eq!(err.client_error(), ClientError::ConstraintViolation);
     }
 
     #[tokio::test]
@@ -252,7 +485,7 @@ mod tests {
             .expect("Failed to create connection pool");
 
         let account = random_account(&db).await.unwrap();
-        let amount = random_money();
+        let amount = Money::new(random_money(), account.currency);
         let mut tx = db.begin().await.unwrap();
         let account2 = add_account_balance(
             &mut tx,
@@ -264,7 +497,10 @@ mod tests {
         .await
         .unwrap();
 
-        assert_eq!(account2.balance, account.balance + amount);
+        assert_eq!(
+            account2.balance,
+            account.balance.checked_add(amount).unwrap()
+        );
     }
 
     #[tokio::test]
@@ -275,7 +511,7 @@ mod tests {
             .expect("Failed to create connection pool");
 
         let account = random_account(&db).await.unwrap();
-        let new_balance = random_money();
+        let new_balance = Money::new(random_money(), account.currency);
         let mut tx = db.begin().await.unwrap();
         let account2 = update_account_tx(&mut tx, account.id, new_balance)
             .await
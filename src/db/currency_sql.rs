@@ -0,0 +1,42 @@
+use crate::currency::Currency;
+use crate::models::CurrencyInfo;
+use crate::prelude::*;
+use tracing::instrument;
+
+#[instrument(skip(pool))]
+pub async fn list_currencies(pool: &sqlx::PgPool) -> Result<Vec<CurrencyInfo>> {
+    let currencies = sqlx::query_as!(
+        CurrencyInfo,
+        r#"SELECT code AS "code: Currency", numeric_code, minor_units, enabled
+        FROM currencies
+        ORDER BY code;"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(currencies)
+}
+
+mod tests {
+    use super::*;
+    use crate::{currency::iso_4217, db::create_connection_pool};
+
+    #[tokio::test]
+    async fn test_list_currencies() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let currencies = list_currencies(&db).await.unwrap();
+
+        // The compiled-in table mirrors the migration's seed rows.
+        for seeded in iso_4217() {
+            let row = currencies
+                .iter()
+                .find(|row| row.code == seeded.code)
+                .unwrap();
+            assert_eq!(row.numeric_code, seeded.numeric_code);
+            assert_eq!(row.minor_units, seeded.minor_units);
+        }
+    }
+}
@@ -1,38 +1,83 @@
 use crate::models::Entry;
+use crate::money::Money;
 use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
 
 #[derive(Debug, Clone)]
 pub struct CreateEntryParams {
     pub account_id: i64,
-    pub amount: i64,
+    /// Must be in the currency of the account.
+    pub amount: Money,
+    pub transfer_id: Option<i64>,
 }
 
+#[instrument(skip(transaction))]
 pub async fn create_entry(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     arg: CreateEntryParams,
-) -> SQLResult<Entry> {
-    sqlx::query_as!(
+) -> Result<Entry> {
+    let entry = sqlx::query_as!(
         Entry,
-        "INSERT INTO entries (account_id, amount) VALUES ($1, $2) RETURNING *;",
+        r#"WITH e AS (
+            INSERT INTO entries (account_id, amount, transfer_id) VALUES ($1, $2, $3)
+            RETURNING *
+        )
+        SELECT e.id AS "id!", e.account_id AS "account_id!", (e.amount, a.currency) AS "amount!: Money",
+            e.created_at AS "created_at!", e.transfer_id AS "transfer_id?"
+        FROM e JOIN accounts a ON a.id = e.account_id;"#,
         arg.account_id,
-        arg.amount
+        arg.amount.minor_units,
+        arg.transfer_id
     )
     .fetch_one(&mut **transaction)
-    .await
+    .await?;
+    // Rolls the insert back with the caller's transaction.
+    arg.amount.same_currency(entry.amount)?;
+    Ok(entry)
 }
 
+#[instrument(skip(pool))]
 pub async fn get_entry(pool: &sqlx::PgPool, id: i64) -> Result<Entry> {
-    let entry = sqlx::query_as!(Entry, "SELECT * FROM entries WHERE id = $1 LIMIT 1;", id)
-        .fetch_one(pool)
-        .await?;
+    let entry = sqlx::query_as!(
+        Entry,
+        r#"SELECT e.id, e.account_id, (e.amount, a.currency) AS "amount!: Money",
+            e.created_at, e.transfer_id
+        FROM entries e JOIN accounts a ON a.id = e.account_id
+        WHERE e.id = $1 LIMIT 1;"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
     Ok(entry)
 }
 
-pub async fn list_entries(pool: &sqlx::PgPool, account_id: i64) -> Result<Vec<Entry>> {
+#[derive(Debug, Clone)]
+pub struct ListEntriesParams {
+    pub account_id: i64,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[instrument(skip(pool))]
+pub async fn list_entries(pool: &sqlx::PgPool, arg: ListEntriesParams) -> Result<Vec<Entry>> {
     let entries = sqlx::query_as!(
         Entry,
-        "SELECT * FROM entries WHERE account_id = $1 ORDER BY id;",
-        account_id
+        r#"SELECT e.id, e.account_id, (e.amount, a.currency) AS "amount!: Money",
+            e.created_at, e.transfer_id
+        FROM entries e JOIN accounts a ON a.id = e.account_id
+        WHERE e.account_id = $1
+            AND ($2::timestamptz IS NULL OR e.created_at >= $2)
+            AND ($3::timestamptz IS NULL OR e.created_at < $3)
+        ORDER BY e.id
+        LIMIT $4 OFFSET $5;"#,
+        arg.account_id,
+        arg.start_time,
+        arg.end_time,
+        arg.limit,
+        arg.offset
     )
     .fetch_all(pool)
     .await?;
@@ -49,7 +94,7 @@ mod tests {
         dotenv::dotenv().ok();
         let pool = create_connection_pool(Some(10)).await.unwrap();
         let account = random_account(&pool).await.unwrap();
-        let amount = random_money();
+        let amount = Money::new(random_money(), account.currency);
 
         let mut tx = pool.begin().await.unwrap();
         let entry = create_entry(
@@ -57,6 +102,7 @@ mod tests {
             CreateEntryParams {
                 account_id: account.id,
                 amount,
+                transfer_id: None,
             },
         )
         .await
@@ -77,4 +123,58 @@ mod tests {
         let got = get_entry(&pool, entry.id).await.unwrap();
         assert_eq!(got, entry);
     }
+
+    #[tokio::test]
+    async fn test_list_entries() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let account = random_account(&pool).await.unwrap();
+
+        let mut entries = vec![];
+        for _ in 0..5 {
+            entries.push(random_entry(&pool, account.id).await.unwrap());
+        }
+
+        let got = list_entries(
+            &pool,
+            ListEntriesParams {
+                account_id: account.id,
+                start_time: None,
+                end_time: None,
+                limit: 3,
+                offset: 1,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(got, entries[1..4]);
+
+        let got = list_entries(
+            &pool,
+            ListEntriesParams {
+                account_id: account.id,
+                start_time: Some(entries[4].created_at),
+                end_time: None,
+                limit: 5,
+                offset: 0,
+            },
+        )
+        .await
+        .unwrap();
+        assert_eq!(got, entries[4..]);
+
+        let got = list_entries(
+            &pool,
+            ListEntriesParams {
+                account_id: account.id,
+                start_time: None,
+                end_time: Some(entries[0].created_at),
+                limit: 5,
+                offset: 0,
+            },
+        )
+        .await
+        .unwrap();
+        assert!(got.is_empty());
+    }
 }
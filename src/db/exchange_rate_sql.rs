@@ -0,0 +1,154 @@
+use crate::currency::Currency;
+use crate::decimal::Decimal;
+use crate::models::ExchangeRate;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+#[derive(Debug, Clone)]
+pub struct CreateExchangeRateParams {
+    pub base_currency: Currency,
+    pub quote_currency: Currency,
+    pub rate: Decimal,
+    pub spread_bps: i32,
+    pub effective_from: DateTime<Utc>,
+}
+
+#[instrument(skip(pool))]
+pub async fn create_exchange_rate(
+    pool: &sqlx::PgPool,
+    arg: CreateExchangeRateParams,
+) -> Result<ExchangeRate> {
+    let rate = sqlx::query_as!(
+        ExchangeRate,
+        r#"INSERT INTO exchange_rates
+            (base_currency, quote_currency, rate, spread_bps, effective_from)
+        VALUES ($1, $2, $3::text::numeric, $4, $5)
+        RETURNING id, base_currency AS "base_currency: Currency",
+            quote_currency AS "quote_currency: Currency", rate::text AS "rate!: Decimal",
+            spread_bps, effective_from, created_at;"#,
+        arg.base_currency.as_str(),
+        arg.quote_currency.as_str(),
+        arg.rate.to_string(),
+        arg.spread_bps,
+        arg.effective_from
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(rate)
+}
+
+#[instrument(skip(transaction))]
+pub async fn get_exchange_rate_tx(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: i64,
+) -> Result<ExchangeRate> {
+    let rate = sqlx::query_as!(
+        ExchangeRate,
+        r#"SELECT id, base_currency AS "base_currency: Currency",
+            quote_currency AS "quote_currency: Currency", rate::text AS "rate!: Decimal",
+            spread_bps, effective_from, created_at
+        FROM exchange_rates
+        WHERE id = $1
+        LIMIT 1;"#,
+        id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(rate)
+}
+
+/// The rate for the pair in force at `at`: the one with the latest
+/// `effective_from` not after it.
+#[instrument(skip(pool))]
+pub async fn find_exchange_rate(
+    pool: &sqlx::PgPool,
+    base_currency: Currency,
+    quote_currency: Currency,
+    at: DateTime<Utc>,
+) -> Result<Option<ExchangeRate>> {
+    let rate = sqlx::query_as!(
+        ExchangeRate,
+        r#"SELECT id, base_currency AS "base_currency: Currency",
+            quote_currency AS "quote_currency: Currency", rate::text AS "rate!: Decimal",
+            spread_bps, effective_from, created_at
+        FROM exchange_rates
+        WHERE base_currency = $1 AND quote_currency = $2 AND effective_from <= $3
+        ORDER BY effective_from DESC
+        LIMIT 1;"#,
+        base_currency.as_str(),
+        quote_currency.as_str(),
+        at
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(rate)
+}
+
+/// [`find_exchange_rate`] inside the caller's transaction.
+#[instrument(skip(transaction))]
+pub async fn find_exchange_rate_tx(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    base_currency: Currency,
+    quote_currency: Currency,
+    at: DateTime<Utc>,
+) -> Result<Option<ExchangeRate>> {
+    let rate = sqlx::query_as!(
+        ExchangeRate,
+        r#"SELECT id, base_currency AS "base_currency: Currency",
+            quote_currency AS "quote_currency: Currency", rate::text AS "rate!: Decimal",
+            spread_bps, effective_from, created_at
+        FROM exchange_rates
+        WHERE base_currency = $1 AND quote_currency = $2 AND effective_from <= $3
+        ORDER BY effective_from DESC
+        LIMIT 1;"#,
+        base_currency.as_str(),
+        quote_currency.as_str(),
+        at
+    )
+    .fetch_optional(&mut **transaction)
+    .await?;
+    Ok(rate)
+}
+
+mod tests {
+    use super::*;
+    use crate::{db::create_connection_pool, utils::*};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_find_exchange_rate() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        // Days apart from the rates of other runs, so they cannot interleave.
+        let start = Utc::now() + Duration::days(random_int(1, 1_000_000));
+        let create = |rate: &str, effective_from| {
+            create_exchange_rate(
+                &db,
+                CreateExchangeRateParams {
+                    base_currency: "CHF".parse().unwrap(),
+                    quote_currency: "KRW".parse().unwrap(),
+                    rate: rate.parse().unwrap(),
+                    spread_bps: 25,
+                    effective_from,
+                },
+            )
+        };
+        let old = create("1550.5", start).await.unwrap();
+        let new = create("1561.25", start + Duration::hours(1)).await.unwrap();
+        assert_eq!(new.rate.to_string(), "1561.25");
+        assert_eq!(new.spread_bps, 25);
+
+        let (base_currency, quote_currency) = (old.base_currency, old.quote_currency);
+        let find = |at| find_exchange_rate(&db, base_currency, quote_currency, at);
+        assert_eq!(find(start).await.unwrap(), Some(old.clone()));
+        assert_eq!(
+            find(start + Duration::minutes(59)).await.unwrap(),
+            Some(old)
+        );
+        assert_eq!(find(start + Duration::hours(2)).await.unwrap(), Some(new));
+    }
+}
@@ -0,0 +1,99 @@
+use crate::currency::Currency;
+use crate::decimal::Decimal;
+use crate::models::FxQuote;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct CreateFxQuoteParams {
+    pub id: Uuid,
+    pub owner: String,
+    pub exchange_rate_id: i64,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[instrument(skip(pool))]
+pub async fn create_fx_quote(pool: &sqlx::PgPool, arg: CreateFxQuoteParams) -> Result<FxQuote> {
+    let quote = sqlx::query_as!(
+        FxQuote,
+        r#"WITH q AS (
+            INSERT INTO fx_quotes (id, owner, exchange_rate_id, expires_at)
+            VALUES ($1, $2, $3, $4)
+            RETURNING *
+        )
+        SELECT q.id, q.owner, q.exchange_rate_id,
+            r.base_currency AS "base_currency: Currency",
+            r.quote_currency AS "quote_currency: Currency", r.rate::text AS "rate!: Decimal",
+            r.spread_bps, q.expires_at, q.transfer_id, q.used_at, q.created_at
+        FROM q JOIN exchange_rates r ON r.id = q.exchange_rate_id;"#,
+        arg.id,
+        arg.owner,
+        arg.exchange_rate_id,
+        arg.expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(quote)
+}
+
+#[instrument(skip(pool))]
+pub async fn get_fx_quote(pool: &sqlx::PgPool, id: Uuid) -> Result<FxQuote> {
+    let quote = sqlx::query_as!(
+        FxQuote,
+        r#"SELECT q.id, q.owner, q.exchange_rate_id,
+            r.base_currency AS "base_currency: Currency",
+            r.quote_currency AS "quote_currency: Currency", r.rate::text AS "rate!: Decimal",
+            r.spread_bps, q.expires_at, q.transfer_id, q.used_at, q.created_at
+        FROM fx_quotes q JOIN exchange_rates r ON r.id = q.exchange_rate_id
+        WHERE q.id = $1
+        LIMIT 1;"#,
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(quote)
+}
+
+/// Locks the quote until the transaction ends, so that only one transfer
+/// can use it.
+#[instrument(skip(transaction))]
+pub async fn get_fx_quote_for_update(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: Uuid,
+) -> Result<FxQuote> {
+    let quote = sqlx::query_as!(
+        FxQuote,
+        r#"SELECT q.id, q.owner, q.exchange_rate_id,
+            r.base_currency AS "base_currency: Currency",
+            r.quote_currency AS "quote_currency: Currency", r.rate::text AS "rate!: Decimal",
+            r.spread_bps, q.expires_at, q.transfer_id, q.used_at, q.created_at
+        FROM fx_quotes q JOIN exchange_rates r ON r.id = q.exchange_rate_id
+        WHERE q.id = $1
+        LIMIT 1
+        FOR NO KEY UPDATE OF q;"#,
+        id
+    )
+    .fetch_one(&mut **transaction)
+    .await?;
+    Ok(quote)
+}
+
+/// Marks the quote as used by `transfer_id`. Callers lock and check it first
+/// with [`get_fx_quote_for_update`].
+#[instrument(skip(transaction))]
+pub async fn use_fx_quote(
+    transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    id: Uuid,
+    transfer_id: i64,
+) -> Result<()> {
+    sqlx::query!(
+        "UPDATE fx_quotes SET transfer_id = $2, used_at = now() WHERE id = $1;",
+        id,
+        transfer_id
+    )
+    .execute(&mut **transaction)
+    .await?;
+    Ok(())
+}
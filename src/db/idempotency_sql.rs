@@ -0,0 +1,207 @@
+use crate::models::IdempotencyKey;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+
+#[derive(Debug, Clone)]
+pub struct CreateIdempotencyKeyParams {
+    pub scope: String,
+    pub key: String,
+    pub request_hash: String,
+}
+
+/// Claims `key` for a new request. Returns `None` if the key is already taken,
+/// in which case the existing row decides how the request is answered.
+#[instrument(skip(pool))]
+pub async fn create_idempotency_key(
+    pool: &sqlx::PgPool,
+    arg: CreateIdempotencyKeyParams,
+) -> Result<Option<IdempotencyKey>> {
+    let key = sqlx::query_as!(
+        IdempotencyKey,
+        "INSERT INTO idempotency_keys (scope, key, request_hash)
+        VALUES ($1, $2, $3)
+        ON CONFLICT DO NOTHING
+        RETURNING *;",
+        arg.scope,
+        arg.key,
+        arg.request_hash
+    )
+    .fetch_optional(pool)
+    .await?;
+    Ok(key)
+}
+
+#[instrument(skip(pool))]
+pub async fn get_idempotency_key(
+    pool: &sqlx::PgPool,
+    scope: &str,
+    key: &str,
+) -> Result<IdempotencyKey> {
+    let key = sqlx::query_as!(
+        IdempotencyKey,
+        "SELECT * FROM idempotency_keys WHERE scope = $1 AND key = $2 LIMIT 1;",
+        scope,
+        key
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(key)
+}
+
+#[derive(Debug, Clone)]
+pub struct CompleteIdempotencyKeyParams {
+    pub scope: String,
+    pub key: String,
+    pub response_status: i16,
+    pub response_content_type: Option<String>,
+    pub response_body: Vec<u8>,
+}
+
+#[instrument(skip(pool, arg), fields(scope = %arg.scope, key = %arg.key, status = arg.response_status))]
+pub async fn complete_idempotency_key(
+    pool: &sqlx::PgPool,
+    arg: CompleteIdempotencyKeyParams,
+) -> Result<IdempotencyKey> {
+    let key = sqlx::query_as!(
+        IdempotencyKey,
+        "UPDATE idempotency_keys
+        SET response_status = $3, response_content_type = $4, response_body = $5
+        WHERE scope = $1 AND key = $2
+        RETURNING *;",
+        arg.scope,
+        arg.key,
+        arg.response_status,
+        arg.response_content_type,
+        arg.response_body
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(key)
+}
+
+#[instrument(skip(pool))]
+pub async fn delete_idempotency_key(pool: &sqlx::PgPool, scope: &str, key: &str) -> Result<()> {
+    sqlx::query!(
+        "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2;",
+        scope,
+        key
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// Deletes `key` if it was created before `expired_before`, releasing it for reuse.
+#[instrument(skip(pool))]
+pub async fn delete_expired_idempotency_key(
+    pool: &sqlx::PgPool,
+    scope: &str,
+    key: &str,
+    expired_before: DateTime<Utc>,
+) -> Result<bool> {
+    let res = sqlx::query!(
+        "DELETE FROM idempotency_keys WHERE scope = $1 AND key = $2 AND created_at < $3;",
+        scope,
+        key,
+        expired_before
+    )
+    .execute(pool)
+    .await?;
+    Ok(res.rows_affected() > 0)
+}
+
+mod tests {
+    use super::*;
+    use crate::{db::create_connection_pool, utils::*};
+    use chrono::Duration;
+
+    fn random_params() -> CreateIdempotencyKeyParams {
+        CreateIdempotencyKeyParams {
+            scope: random_string(10),
+            key: random_string(16),
+            request_hash: random_string(64),
+        }
+    }
+
+    #[tokio::test]
+    async fn test_create_idempotency_key() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let arg = random_params();
+        let key = create_idempotency_key(&db, arg.clone())
+            .await
+            .unwrap()
+            .unwrap();
+        assert_eq!(key.request_hash, arg.request_hash);
+        assert_eq!(key.response_status, None);
+
+        let taken = create_idempotency_key(&db, arg.clone()).await.unwrap();
+        assert_eq!(taken, None);
+    }
+
+    #[tokio::test]
+    async fn test_complete_idempotency_key() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let arg = random_params();
+        create_idempotency_key(&db, arg.clone()).await.unwrap();
+
+        complete_idempotency_key(
+            &db,
+            CompleteIdempotencyKeyParams {
+                scope: arg.scope.clone(),
+                key: arg.key.clone(),
+                response_status: 200,
+                response_content_type: Some("application/json".to_string()),
+                response_body: b"{}".to_vec(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let key = get_idempotency_key(&db, &arg.scope, &arg.key)
+            .await
+            .unwrap();
+        assert_eq!(key.response_status, Some(200));
+        assert_eq!(key.response_body.as_deref(), Some(&b"{}"[..]));
+    }
+
+    #[tokio::test]
+    async fn test_delete_expired_idempotency_key() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let arg = random_params();
+        let key = create_idempotency_key(&db, arg.clone())
+            .await
+            .unwrap()
+            .unwrap();
+
+        let deleted = delete_expired_idempotency_key(&db, &arg.scope, &arg.key, key.created_at)
+            .await
+            .unwrap();
+        assert!(!deleted);
+
+        let deleted = delete_expired_idempotency_key(
+            &db,
+            &arg.scope,
+            &arg.key,
+            key.created_at + Duration::microseconds(1),
+        )
+        .await
+        .unwrap();
+        assert!(deleted);
+        assert!(get_idempotency_key(&db, &arg.scope, &arg.key)
+            .await
+            .is_err());
+    }
+}
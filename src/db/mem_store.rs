@@ -0,0 +1,1133 @@
+use crate::{
+    currency::{iso_4217, Currency},
+    db::{
+        account_sql::{CreateAccountParams, CreateAccountStatusChangeParams, ListAccountsParams},
+        entry_sql::ListEntriesParams,
+        exchange_rate_sql::CreateExchangeRateParams,
+        fx_quote_sql::CreateFxQuoteParams,
+        idempotency_sql::{CompleteIdempotencyKeyParams, CreateIdempotencyKeyParams},
+        migrate,
+        report_sql::{BalanceReport, TransferVolumeReport, TransferVolumeReportParams},
+        session_sql::CreateSessionParams,
+        store::{
+            check_position_account, check_status_change, check_transfer_accounts, Store,
+            TransferTxParams, TransferTxResult, UpdateAccountStatusParams,
+        },
+        transfer_sql::{ListTransfersParams, TransferDirection},
+        user_sql::CreateUserParams,
+    },
+    decimal::Decimal,
+    fx::{self, FX_POSITION_OWNER},
+    metrics::PoolStats,
+    models::{
+        Account, AccountStatus, CurrencyInfo, Entry, ExchangeRate, FxQuote, IdempotencyKey, Role,
+        Session, Transfer, User,
+    },
+    money::{Money, MoneyError},
+    prelude::*,
+};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use sqlx::error::ErrorKind;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    sync::{Arc, Mutex, MutexGuard},
+};
+use tokio::sync::{Mutex as RowLock, OwnedMutexGuard};
+use uuid::Uuid;
+
+/// In-memory `Store` for tests that should not need Postgres.
+///
+/// Reads see the last committed state, like MVCC reads in Postgres. Writes to
+/// an account take a per-row lock first, as `SELECT ... FOR NO KEY UPDATE`
+/// does, and `transfer_tx` takes both row locks in id order, so concurrent
+/// transfers interleave the same way they do against the database. Errors are
+/// `sqlx::Error`s carrying the SQLSTATE and constraint name Postgres would
+/// report, so they map to the same API errors.
+#[derive(Default)]
+pub struct MemStore {
+    tables: Mutex<Tables>,
+    row_locks: Mutex<HashMap<i64, Arc<RowLock<()>>>>,
+}
+
+#[derive(Default)]
+struct Tables {
+    currencies: BTreeMap<Currency, CurrencyInfo>,
+    exchange_rates: BTreeMap<i64, ExchangeRate>,
+    fx_quotes: HashMap<Uuid, FxQuote>,
+    account_status_changes: Vec<CreateAccountStatusChangeParams>,
+    users: BTreeMap<String, User>,
+    accounts: BTreeMap<i64, Account>,
+    entries: BTreeMap<i64, Entry>,
+    transfers: BTreeMap<i64, Transfer>,
+    sessions: HashMap<Uuid, Session>,
+    idempotency_keys: HashMap<(String, String), IdempotencyKey>,
+    last_exchange_rate_id: i64,
+    last_account_id: i64,
+    last_entry_id: i64,
+    last_transfer_id: i64,
+}
+
+impl MemStore {
+    /// An empty store with the rows the migrations seed: the ISO 4217
+    /// currencies and the owner of the FX position accounts.
+    pub fn new() -> Self {
+        let store = Self::default();
+        let mut tables = store.tables();
+        tables.currencies = iso_4217()
+            .into_iter()
+            .map(|info| (info.code, info))
+            .collect();
+        let fx_position = User {
+            username: FX_POSITION_OWNER.to_string(),
+            hashed_password: String::new(),
+            full_name: "FX position".to_string(),
+            email: format!("{FX_POSITION_OWNER}@users.invalid"),
+            password_changed_at: DateTime::<Utc>::MIN_UTC,
+            created_at: Utc::now(),
+            role: Role::Depositor,
+        };
+        tables
+            .users
+            .insert(fx_position.username.clone(), fx_position);
+        drop(tables);
+        store
+    }
+
+    fn tables(&self) -> MutexGuard<'_, Tables> {
+        self.tables.lock().expect("mem store poisoned")
+    }
+
+    async fn lock_account(&self, id: i64) -> OwnedMutexGuard<()> {
+        let lock = self
+            .row_locks
+            .lock()
+            .expect("mem store poisoned")
+            .entry(id)
+            .or_default()
+            .clone();
+        lock.lock_owned().await
+    }
+
+    /// Applies `f` to account `id` under its row lock, then enforces the
+    /// accounts table CHECK constraints before committing the change.
+    async fn update_account_row(
+        &self,
+        id: i64,
+        f: impl FnOnce(&mut Account) -> Result<()> + Send,
+    ) -> Result<Account> {
+        let _row = self.lock_account(id).await;
+        let mut tables = self.tables();
+        let mut account = tables.account(id)?.clone();
+        f(&mut account)?;
+        check_account(&account)?;
+        tables.accounts.insert(id, account.clone());
+        Ok(account)
+    }
+}
+
+impl Tables {
+    fn account(&self, id: i64) -> SQLResult<&Account> {
+        self.accounts.get(&id).ok_or(sqlx::Error::RowNotFound)
+    }
+
+    fn find_exchange_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
+        at: DateTime<Utc>,
+    ) -> Option<&ExchangeRate> {
+        self.exchange_rates
+            .values()
+            .filter(|rate| {
+                rate.base_currency == base_currency
+                    && rate.quote_currency == quote_currency
+                    && rate.effective_from <= at
+            })
+            .max_by_key(|rate| rate.effective_from)
+    }
+
+    /// The FX position account in `currency`, opened on first use.
+    fn fx_position_account(&mut self, currency: Currency) -> Account {
+        let position = self
+            .accounts
+            .values()
+            .find(|account| account.owner == FX_POSITION_OWNER && account.currency == currency);
+        if let Some(position) = position {
+            return position.clone();
+        }
+        self.last_account_id += 1;
+        let account = Account {
+            id: self.last_account_id,
+            owner: FX_POSITION_OWNER.to_string(),
+            balance: Money::zero(currency),
+            currency,
+            created_at: Utc::now(),
+            status: AccountStatus::Active,
+            overdraft_limit: Money::zero(currency),
+        };
+        self.accounts.insert(account.id, account.clone());
+        account
+    }
+
+    fn insert_entry(&mut self, account_id: i64, amount: Money, transfer_id: Option<i64>) -> Entry {
+        self.last_entry_id += 1;
+        let entry = Entry {
+            id: self.last_entry_id,
+            account_id,
+            amount,
+            created_at: Utc::now(),
+            transfer_id,
+        };
+        self.entries.insert(entry.id, entry.clone());
+        entry
+    }
+}
+
+fn check_account(account: &Account) -> Result<()> {
+    if account.overdraft_limit.is_negative() {
+        return Err(violation(CHECK_VIOLATION, "accounts_overdraft_limit_check"));
+    }
+    if account
+        .balance
+        .checked_add(account.overdraft_limit)?
+        .is_negative()
+        && account.owner != FX_POSITION_OWNER
+    {
+        return Err(violation(CHECK_VIOLATION, "accounts_balance_check"));
+    }
+    if account.status == AccountStatus::Closed && account.balance.minor_units != 0 {
+        return Err(violation(CHECK_VIOLATION, "accounts_closed_balance_check"));
+    }
+    Ok(())
+}
+
+fn page<T: Clone>(rows: impl Iterator<Item = T>, limit: i64, offset: i64) -> Vec<T> {
+    rows.skip(offset.max(0) as usize)
+        .take(limit.max(0) as usize)
+        .collect()
+}
+
+fn in_range(
+    created_at: DateTime<Utc>,
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> bool {
+    start_time.is_none_or(|start| created_at >= start)
+        && end_time.is_none_or(|end| created_at < end)
+}
+
+/// Constraint violation as Postgres would raise it.
+#[derive(Debug)]
+struct MemDatabaseError {
+    code: &'static str,
+    constraint: &'static str,
+    message: String,
+}
+
+const UNIQUE_VIOLATION: &str = "23505";
+const FOREIGN_KEY_VIOLATION: &str = "23503";
+const CHECK_VIOLATION: &str = "23514";
+
+fn violation(code: &'static str, constraint: &'static str) -> Error {
+    let message = format!("violates constraint \"{constraint}\"");
+    sqlx::Error::Database(Box::new(MemDatabaseError {
+        code,
+        constraint,
+        message,
+    }))
+    .into()
+}
+
+impl core::fmt::Display for MemDatabaseError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        write!(fmt, "{}", self.message)
+    }
+}
+
+impl std::error::Error for MemDatabaseError {}
+
+impl sqlx::error::DatabaseError for MemDatabaseError {
+    fn message(&self) -> &str {
+        &self.message
+    }
+
+    fn code(&self) -> Option<Cow<'_, str>> {
+        Some(Cow::Borrowed(self.code))
+    }
+
+    fn constraint(&self) -> Option<&str> {
+        Some(self.constraint)
+    }
+
+    fn as_error(&self) -> &(dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn as_error_mut(&mut self) -> &mut (dyn std::error::Error + Send + Sync + 'static) {
+        self
+    }
+
+    fn into_error(self: Box<Self>) -> Box<dyn std::error::Error + Send + Sync + 'static> {
+        self
+    }
+
+    fn kind(&self) -> ErrorKind {
+        match self.code {
+            UNIQUE_VIOLATION => ErrorKind::UniqueViolation,
+            FOREIGN_KEY_VIOLATION => ErrorKind::ForeignKeyViolation,
+            CHECK_VIOLATION => ErrorKind::CheckViolation,
+            _ => ErrorKind::Other,
+        }
+    }
+}
+
+#[async_trait]
+impl Store for MemStore {
+    async fn create_account(&self, arg: CreateAccountParams) -> Result<Account> {
+        let mut tables = self.tables();
+        if !tables.users.contains_key(&arg.owner) {
+            return Err(violation(FOREIGN_KEY_VIOLATION, "accounts_owner_fkey"));
+        }
+        let currency = arg.balance.currency;
+        if !tables.currencies.contains_key(&currency) {
+            return Err(violation(FOREIGN_KEY_VIOLATION, "accounts_currency_fkey"));
+        }
+        let account = Account {
+            id: tables.last_account_id + 1,
+            owner: arg.owner,
+            balance: arg.balance,
+            currency,
+            created_at: Utc::now(),
+            status: AccountStatus::Active,
+            overdraft_limit: Money::zero(currency),
+        };
+        check_account(&account)?;
+        tables.last_account_id = account.id;
+        tables.accounts.insert(account.id, account.clone());
+        Ok(account)
+    }
+
+    async fn get_account(&self, id: i64) -> Result<Account> {
+        Ok(self.tables().account(id)?.clone())
+    }
+
+    async fn list_accounts(&self, arg: ListAccountsParams) -> Result<Vec<Account>> {
+        let tables = self.tables();
+        let accounts = tables
+            .accounts
+            .values()
+            .filter(|account| {
+                arg.owner
+                    .as_ref()
+                    .is_none_or(|owner| &account.owner == owner)
+            })
+            .cloned();
+        Ok(page(accounts, arg.limit, arg.offset))
+    }
+
+    async fn update_account_status(&self, arg: UpdateAccountStatusParams) -> Result<Account> {
+        let _row = self.lock_account(arg.id).await;
+        let mut tables = self.tables();
+        let mut account = tables.account(arg.id)?.clone();
+        check_status_change(&account, arg.status)?;
+        if !tables.users.contains_key(&arg.changed_by) {
+            return Err(violation(
+                FOREIGN_KEY_VIOLATION,
+                "account_status_changes_changed_by_fkey",
+            ));
+        }
+        let from_status = account.status;
+        account.status = arg.status;
+        check_account(&account)?;
+        tables.accounts.insert(account.id, account.clone());
+        tables
+            .account_status_changes
+            .push(CreateAccountStatusChangeParams {
+                account_id: account.id,
+                from_status,
+                to_status: account.status,
+                reason: arg.reason,
+                changed_by: arg.changed_by,
+            });
+        Ok(account)
+    }
+
+    async fn update_overdraft_limit(&self, id: i64, overdraft_limit: Money) -> Result<Account> {
+        self.update_account_row(id, |account| {
+            account.overdraft_limit.same_currency(overdraft_limit)?;
+            account.overdraft_limit = overdraft_limit;
+            Ok(())
+        })
+        .await
+    }
+
+    async fn create_exchange_rate(&self, arg: CreateExchangeRateParams) -> Result<ExchangeRate> {
+        let mut tables = self.tables();
+        if !tables.currencies.contains_key(&arg.base_currency) {
+            return Err(violation(
+                FOREIGN_KEY_VIOLATION,
+                "exchange_rates_base_currency_fkey",
+            ));
+        }
+        if !tables.currencies.contains_key(&arg.quote_currency) {
+            return Err(violation(
+                FOREIGN_KEY_VIOLATION,
+                "exchange_rates_quote_currency_fkey",
+            ));
+        }
+        if !arg.rate.is_positive() {
+            return Err(violation(CHECK_VIOLATION, "exchange_rates_rate_check"));
+        }
+        if !(0..=10_000).contains(&arg.spread_bps) {
+            return Err(violation(
+                CHECK_VIOLATION,
+                "exchange_rates_spread_bps_check",
+            ));
+        }
+        if arg.base_currency == arg.quote_currency {
+            return Err(violation(CHECK_VIOLATION, "exchange_rates_pair_check"));
+        }
+        if tables.exchange_rates.values().any(|rate| {
+            rate.base_currency == arg.base_currency
+                && rate.quote_currency == arg.quote_currency
+                && rate.effective_from == arg.effective_from
+        }) {
+            return Err(violation(
+                UNIQUE_VIOLATION,
+                "exchange_rates_pair_effective_from_key",
+            ));
+        }
+        tables.last_exchange_rate_id += 1;
+        let rate = ExchangeRate {
+            id: tables.last_exchange_rate_id,
+            base_currency: arg.base_currency,
+            quote_currency: arg.quote_currency,
+            rate: arg.rate,
+            spread_bps: arg.spread_bps,
+            effective_from: arg.effective_from,
+            created_at: Utc::now(),
+        };
+        tables.exchange_rates.insert(rate.id, rate.clone());
+        Ok(rate)
+    }
+
+    async fn find_exchange_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
+        at: DateTime<Utc>,
+    ) -> Result<Option<ExchangeRate>> {
+        Ok(self
+            .tables()
+            .find_exchange_rate(base_currency, quote_currency, at)
+            .cloned())
+    }
+
+    async fn create_fx_quote(&self, arg: CreateFxQuoteParams) -> Result<FxQuote> {
+        let mut tables = self.tables();
+        if !tables.users.contains_key(&arg.owner) {
+            return Err(violation(FOREIGN_KEY_VIOLATION, "fx_quotes_owner_fkey"));
+        }
+        let Some(rate) = tables.exchange_rates.get(&arg.exchange_rate_id) else {
+            return Err(violation(
+                FOREIGN_KEY_VIOLATION,
+                "fx_quotes_exchange_rate_id_fkey",
+            ));
+        };
+        if tables.fx_quotes.contains_key(&arg.id) {
+            return Err(violation(UNIQUE_VIOLATION, "fx_quotes_pkey"));
+        }
+        let quote = FxQuote {
+            id: arg.id,
+            owner: arg.owner,
+            exchange_rate_id: rate.id,
+            base_currency: rate.base_currency,
+            quote_currency: rate.quote_currency,
+            rate: rate.rate,
+            spread_bps: rate.spread_bps,
+            expires_at: arg.expires_at,
+            transfer_id: None,
+            used_at: None,
+            created_at: Utc::now(),
+        };
+        tables.fx_quotes.insert(quote.id, quote.clone());
+        Ok(quote)
+    }
+
+    async fn get_fx_quote(&self, id: Uuid) -> Result<FxQuote> {
+        let tables = self.tables();
+        let quote = tables.fx_quotes.get(&id).ok_or(sqlx::Error::RowNotFound)?;
+        Ok(quote.clone())
+    }
+
+    async fn get_entry(&self, id: i64) -> Result<Entry> {
+        let tables = self.tables();
+        let entry = tables.entries.get(&id).ok_or(sqlx::Error::RowNotFound)?;
+        Ok(entry.clone())
+    }
+
+    async fn list_entries(&self, arg: ListEntriesParams) -> Result<Vec<Entry>> {
+        let tables = self.tables();
+        let entries = tables
+            .entries
+            .values()
+            .filter(|entry| {
+                entry.account_id == arg.account_id
+                    && in_range(entry.created_at, arg.start_time, arg.end_time)
+            })
+            .cloned();
+        Ok(page(entries, arg.limit, arg.offset))
+    }
+
+    async fn get_transfer(&self, id: i64) -> Result<Transfer> {
+        let tables = self.tables();
+        let transfer = tables.transfers.get(&id).ok_or(sqlx::Error::RowNotFound)?;
+        Ok(transfer.clone())
+    }
+
+    async fn list_transfers(&self, arg: ListTransfersParams) -> Result<Vec<Transfer>> {
+        let outgoing = arg.direction != Some(TransferDirection::Incoming);
+        let incoming = arg.direction != Some(TransferDirection::Outgoing);
+
+        let tables = self.tables();
+        let transfers = tables
+            .transfers
+            .values()
+            .filter(|transfer| {
+                arg.account_id.is_none_or(|id| {
+                    (outgoing && transfer.from_account_id == id)
+                        || (incoming && transfer.to_account_id == id)
+                }) && in_range(transfer.created_at, arg.start_time, arg.end_time)
+            })
+            .cloned();
+        Ok(page(transfers, arg.limit, arg.offset))
+    }
+
+    async fn transfer_tx(&self, arg: TransferTxParams) -> Result<TransferTxResult> {
+        // The account mutexes are not reentrant, so locking one twice would
+        // never return.
+        if arg.from_account_id == arg.to_account_id {
+            return Err(LedgerError::SameAccount {
+                account_id: arg.from_account_id,
+            }
+            .into());
+        }
+        let (first_id, second_id) = if arg.from_account_id < arg.to_account_id {
+            (arg.from_account_id, arg.to_account_id)
+        } else {
+            (arg.to_account_id, arg.from_account_id)
+        };
+        let _first = self.lock_account(first_id).await;
+        let _second = self.lock_account(second_id).await;
+
+        // Both rows are locked, so nothing below can interleave with another
+        // writer to these accounts.
+        let mut tables = self.tables();
+        let mut from_account = tables.account(arg.from_account_id)?.clone();
+        let mut to_account = tables.account(arg.to_account_id)?.clone();
+        check_transfer_accounts(&from_account, &to_account)?;
+
+        from_account.balance.same_currency(arg.amount)?;
+        let to_currency = arg.to_currency.unwrap_or(arg.amount.currency);
+        if to_account.currency != to_currency {
+            return Err(MoneyError::CurrencyMismatch {
+                expected: to_account.currency,
+                found: to_currency,
+            }
+            .into());
+        }
+
+        let available = from_account
+            .balance
+            .checked_add(from_account.overdraft_limit)?;
+        if available.checked_sub(arg.amount)?.is_negative() {
+            return Err(LedgerError::InsufficientFunds {
+                account_id: from_account.id,
+                available,
+            }
+            .into());
+        }
+
+        let conversion = match arg.quote_id {
+            Some(quote_id) => {
+                let quote = tables
+                    .fx_quotes
+                    .get(&quote_id)
+                    .ok_or(sqlx::Error::RowNotFound)?;
+                fx::check_quote(quote, arg.amount.currency, to_currency, Utc::now())?;
+                let rate = &tables.exchange_rates[&quote.exchange_rate_id];
+                Some(fx::convert(arg.amount, rate)?)
+            }
+            None if to_currency == arg.amount.currency => None,
+            None => {
+                let rate = tables
+                    .find_exchange_rate(arg.amount.currency, to_currency, Utc::now())
+                    .ok_or(LedgerError::ExchangeRateUnavailable {
+                        from: arg.amount.currency,
+                        to: to_currency,
+                    })?;
+                Some(fx::convert(arg.amount, rate)?)
+            }
+        };
+        let credit = conversion.map_or(arg.amount, |conversion| conversion.amount);
+
+        from_account.balance = from_account.balance.checked_sub(arg.amount)?;
+        to_account.balance = to_account.balance.checked_add(credit)?;
+        check_account(&from_account)?;
+        check_account(&to_account)?;
+        if conversion.is_some() {
+            for currency in [arg.amount.currency, to_currency] {
+                check_position_account(&tables.fx_position_account(currency))?;
+            }
+        }
+
+        tables.last_transfer_id += 1;
+        let transfer = Transfer {
+            id: tables.last_transfer_id,
+            from_account_id: arg.from_account_id,
+            to_account_id: arg.to_account_id,
+            amount: arg.amount,
+            to_amount: credit,
+            exchange_rate_id: conversion.map(|conversion| conversion.exchange_rate_id),
+            fx_rate: conversion.map(|conversion| conversion.rate),
+            fx_spread: conversion.map_or(Money::zero(to_currency), |conversion| conversion.spread),
+            fx_residue: conversion.map_or(Decimal::ZERO, |conversion| conversion.residue),
+            created_at: Utc::now(),
+        };
+        tables.transfers.insert(transfer.id, transfer.clone());
+        if let Some(quote) = arg
+            .quote_id
+            .and_then(|quote_id| tables.fx_quotes.get_mut(&quote_id))
+        {
+            quote.transfer_id = Some(transfer.id);
+            quote.used_at = Some(transfer.created_at);
+        }
+        let from_entry = tables.insert_entry(
+            arg.from_account_id,
+            arg.amount.checked_neg()?,
+            Some(transfer.id),
+        );
+        let to_entry = tables.insert_entry(arg.to_account_id, credit, Some(transfer.id));
+        if conversion.is_some() {
+            // The bank buys the sender's currency and sells the receiver's.
+            for amount in [arg.amount, credit.checked_neg()?] {
+                let mut position = tables.fx_position_account(amount.currency);
+                position.balance = position.balance.checked_add(amount)?;
+                tables.insert_entry(position.id, amount, Some(transfer.id));
+                tables.accounts.insert(position.id, position);
+            }
+        }
+        tables
+            .accounts
+            .insert(from_account.id, from_account.clone());
+        tables.accounts.insert(to_account.id, to_account.clone());
+
+        Ok(TransferTxResult {
+            transfer,
+            from_account,
+            to_account,
+            from_entry,
+            to_entry,
+        })
+    }
+
+    async fn create_user(&self, arg: CreateUserParams) -> Result<User> {
+        let mut tables = self.tables();
+        if tables.users.contains_key(&arg.username) {
+            return Err(violation(UNIQUE_VIOLATION, "users_pkey"));
+        }
+        if tables.users.values().any(|user| user.email == arg.email) {
+            return Err(violation(UNIQUE_VIOLATION, "users_email_key"));
+        }
+        let user = User {
+            username: arg.username,
+            hashed_password: arg.hashed_password,
+            full_name: arg.full_name,
+            email: arg.email,
+            password_changed_at: DateTime::<Utc>::MIN_UTC,
+            created_at: Utc::now(),
+            role: arg.role,
+        };
+        tables.users.insert(user.username.clone(), user.clone());
+        Ok(user)
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User> {
+        let tables = self.tables();
+        let user = tables.users.get(username).ok_or(sqlx::Error::RowNotFound)?;
+        Ok(user.clone())
+    }
+
+    async fn create_session(&self, arg: CreateSessionParams) -> Result<Session> {
+        let mut tables = self.tables();
+        if !tables.users.contains_key(&arg.username) {
+            return Err(violation(FOREIGN_KEY_VIOLATION, "sessions_username_fkey"));
+        }
+        if tables.sessions.contains_key(&arg.id) {
+            return Err(violation(UNIQUE_VIOLATION, "sessions_pkey"));
+        }
+        let session = Session {
+            id: arg.id,
+            username: arg.username,
+            refresh_token: arg.refresh_token,
+            user_agent: arg.user_agent,
+            client_ip: arg.client_ip,
+            is_blocked: false,
+            expires_at: arg.expires_at,
+            created_at: Utc::now(),
+        };
+        tables.sessions.insert(session.id, session.clone());
+        Ok(session)
+    }
+
+    async fn get_session(&self, id: Uuid) -> Result<Session> {
+        let tables = self.tables();
+        let session = tables.sessions.get(&id).ok_or(sqlx::Error::RowNotFound)?;
+        Ok(session.clone())
+    }
+
+    async fn list_active_sessions(&self, username: &str) -> Result<Vec<Session>> {
+        let now = Utc::now();
+        let tables = self.tables();
+        let mut sessions: Vec<Session> = tables
+            .sessions
+            .values()
+            .filter(|session| {
+                session.username == username && !session.is_blocked && session.expires_at > now
+            })
+            .cloned()
+            .collect();
+        sessions.sort_by_key(|session| std::cmp::Reverse(session.created_at));
+        Ok(sessions)
+    }
+
+    async fn block_session(&self, id: Uuid) -> Result<Session> {
+        let mut tables = self.tables();
+        let session = tables
+            .sessions
+            .get_mut(&id)
+            .ok_or(sqlx::Error::RowNotFound)?;
+        session.is_blocked = true;
+        Ok(session.clone())
+    }
+
+    async fn balance_report(&self) -> Result<Vec<BalanceReport>> {
+        let tables = self.tables();
+        let mut report: BTreeMap<Currency, BalanceReport> = BTreeMap::new();
+        for account in tables.accounts.values() {
+            let row = report
+                .entry(account.currency)
+                .or_insert_with(|| BalanceReport {
+                    currency: account.currency,
+                    accounts: 0,
+                    frozen_accounts: 0,
+                    total_balance: Money::zero(account.currency),
+                });
+            row.accounts += 1;
+            row.frozen_accounts += i64::from(account.status == AccountStatus::Frozen);
+            row.total_balance = row.total_balance.checked_add(account.balance)?;
+        }
+        Ok(report.into_values().collect())
+    }
+
+    async fn transfer_volume_report(
+        &self,
+        arg: TransferVolumeReportParams,
+    ) -> Result<Vec<TransferVolumeReport>> {
+        let tables = self.tables();
+        let mut report: BTreeMap<Currency, TransferVolumeReport> = BTreeMap::new();
+        for transfer in tables.transfers.values() {
+            if !in_range(transfer.created_at, arg.start_time, arg.end_time) {
+                continue;
+            }
+            let Some(from_account) = tables.accounts.get(&transfer.from_account_id) else {
+                continue;
+            };
+            let row = report
+                .entry(from_account.currency)
+                .or_insert_with(|| TransferVolumeReport {
+                    currency: from_account.currency,
+                    transfers: 0,
+                    total_amount: Money::zero(from_account.currency),
+                });
+            row.transfers += 1;
+            row.total_amount = row.total_amount.checked_add(transfer.amount)?;
+        }
+        Ok(report.into_values().collect())
+    }
+
+    async fn create_idempotency_key(
+        &self,
+        arg: CreateIdempotencyKeyParams,
+    ) -> Result<Option<IdempotencyKey>> {
+        let mut tables = self.tables();
+        let id = (arg.scope.clone(), arg.key.clone());
+        if tables.idempotency_keys.contains_key(&id) {
+            return Ok(None);
+        }
+        let key = IdempotencyKey {
+            scope: arg.scope,
+            key: arg.key,
+            request_hash: arg.request_hash,
+            response_status: None,
+            response_content_type: None,
+            response_body: None,
+            created_at: Utc::now(),
+        };
+        tables.idempotency_keys.insert(id, key.clone());
+        Ok(Some(key))
+    }
+
+    async fn get_idempotency_key(&self, scope: &str, key: &str) -> Result<IdempotencyKey> {
+        let tables = self.tables();
+        let key = tables
+            .idempotency_keys
+            .get(&(scope.to_string(), key.to_string()))
+            .ok_or(sqlx::Error::RowNotFound)?;
+        Ok(key.clone())
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        arg: CompleteIdempotencyKeyParams,
+    ) -> Result<IdempotencyKey> {
+        let mut tables = self.tables();
+        let key = tables
+            .idempotency_keys
+            .get_mut(&(arg.scope, arg.key))
+            .ok_or(sqlx::Error::RowNotFound)?;
+        key.response_status = Some(arg.response_status);
+        key.response_content_type = arg.response_content_type;
+        key.response_body = Some(arg.response_body);
+        Ok(key.clone())
+    }
+
+    async fn delete_idempotency_key(&self, scope: &str, key: &str) -> Result<()> {
+        self.tables()
+            .idempotency_keys
+            .remove(&(scope.to_string(), key.to_string()));
+        Ok(())
+    }
+
+    async fn delete_expired_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        expired_before: DateTime<Utc>,
+    ) -> Result<bool> {
+        let mut tables = self.tables();
+        let id = (scope.to_string(), key.to_string());
+        match tables.idempotency_keys.get(&id) {
+            Some(existing) if existing.created_at < expired_before => {
+                tables.idempotency_keys.remove(&id);
+                Ok(true)
+            }
+            _ => Ok(false),
+        }
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<CurrencyInfo>> {
+        Ok(self.tables().currencies.values().copied().collect())
+    }
+
+    async fn ping(&self) -> Result<()> {
+        Ok(())
+    }
+
+    /// Always fully migrated: there is no schema to migrate.
+    async fn schema_version(&self) -> Result<Option<i64>> {
+        Ok(migrate::expected_version())
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        None
+    }
+}
+
+mod tests {
+    use super::*;
+    use crate::{models::Role, utils::*};
+
+    fn usd(minor_units: i64) -> Money {
+        Money::new(minor_units, "USD".parse().unwrap())
+    }
+
+    async fn funded_account(store: &MemStore, balance: i64) -> Account {
+        let user = random_store_user(store, Role::Depositor).await.unwrap();
+        random_store_account(store, &user.username, "USD", balance)
+            .await
+            .unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_opposite_directions() {
+        let store = Arc::new(MemStore::new());
+        let account1 = funded_account(&store, 1000).await;
+        let account2 = funded_account(&store, 1000).await;
+
+        let n = 10;
+        let mut handles = vec![];
+        for i in 0..n {
+            let store = store.clone();
+            let (from, to) = if i % 2 == 1 {
+                (account2.id, account1.id)
+            } else {
+                (account1.id, account2.id)
+            };
+            handles.push(tokio::spawn(async move {
+                store
+                    .transfer_tx(TransferTxParams {
+                        from_account_id: from,
+                        to_account_id: to,
+                        amount: usd(10),
+                        to_currency: None,
+                        quote_id: None,
+                    })
+                    .await
+                    .unwrap()
+            }));
+        }
+        for handle in futures::future::join_all(handles).await {
+            let result = handle.unwrap();
+            assert_eq!(result.from_entry.transfer_id, Some(result.transfer.id));
+            assert_eq!(
+                Ok(result.from_entry.amount),
+                result.to_entry.amount.checked_neg()
+            );
+        }
+
+        assert_eq!(
+            store.get_account(account1.id).await.unwrap().balance,
+            usd(1000)
+        );
+        assert_eq!(
+            store.get_account(account2.id).await.unwrap().balance,
+            usd(1000)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_never_overdraws() {
+        let store = Arc::new(MemStore::new());
+        let from_account = funded_account(&store, 100).await;
+        let to_account = funded_account(&store, 0).await;
+
+        let handles: Vec<_> = (0..10)
+            .map(|_| {
+                let store = store.clone();
+                tokio::spawn(async move {
+                    store
+                        .transfer_tx(TransferTxParams {
+                            from_account_id: from_account.id,
+                            to_account_id: to_account.id,
+                            amount: usd(30),
+                            to_currency: None,
+                            quote_id: None,
+                        })
+                        .await
+                })
+            })
+            .collect();
+        let succeeded = futures::future::join_all(handles)
+            .await
+            .into_iter()
+            .filter(|result| result.as_ref().unwrap().is_ok())
+            .count();
+
+        assert_eq!(succeeded, 3);
+        let from_account = store.get_account(from_account.id).await.unwrap();
+        assert_eq!(from_account.balance, usd(10));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_same_account() {
+        let store = MemStore::new();
+        let account = funded_account(&store, 100).await;
+
+        let err: ServerError = store
+            .transfer_tx(TransferTxParams {
+                from_account_id: account.id,
+                to_account_id: account.id,
+                amount: usd(10),
+                to_currency: None,
+                quote_id: None,
+            })
+            .await
+            .unwrap_err()
+            .into();
+
+        assert!(matches!(
+            err,
+            ServerError::Ledger(LedgerError::SameAccount { account_id }) if account_id == account.id
+        ));
+        let account = store.get_account(account.id).await.unwrap();
+        assert_eq!(account.balance, usd(100));
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_balances_each_currency() {
+        let store = MemStore::new();
+        let user = random_store_user(&store, Role::Depositor).await.unwrap();
+        let from_account = random_store_account(&store, &user.username, "USD", 500)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&store, &user.username, "JPY", 0)
+            .await
+            .unwrap();
+        store
+            .create_exchange_rate(CreateExchangeRateParams {
+                base_currency: from_account.currency,
+                quote_currency: to_account.currency,
+                rate: "149.5".parse().unwrap(),
+                spread_bps: 0,
+                effective_from: Utc::now(),
+            })
+            .await
+            .unwrap();
+
+        for _ in 0..2 {
+            store
+                .transfer_tx(TransferTxParams {
+                    from_account_id: from_account.id,
+                    to_account_id: to_account.id,
+                    amount: usd(205),
+                    to_currency: Some(to_account.currency),
+                    quote_id: None,
+                })
+                .await
+                .unwrap();
+        }
+
+        let report = store.balance_report().await.unwrap();
+        for row in report {
+            let expected = if row.currency == from_account.currency {
+                from_account.balance
+            } else {
+                Money::zero(row.currency)
+            };
+            assert_eq!(row.total_balance, expected);
+        }
+        let to_account = store.get_account(to_account.id).await.unwrap();
+        assert_eq!(to_account.balance.amount(), "612");
+    }
+
+    #[tokio::test]
+    async fn test_fx_position_accounts_stay_active() {
+        let store = MemStore::new();
+        let user = random_store_user(&store, Role::Banker).await.unwrap();
+        let from_account = random_store_account(&store, &user.username, "USD", 500)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&store, &user.username, "JPY", 0)
+            .await
+            .unwrap();
+        store
+            .create_exchange_rate(CreateExchangeRateParams {
+                base_currency: from_account.currency,
+                quote_currency: to_account.currency,
+                rate: "149.5".parse().unwrap(),
+                spread_bps: 0,
+                effective_from: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let position = store.tables().fx_position_account(to_account.currency);
+
+        let err: ServerError = store
+            .update_account_status(UpdateAccountStatusParams {
+                id: position.id,
+                status: AccountStatus::Frozen,
+                reason: "reconciliation".to_string(),
+                changed_by: user.username.clone(),
+            })
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(
+            err,
+            ServerError::Ledger(LedgerError::SystemAccount { account_id }) if account_id == position.id
+        ));
+
+        // A position frozen behind the store's back blocks conversions.
+        store
+            .tables()
+            .accounts
+            .get_mut(&position.id)
+            .unwrap()
+            .status = AccountStatus::Frozen;
+        let err: ServerError = store
+            .transfer_tx(TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount: usd(100),
+                to_currency: Some(to_account.currency),
+                quote_id: None,
+            })
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(
+            err,
+            ServerError::Ledger(LedgerError::AccountUnavailable { account_id, .. })
+                if account_id == position.id
+        ));
+        assert_eq!(
+            store.get_account(from_account.id).await.unwrap().balance,
+            from_account.balance
+        );
+        assert!(store.tables().transfers.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_constraint_errors_match_postgres() {
+        let store = MemStore::new();
+        let account = funded_account(&store, 0).await;
+
+        let err: ServerError = store
+            .update_account_row(account.id, |account| {
+                account.balance = usd(-1);
+                Ok(())
+            })
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(
+            err,
+            ServerError::Database(DatabaseError::CheckViolation { constraint })
+                if constraint.as_deref() == Some("accounts_balance_check")
+        ));
+
+        let err: ServerError = store
+            .create_account(CreateAccountParams {
+                owner: account.owner.clone(),
+                balance: Money::zero("XTS".parse().unwrap()),
+            })
+            .await
+            .unwrap_err()
+            .into();
+        assert!(matches!(
+            err,
+            ServerError::Database(DatabaseError::ForeignKeyViolation { constraint })
+                if constraint.as_deref() == Some("accounts_currency_fkey")
+        ));
+
+        let err: ServerError = store.get_account(account.id + 1).await.unwrap_err().into();
+        assert_eq!(err.client_error(), ClientError::NotFound);
+
+        let user = store.get_user(&account.owner).await.unwrap();
+        let err: ServerError = store
+            .create_user(CreateUserParams {
+                username: user.username,
+                hashed_password: random_string(32),
+                full_name: random_owner(),
+                email: random_email(),
+                role: Role::Depositor,
+            })
+            .await
+            .unwrap_err()
+            .into();
+        assert_eq!(err.client_error(), ClientError::DuplicateResource);
+    }
+}
@@ -0,0 +1,246 @@
+use crate::prelude::*;
+use sqlx::{
+    migrate::{Migrate, Migrator},
+    PgPool,
+};
+use tracing::instrument;
+
+/// SQLSTATE of a missing table: nothing has been migrated yet.
+const UNDEFINED_TABLE: &str = "42P01";
+
+/// The `migrations/` directory, embedded at compile time.
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct MigrationStatus {
+    pub version: i64,
+    pub description: String,
+    pub applied: bool,
+    /// The applied migration differs from the embedded one.
+    pub checksum_mismatch: bool,
+}
+
+/// Version of the newest embedded migration, which a fully migrated database is at.
+pub fn expected_version() -> Option<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| migration.version)
+        .max()
+}
+
+/// Applies every pending migration.
+#[instrument(skip(pool))]
+pub async fn migrate_up(pool: &PgPool) -> Result<()> {
+    MIGRATOR.run(pool).await?;
+    Ok(())
+}
+
+/// Reverts the newest applied migration, returning its version, or `None`
+/// when nothing is applied.
+#[instrument(skip(pool))]
+pub async fn migrate_down(pool: &PgPool) -> Result<Option<i64>> {
+    let applied = applied_versions(pool).await?;
+    let Some(&latest) = applied.last() else {
+        return Ok(None);
+    };
+    let target = applied.iter().rev().nth(1).copied().unwrap_or(0);
+    MIGRATOR.undo(pool, target).await?;
+    Ok(Some(latest))
+}
+
+/// Every embedded migration and whether it has been applied, oldest first.
+#[instrument(skip(pool))]
+pub async fn migration_status(pool: &PgPool) -> Result<Vec<MigrationStatus>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let applied = conn.list_applied_migrations().await?;
+
+    let status = MIGRATOR
+        .iter()
+        .filter(|migration| migration.migration_type.is_up_migration())
+        .map(|migration| {
+            let applied = applied
+                .iter()
+                .find(|applied| applied.version == migration.version);
+            MigrationStatus {
+                version: migration.version,
+                description: migration.description.to_string(),
+                applied: applied.is_some(),
+                checksum_mismatch: applied
+                    .is_some_and(|applied| applied.checksum != migration.checksum),
+            }
+        })
+        .collect();
+    Ok(status)
+}
+
+/// Newest applied migration version, or `None` for an unmigrated database.
+/// Read-only, unlike the migrator, so readiness probes can call it.
+#[instrument(skip(pool))]
+pub async fn current_version(pool: &PgPool) -> Result<Option<i64>> {
+    let version = sqlx::query_scalar("SELECT max(version) FROM _sqlx_migrations")
+        .fetch_one(pool)
+        .await;
+    match version {
+        Ok(version) => Ok(version),
+        Err(sqlx::Error::Database(err)) if err.code().as_deref() == Some(UNDEFINED_TABLE) => {
+            Ok(None)
+        }
+        Err(err) => Err(err.into()),
+    }
+}
+
+#[instrument(skip(pool))]
+async fn applied_versions(pool: &PgPool) -> Result<Vec<i64>> {
+    let mut conn = pool.acquire().await?;
+    conn.ensure_migrations_table().await?;
+    let mut versions: Vec<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .into_iter()
+        .map(|migration| migration.version)
+        .collect();
+    versions.sort_unstable();
+    Ok(versions)
+}
+
+mod tests {
+    use super::*;
+    use crate::{
+        currency::Currency,
+        db::{
+            account_sql::update_account,
+            create_connection_pool,
+            exchange_rate_sql::{create_exchange_rate, CreateExchangeRateParams},
+            store::{transfer_tx, TransferTxParams},
+            TxOptions,
+        },
+        money::Money,
+        utils::*,
+    };
+    use sqlx::{postgres::PgConnectOptions, postgres::PgPoolOptions, ConnectOptions};
+
+    /// A new, empty database next to the one in `DATABASE_URL`, so that
+    /// reverting migrations does not disturb other tests.
+    async fn scratch_database(pool: &PgPool) -> (String, PgPool) {
+        let name = format!("simplebank_{}", random_string(12));
+        sqlx::query(&format!(r#"CREATE DATABASE "{name}""#))
+            .execute(pool)
+            .await
+            .unwrap();
+        let options: PgConnectOptions = std::env::var("DATABASE_URL").unwrap().parse().unwrap();
+        let scratch = PgPoolOptions::new()
+            .max_connections(2)
+            .connect_with(options.database(&name).disable_statement_logging())
+            .await
+            .unwrap();
+        (name, scratch)
+    }
+
+    #[tokio::test]
+    async fn test_migration_status() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+
+        let status = migration_status(&pool).await.unwrap();
+
+        assert_eq!(status.len(), MIGRATOR.iter().count() / 2);
+        assert!(status.iter().all(|migration| migration.applied));
+        assert!(status.iter().all(|migration| !migration.checksum_mismatch));
+        assert_eq!(current_version(&pool).await.unwrap(), expected_version());
+    }
+
+    #[tokio::test]
+    async fn test_current_version_of_unmigrated_database() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(1)).await.unwrap();
+        let (name, scratch) = scratch_database(&pool).await;
+
+        let version = current_version(&scratch).await;
+        let tables: Option<i64> = sqlx::query_scalar(
+            "SELECT count(*) FROM pg_tables WHERE tablename = '_sqlx_migrations'",
+        )
+        .fetch_one(&scratch)
+        .await
+        .ok();
+
+        scratch.close().await;
+        sqlx::query(&format!(r#"DROP DATABASE "{name}" WITH (FORCE)"#))
+            .execute(&pool)
+            .await
+            .unwrap();
+        assert_eq!(version.unwrap(), None);
+        // The probe must not create the migrations table.
+        assert_eq!(tables, Some(0));
+    }
+
+    #[tokio::test]
+    async fn test_migrate_down_after_conversion() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(1)).await.unwrap();
+        let (name, scratch) = scratch_database(&pool).await;
+        migrate_up(&scratch).await.unwrap();
+
+        let gbp: Currency = "GBP".parse().unwrap();
+        let jpy: Currency = "JPY".parse().unwrap();
+        let from_account = random_account_in(&scratch, gbp).await.unwrap();
+        update_account(
+            &scratch,
+            TxOptions::default(),
+            from_account.id,
+            Money::new(1_000, gbp),
+        )
+        .await
+        .unwrap();
+        let to_account = random_account_in(&scratch, jpy).await.unwrap();
+        create_exchange_rate(
+            &scratch,
+            CreateExchangeRateParams {
+                base_currency: gbp,
+                quote_currency: jpy,
+                rate: "189.6125".parse().unwrap(),
+                spread_bps: 0,
+                effective_from: chrono::Utc::now(),
+            },
+        )
+        .await
+        .unwrap();
+        // Leaves the GBP position account negative.
+        transfer_tx(
+            &scratch,
+            TxOptions::default(),
+            TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount: Money::new(500, gbp),
+                to_currency: Some(jpy),
+                quote_id: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        // Down to before exchange rates, and back up again.
+        let undone = MIGRATOR.undo(&scratch, 8).await;
+        let positions: Option<i64> =
+            sqlx::query_scalar("SELECT count(*) FROM accounts WHERE owner = 'fx-position'")
+                .fetch_one(&scratch)
+                .await
+                .ok();
+        // A failed undo keeps the migration lock, so only retry after a success.
+        let redone = match &undone {
+            Ok(()) => migrate_up(&scratch).await,
+            Err(_) => Ok(()),
+        };
+
+        scratch.close().await;
+        sqlx::query(&format!(r#"DROP DATABASE "{name}" WITH (FORCE)"#))
+            .execute(&pool)
+            .await
+            .unwrap();
+        undone.unwrap();
+        assert_eq!(positions, Some(0));
+        redone.unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+use crate::currency::Currency;
+use crate::money::Money;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tracing::instrument;
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BalanceReport {
+    pub currency: Currency,
+    pub accounts: i64,
+    pub frozen_accounts: i64,
+    pub total_balance: Money,
+}
+
+/// Number of accounts and sum of balances per currency.
+#[instrument(skip(pool))]
+pub async fn balance_report(pool: &sqlx::PgPool) -> Result<Vec<BalanceReport>> {
+    let report = sqlx::query_as!(
+        BalanceReport,
+        r#"SELECT currency AS "currency: Currency",
+            count(*) AS "accounts!",
+            count(*) FILTER (WHERE status = 'frozen') AS "frozen_accounts!",
+            (coalesce(sum(balance), 0)::bigint, currency) AS "total_balance!: Money"
+        FROM accounts
+        GROUP BY currency
+        ORDER BY currency;"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(report)
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct TransferVolumeReport {
+    pub currency: Currency,
+    pub transfers: i64,
+    pub total_amount: Money,
+}
+
+#[derive(Debug, Clone)]
+pub struct TransferVolumeReportParams {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+/// Number and total amount of transfers per currency of the sending account.
+#[instrument(skip(pool))]
+pub async fn transfer_volume_report(
+    pool: &sqlx::PgPool,
+    arg: TransferVolumeReportParams,
+) -> Result<Vec<TransferVolumeReport>> {
+    let report = sqlx::query_as!(
+        TransferVolumeReport,
+        r#"SELECT a.currency AS "currency: Currency",
+            count(*) AS "transfers!",
+            (coalesce(sum(t.amount), 0)::bigint, a.currency) AS "total_amount!: Money"
+        FROM transfers t
+        JOIN accounts a ON a.id = t.from_account_id
+        WHERE ($1::timestamptz IS NULL OR t.created_at >= $1)
+            AND ($2::timestamptz IS NULL OR t.created_at < $2)
+        GROUP BY a.currency
+        ORDER BY a.currency;"#,
+        arg.start_time,
+        arg.end_time
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(report)
+}
+
+mod tests {
+    use super::*;
+    use crate::{db::create_connection_pool, utils::*};
+
+    #[tokio::test]
+    async fn test_transfer_volume_report() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let account1 = random_account(&db).await.unwrap();
+        let account2 = random_account_in(&db, account1.currency).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, account1.currency);
+        let transfer1 = random_transfer(&db, account1.id, account2.id, money(10))
+            .await
+            .unwrap();
+        let transfer2 = random_transfer(&db, account1.id, account2.id, money(20))
+            .await
+            .unwrap();
+
+        let report = transfer_volume_report(
+            &db,
+            TransferVolumeReportParams {
+                start_time: Some(transfer1.created_at),
+                end_time: Some(transfer2.created_at + chrono::Duration::microseconds(1)),
+            },
+        )
+        .await
+        .unwrap();
+
+        // Concurrent tests may add transfers inside the window as well.
+        let row = report
+            .iter()
+            .find(|row| row.currency == account1.currency)
+            .unwrap();
+        assert!(row.transfers >= 2);
+        assert!(row.total_amount.minor_units >= 30);
+    }
+}
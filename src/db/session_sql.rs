@@ -0,0 +1,127 @@
+use crate::models::Session;
+use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use tracing::instrument;
+use uuid::Uuid;
+
+#[derive(Debug, Clone)]
+pub struct CreateSessionParams {
+    pub id: Uuid,
+    pub username: String,
+    pub refresh_token: String,
+    pub user_agent: String,
+    pub client_ip: String,
+    pub expires_at: DateTime<Utc>,
+}
+
+#[instrument(skip(pool, arg), fields(id = %arg.id, username = %arg.username))]
+pub async fn create_session(pool: &sqlx::PgPool, arg: CreateSessionParams) -> Result<Session> {
+    let session = sqlx::query_as!(
+        Session,
+        "INSERT INTO sessions (id, username, refresh_token, user_agent, client_ip, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING *;",
+        arg.id,
+        arg.username,
+        arg.refresh_token,
+        arg.user_agent,
+        arg.client_ip,
+        arg.expires_at
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(session)
+}
+
+#[instrument(skip(pool))]
+pub async fn get_session(pool: &sqlx::PgPool, id: Uuid) -> Result<Session> {
+    let session = sqlx::query_as!(Session, "SELECT * FROM sessions WHERE id = $1 LIMIT 1;", id)
+        .fetch_one(pool)
+        .await?;
+    Ok(session)
+}
+
+/// Sessions of `username` that are neither blocked nor expired, newest first.
+#[instrument(skip(pool))]
+pub async fn list_active_sessions(pool: &sqlx::PgPool, username: &str) -> Result<Vec<Session>> {
+    let sessions = sqlx::query_as!(
+        Session,
+        "SELECT * FROM sessions
+        WHERE username = $1 AND NOT is_blocked AND expires_at > now()
+        ORDER BY created_at DESC;",
+        username
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(sessions)
+}
+
+#[instrument(skip(pool))]
+pub async fn block_session(pool: &sqlx::PgPool, id: Uuid) -> Result<Session> {
+    let session = sqlx::query_as!(
+        Session,
+        "UPDATE sessions SET is_blocked = true WHERE id = $1 RETURNING *;",
+        id
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(session)
+}
+
+mod tests {
+    use super::*;
+    use crate::{db::create_connection_pool, utils::*};
+    use chrono::Duration;
+
+    #[tokio::test]
+    async fn test_create_session() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let user = random_user(&db).await.unwrap();
+        let arg = CreateSessionParams {
+            id: Uuid::new_v4(),
+            username: user.username,
+            refresh_token: random_string(32),
+            user_agent: random_string(10),
+            client_ip: "127.0.0.1".to_string(),
+            expires_at: Utc::now() + Duration::hours(1),
+        };
+
+        let session = create_session(&db, arg.clone()).await.unwrap();
+        assert_eq!(session.id, arg.id);
+        assert_eq!(session.username, arg.username);
+        assert_eq!(session.refresh_token, arg.refresh_token);
+        assert!(!session.is_blocked);
+
+        let got = get_session(&db, session.id).await.unwrap();
+        assert_eq!(got, session);
+    }
+
+    #[tokio::test]
+    async fn test_list_active_sessions() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let user = random_user(&db).await.unwrap();
+        let active = random_session(&db, &user.username, Duration::hours(1))
+            .await
+            .unwrap();
+        let blocked = random_session(&db, &user.username, Duration::hours(1))
+            .await
+            .unwrap();
+        random_session(&db, &user.username, -Duration::hours(1))
+            .await
+            .unwrap();
+
+        let blocked = block_session(&db, blocked.id).await.unwrap();
+        assert!(blocked.is_blocked);
+
+        let sessions = list_active_sessions(&db, &user.username).await.unwrap();
+        assert_eq!(sessions, vec![active]);
+    }
+}
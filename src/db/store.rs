@@ -1,34 +1,399 @@
 use crate::{
+    currency::Currency,
     db::{
-        entry_sql::{create_entry, CreateEntryParams},
-        transfer_sql::{create_transfer, CreateTransferParams},
+        account_sql::{self, CreateAccountParams, ListAccountsParams},
+        currency_sql,
+        entry_sql::{self, create_entry, CreateEntryParams, ListEntriesParams},
+        exchange_rate_sql::{
+            self, find_exchange_rate_tx, get_exchange_rate_tx, CreateExchangeRateParams,
+        },
+        fx_quote_sql::{self, get_fx_quote_for_update, use_fx_quote, CreateFxQuoteParams},
+        idempotency_sql::{self, CompleteIdempotencyKeyParams, CreateIdempotencyKeyParams},
+        migrate,
+        report_sql::{self, BalanceReport, TransferVolumeReport, TransferVolumeReportParams},
+        session_sql::{self, CreateSessionParams},
+        transfer_sql::{self, create_transfer, CreateTransferParams, ListTransfersParams},
+        tx_exec,
+        user_sql::{self, CreateUserParams},
+        TxOptions,
     },
-    models::{Account, Entry, Transfer},
+    fx,
+    metrics::{PoolStats, METRICS},
+    models::{
+        Account, AccountStatus, CurrencyInfo, Entry, ExchangeRate, FxQuote, IdempotencyKey,
+        Session, Transfer, User,
+    },
+    money::{Money, MoneyError},
     prelude::*,
 };
-use sqlx::{Executor, PgPool};
+use axum::async_trait;
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::PgPool;
+use std::{collections::HashMap, time::Instant};
+use tracing::instrument;
+use uuid::Uuid;
 
-use super::account_sql::{add_account_balance, AddAccountBalanceParams};
+use super::account_sql::{
+    add_account_balance, create_account_status_change, get_account_for_update,
+    get_fx_position_account_id, update_account_status_tx, AddAccountBalanceParams,
+    CreateAccountStatusChangeParams,
+};
 
-macro_rules! execute_transaction {
-    ($tx:expr, $action:expr) => {
-        match $action.await {
-            Ok(result) => result,
-            Err(err) => {
-                $tx.rollback().await?;
-                return Err(err.into());
-            }
-        }
-    };
+/// Everything the API needs from persistence. `PgStore` is the production
+/// implementation; `MemStore` is an in-memory stand-in for handler tests.
+#[async_trait]
+pub trait Store: Send + Sync + 'static {
+    async fn create_account(&self, arg: CreateAccountParams) -> Result<Account>;
+    async fn get_account(&self, id: i64) -> Result<Account>;
+    async fn list_accounts(&self, arg: ListAccountsParams) -> Result<Vec<Account>>;
+    async fn update_account_status(&self, arg: UpdateAccountStatusParams) -> Result<Account>;
+    async fn update_overdraft_limit(&self, id: i64, overdraft_limit: Money) -> Result<Account>;
+
+    async fn list_currencies(&self) -> Result<Vec<CurrencyInfo>>;
+
+    async fn create_exchange_rate(&self, arg: CreateExchangeRateParams) -> Result<ExchangeRate>;
+    /// The rate for the pair in force at `at`, if any.
+    async fn find_exchange_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
+        at: DateTime<Utc>,
+    ) -> Result<Option<ExchangeRate>>;
+    async fn create_fx_quote(&self, arg: CreateFxQuoteParams) -> Result<FxQuote>;
+    async fn get_fx_quote(&self, id: Uuid) -> Result<FxQuote>;
+
+    async fn get_entry(&self, id: i64) -> Result<Entry>;
+    async fn list_entries(&self, arg: ListEntriesParams) -> Result<Vec<Entry>>;
+
+    async fn get_transfer(&self, id: i64) -> Result<Transfer>;
+    async fn list_transfers(&self, arg: ListTransfersParams) -> Result<Vec<Transfer>>;
+    async fn transfer_tx(&self, arg: TransferTxParams) -> Result<TransferTxResult>;
+
+    async fn create_user(&self, arg: CreateUserParams) -> Result<User>;
+    async fn get_user(&self, username: &str) -> Result<User>;
+
+    async fn create_session(&self, arg: CreateSessionParams) -> Result<Session>;
+    async fn get_session(&self, id: Uuid) -> Result<Session>;
+    async fn list_active_sessions(&self, username: &str) -> Result<Vec<Session>>;
+    async fn block_session(&self, id: Uuid) -> Result<Session>;
+
+    async fn balance_report(&self) -> Result<Vec<BalanceReport>>;
+    async fn transfer_volume_report(
+        &self,
+        arg: TransferVolumeReportParams,
+    ) -> Result<Vec<TransferVolumeReport>>;
+
+    async fn create_idempotency_key(
+        &self,
+        arg: CreateIdempotencyKeyParams,
+    ) -> Result<Option<IdempotencyKey>>;
+    async fn get_idempotency_key(&self, scope: &str, key: &str) -> Result<IdempotencyKey>;
+    async fn complete_idempotency_key(
+        &self,
+        arg: CompleteIdempotencyKeyParams,
+    ) -> Result<IdempotencyKey>;
+    async fn delete_idempotency_key(&self, scope: &str, key: &str) -> Result<()>;
+    async fn delete_expired_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        expired_before: DateTime<Utc>,
+    ) -> Result<bool>;
+
+    /// Checks that the database accepts queries.
+    async fn ping(&self) -> Result<()>;
+    /// Newest applied migration, or `None` for an unmigrated database.
+    async fn schema_version(&self) -> Result<Option<i64>>;
+    /// Connection pool usage, if the store has a pool.
+    fn pool_stats(&self) -> Option<PoolStats>;
+}
+
+/// `Store` backed by Postgres. Transactions run with `tx_options`.
+#[derive(Clone)]
+pub struct PgStore {
+    pool: PgPool,
+    tx_options: TxOptions,
+}
+
+impl PgStore {
+    pub fn new(pool: PgPool, tx_options: TxOptions) -> Self {
+        Self { pool, tx_options }
+    }
+
+    pub fn pool(&self) -> &PgPool {
+        &self.pool
+    }
+}
+
+#[async_trait]
+impl Store for PgStore {
+    async fn create_account(&self, arg: CreateAccountParams) -> Result<Account> {
+        account_sql::create_account(&self.pool, self.tx_options, arg).await
+    }
+
+    async fn get_account(&self, id: i64) -> Result<Account> {
+        account_sql::get_account(&self.pool, id).await
+    }
+
+    async fn list_accounts(&self, arg: ListAccountsParams) -> Result<Vec<Account>> {
+        account_sql::list_accounts(&self.pool, arg).await
+    }
+
+    async fn update_account_status(&self, arg: UpdateAccountStatusParams) -> Result<Account> {
+        update_account_status(&self.pool, self.tx_options, arg).await
+    }
+
+    async fn update_overdraft_limit(&self, id: i64, overdraft_limit: Money) -> Result<Account> {
+        account_sql::update_overdraft_limit(&self.pool, self.tx_options, id, overdraft_limit).await
+    }
+
+    async fn list_currencies(&self) -> Result<Vec<CurrencyInfo>> {
+        currency_sql::list_currencies(&self.pool).await
+    }
+
+    async fn create_exchange_rate(&self, arg: CreateExchangeRateParams) -> Result<ExchangeRate> {
+        exchange_rate_sql::create_exchange_rate(&self.pool, arg).await
+    }
+
+    async fn find_exchange_rate(
+        &self,
+        base_currency: Currency,
+        quote_currency: Currency,
+        at: DateTime<Utc>,
+    ) -> Result<Option<ExchangeRate>> {
+        exchange_rate_sql::find_exchange_rate(&self.pool, base_currency, quote_currency, at).await
+    }
+
+    async fn create_fx_quote(&self, arg: CreateFxQuoteParams) -> Result<FxQuote> {
+        fx_quote_sql::create_fx_quote(&self.pool, arg).await
+    }
+
+    async fn get_fx_quote(&self, id: Uuid) -> Result<FxQuote> {
+        fx_quote_sql::get_fx_quote(&self.pool, id).await
+    }
+
+    async fn get_entry(&self, id: i64) -> Result<Entry> {
+        entry_sql::get_entry(&self.pool, id).await
+    }
+
+    async fn list_entries(&self, arg: ListEntriesParams) -> Result<Vec<Entry>> {
+        entry_sql::list_entries(&self.pool, arg).await
+    }
+
+    async fn get_transfer(&self, id: i64) -> Result<Transfer> {
+        transfer_sql::get_transfer(&self.pool, id).await
+    }
+
+    async fn list_transfers(&self, arg: ListTransfersParams) -> Result<Vec<Transfer>> {
+        transfer_sql::list_transfers(&self.pool, arg).await
+    }
+
+    async fn transfer_tx(&self, arg: TransferTxParams) -> Result<TransferTxResult> {
+        transfer_tx(&self.pool, self.tx_options, arg).await
+    }
+
+    async fn create_user(&self, arg: CreateUserParams) -> Result<User> {
+        user_sql::create_user(&self.pool, arg).await
+    }
+
+    async fn get_user(&self, username: &str) -> Result<User> {
+        user_sql::get_user(&self.pool, username).await
+    }
+
+    async fn create_session(&self, arg: CreateSessionParams) -> Result<Session> {
+        session_sql::create_session(&self.pool, arg).await
+    }
+
+    async fn get_session(&self, id: Uuid) -> Result<Session> {
+        session_sql::get_session(&self.pool, id).await
+    }
+
+    async fn list_active_sessions(&self, username: &str) -> Result<Vec<Session>> {
+        session_sql::list_active_sessions(&self.pool, username).await
+    }
+
+    async fn block_session(&self, id: Uuid) -> Result<Session> {
+        session_sql::block_session(&self.pool, id).await
+    }
+
+    async fn balance_report(&self) -> Result<Vec<BalanceReport>> {
+        report_sql::balance_report(&self.pool).await
+    }
+
+    async fn transfer_volume_report(
+        &self,
+        arg: TransferVolumeReportParams,
+    ) -> Result<Vec<TransferVolumeReport>> {
+        report_sql::transfer_volume_report(&self.pool, arg).await
+    }
+
+    async fn create_idempotency_key(
+        &self,
+        arg: CreateIdempotencyKeyParams,
+    ) -> Result<Option<IdempotencyKey>> {
+        idempotency_sql::create_idempotency_key(&self.pool, arg).await
+    }
+
+    async fn get_idempotency_key(&self, scope: &str, key: &str) -> Result<IdempotencyKey> {
+        idempotency_sql::get_idempotency_key(&self.pool, scope, key).await
+    }
+
+    async fn complete_idempotency_key(
+        &self,
+        arg: CompleteIdempotencyKeyParams,
+    ) -> Result<IdempotencyKey> {
+        idempotency_sql::complete_idempotency_key(&self.pool, arg).await
+    }
+
+    async fn delete_idempotency_key(&self, scope: &str, key: &str) -> Result<()> {
+        idempotency_sql::delete_idempotency_key(&self.pool, scope, key).await
+    }
+
+    async fn delete_expired_idempotency_key(
+        &self,
+        scope: &str,
+        key: &str,
+        expired_before: DateTime<Utc>,
+    ) -> Result<bool> {
+        idempotency_sql::delete_expired_idempotency_key(&self.pool, scope, key, expired_before)
+            .await
+    }
+
+    async fn ping(&self) -> Result<()> {
+        let mut conn = self.pool.acquire().await?;
+        sqlx::query("SELECT 1").execute(&mut *conn).await?;
+        Ok(())
+    }
+
+    async fn schema_version(&self) -> Result<Option<i64>> {
+        migrate::current_version(&self.pool).await
+    }
+
+    fn pool_stats(&self) -> Option<PoolStats> {
+        Some(PoolStats {
+            max: self.pool.options().get_max_connections(),
+            open: self.pool.size(),
+            idle: self.pool.num_idle(),
+        })
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct UpdateAccountStatusParams {
+    pub id: i64,
+    pub status: AccountStatus,
+    /// Why the status changed, for the audit trail.
+    pub reason: String,
+    /// The user making the change.
+    pub changed_by: String,
+}
+
+/// Moves an account to `status` and records the change with its reason.
+#[instrument(skip(pool))]
+pub async fn update_account_status(
+    pool: &PgPool,
+    opts: TxOptions,
+    arg: UpdateAccountStatusParams,
+) -> Result<Account> {
+    tx_exec(pool, opts, |tx| {
+        let arg = arg.clone();
+        Box::pin(async move {
+            let account = get_account_for_update(tx, arg.id).await?;
+            check_status_change(&account, arg.status)?;
+            let updated = update_account_status_tx(tx, arg.id, arg.status).await?;
+            create_account_status_change(
+                tx,
+                CreateAccountStatusChangeParams {
+                    account_id: arg.id,
+                    from_status: account.status,
+                    to_status: arg.status,
+                    reason: arg.reason,
+                    changed_by: arg.changed_by,
+                },
+            )
+            .await?;
+            Ok(updated)
+        })
+    })
+    .await
+}
+
+/// Checks that `account` may move to `status`. Only an empty account can
+/// close, and FX position accounts always stay active.
+pub(crate) fn check_status_change(
+    account: &Account,
+    status: AccountStatus,
+) -> std::result::Result<(), LedgerError> {
+    if account.owner == fx::FX_POSITION_OWNER {
+        return Err(LedgerError::SystemAccount {
+            account_id: account.id,
+        });
+    }
+    if !account.status.can_become(status) {
+        return Err(LedgerError::InvalidStatusTransition {
+            account_id: account.id,
+            from: account.status,
+            to: status,
+        });
+    }
+    if status == AccountStatus::Closed && account.balance.minor_units != 0 {
+        return Err(LedgerError::AccountNotEmpty {
+            account_id: account.id,
+            balance: account.balance,
+        });
+    }
+    Ok(())
+}
+
+/// Checks that the statuses of both accounts allow the transfer.
+pub(crate) fn check_transfer_accounts(
+    sender: &Account,
+    receiver: &Account,
+) -> std::result::Result<(), LedgerError> {
+    if !sender.status.can_send() {
+        return Err(LedgerError::AccountUnavailable {
+            account_id: sender.id,
+            status: sender.status,
+        });
+    }
+    if !receiver.status.can_receive() {
+        return Err(LedgerError::AccountUnavailable {
+            account_id: receiver.id,
+            status: receiver.status,
+        });
+    }
+    Ok(())
 }
 
+/// Checks that an FX position account can take its side of a conversion.
+/// Their status cannot be changed through the API, so this only trips if it
+/// was changed in the database directly.
+pub(crate) fn check_position_account(position: &Account) -> std::result::Result<(), LedgerError> {
+    if position.status != AccountStatus::Active {
+        return Err(LedgerError::AccountUnavailable {
+            account_id: position.id,
+            status: position.status,
+        });
+    }
+    Ok(())
+}
+
+#[derive(Debug, Clone)]
 pub struct TransferTxParams {
     pub from_account_id: i64,
     pub to_account_id: i64,
-    pub amount: i64,
+    /// In the currency of the sending account.
+    pub amount: Money,
+    /// The currency to credit the receiver in. If it differs from the
+    /// amount's, the amount is converted at the exchange rate in effect;
+    /// `None` requires both accounts to share a currency.
+    pub to_currency: Option<Currency>,
+    /// Converts at the rate of this quote instead, and uses it up.
+    pub quote_id: Option<Uuid>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize)]
 pub struct TransferTxResult {
     pub transfer: Transfer,
     pub from_account: Account,
@@ -37,115 +402,205 @@ pub struct TransferTxResult {
     pub to_entry: Entry,
 }
 
-pub async fn transfer_tx(pool: &PgPool, arg: TransferTxParams) -> Result<TransferTxResult> {
-    let mut tx = pool.begin().await?;
-    // tx.execute("SET TRANSACTION ISOLATION LEVEL SERIALIZABLE;")
-    //     .await?;
+/// Moves `amount` between two accounts: records the transfer and its balanced
+/// entries and updates the balances, atomically. A conversion between
+/// currencies also books both amounts against the FX position accounts, and
+/// uses up the quote it was made at, if any.
+/// Serialization failures and deadlocks are retried as configured by `opts`.
+#[instrument(skip(pool))]
+pub async fn transfer_tx(
+    pool: &PgPool,
+    opts: TxOptions,
+    arg: TransferTxParams,
+) -> Result<TransferTxResult> {
+    if arg.from_account_id == arg.to_account_id {
+        return Err(LedgerError::SameAccount {
+            account_id: arg.from_account_id,
+        }
+        .into());
+    }
+    let started = Instant::now();
+    let res = tx_exec(pool, opts, |tx| {
+        let arg = arg.clone();
+        Box::pin(async move { transfer(tx, arg).await })
+    })
+    .await;
 
-    let transfer = execute_transaction!(
-        tx,
-        create_transfer(
-            &mut tx,
-            CreateTransferParams {
-                from_account_id: arg.from_account_id,
-                to_account_id: arg.to_account_id,
-                amount: arg.amount,
-            },
-        )
-    );
+    let outcome = if res.is_ok() { "ok" } else { "error" };
+    METRICS
+        .transfer_tx_duration_seconds
+        .with_label_values(&[outcome])
+        .observe(started.elapsed().as_secs_f64());
+    if let Ok(result) = &res {
+        METRICS
+            .transfer_volume_total
+            .with_label_values(&[result.from_account.currency.as_str()])
+            .inc_by(result.transfer.amount.minor_units.unsigned_abs());
+    }
+    res
+}
 
-    let from_entry = execute_transaction!(
-        tx,
-        create_entry(
-            &mut tx,
-            CreateEntryParams {
-                account_id: arg.from_account_id,
-                amount: -arg.amount,
-            },
-        )
-    );
+#[instrument(skip(tx))]
+async fn transfer(
+    tx: &mut sqlx::Transaction<'_, sqlx::Postgres>,
+    arg: TransferTxParams,
+) -> Result<TransferTxResult> {
+    // Lock both accounts in id order so concurrent opposite transfers cannot deadlock,
+    // and so the balance read below cannot change before it is debited.
+    let (first_id, second_id) = if arg.from_account_id < arg.to_account_id {
+        (arg.from_account_id, arg.to_account_id)
+    } else {
+        (arg.to_account_id, arg.from_account_id)
+    };
+    let first = get_account_for_update(tx, first_id).await?;
+    let second = get_account_for_update(tx, second_id).await?;
+    let (sender, receiver) = if first.id == arg.from_account_id {
+        (first, second)
+    } else {
+        (second, first)
+    };
+    check_transfer_accounts(&sender, &receiver)?;
+    sender.balance.same_currency(arg.amount)?;
+    let to_currency = arg.to_currency.unwrap_or(arg.amount.currency);
+    if receiver.currency != to_currency {
+        return Err(MoneyError::CurrencyMismatch {
+            expected: receiver.currency,
+            found: to_currency,
+        }
+        .into());
+    }
 
-    let to_entry = execute_transaction!(
+    let available = sender.balance.checked_add(sender.overdraft_limit)?;
+    if available.checked_sub(arg.amount)?.is_negative() {
+        return Err(LedgerError::InsufficientFunds {
+            account_id: sender.id,
+            available,
+        }
+        .into());
+    }
+
+    let conversion = match arg.quote_id {
+        // The quote stays locked until commit, so no other transfer can use it.
+        Some(quote_id) => {
+            let quote = get_fx_quote_for_update(tx, quote_id).await?;
+            fx::check_quote(&quote, arg.amount.currency, to_currency, Utc::now())?;
+            let rate = get_exchange_rate_tx(tx, quote.exchange_rate_id).await?;
+            Some(fx::convert(arg.amount, &rate)?)
+        }
+        None if to_currency == arg.amount.currency => None,
+        None => {
+            let rate = find_exchange_rate_tx(tx, arg.amount.currency, to_currency, Utc::now())
+                .await?
+                .ok_or(LedgerError::ExchangeRateUnavailable {
+                    from: arg.amount.currency,
+                    to: to_currency,
+                })?;
+            Some(fx::convert(arg.amount, &rate)?)
+        }
+    };
+    let credit = conversion.map_or(arg.amount, |conversion| conversion.amount);
+
+    let transfer = create_transfer(
         tx,
-        create_entry(
-            &mut tx,
-            CreateEntryParams {
-                account_id: arg.from_account_id,
-                amount: arg.amount,
-            },
-        )
-    );
+        CreateTransferParams {
+            from_account_id: arg.from_account_id,
+            to_account_id: arg.to_account_id,
+            amount: arg.amount,
+            conversion,
+        },
+    )
+    .await?;
+    if let Some(quote_id) = arg.quote_id {
+        use_fx_quote(tx, quote_id, transfer.id).await?;
+    }
 
-    let from_account;
-    let to_account;
+    let mut postings = vec![
+        AddAccountBalanceParams {
+            id: arg.from_account_id,
+            amount: arg.amount.checked_neg()?,
+        },
+        AddAccountBalanceParams {
+            id: arg.to_account_id,
+            amount: credit,
+        },
+    ];
+    if conversion.is_some() {
+        // The bank buys the sender's currency and sells the receiver's.
+        postings.push(AddAccountBalanceParams {
+            id: get_fx_position_account_id(tx, arg.amount.currency).await?,
+            amount: arg.amount,
+        });
+        postings.push(AddAccountBalanceParams {
+            id: get_fx_position_account_id(tx, to_currency).await?,
+            amount: credit.checked_neg()?,
+        });
+    }
 
-    if arg.from_account_id < arg.to_account_id {
-        from_account = execute_transaction!(
-            tx,
-            add_account_balance(
-                &mut tx,
-                AddAccountBalanceParams {
-                    id: arg.from_account_id,
-                    amount: -arg.amount,
-                }
-            )
-        );
-        to_account = execute_transaction!(
-            tx,
-            add_account_balance(
-                &mut tx,
-                AddAccountBalanceParams {
-                    id: arg.to_account_id,
-                    amount: arg.amount,
-                }
-            )
-        );
-    } else {
-        to_account = execute_transaction!(
+    let mut entries = Vec::with_capacity(postings.len());
+    for posting in &postings {
+        let entry = create_entry(
             tx,
-            add_account_balance(
-                &mut tx,
-                AddAccountBalanceParams {
-                    id: arg.to_account_id,
-                    amount: arg.amount,
-                }
-            )
-        );
-        from_account = execute_transaction!(
-            tx,
-            add_account_balance(
-                &mut tx,
-                AddAccountBalanceParams {
-                    id: arg.from_account_id,
-                    amount: -arg.amount,
-                }
-            )
-        );
+            CreateEntryParams {
+                account_id: posting.id,
+                amount: posting.amount,
+                transfer_id: Some(transfer.id),
+            },
+        )
+        .await?;
+        entries.push(entry);
     }
+    // The position entries are not part of the result.
+    entries.truncate(2);
+    let to_entry = entries.pop().expect("credit was booked");
+    let from_entry = entries.pop().expect("debit was booked");
 
-    tx.commit().await?;
+    // Update balances in id order so concurrent transfers cannot deadlock.
+    postings.sort_by_key(|posting| posting.id);
+    let mut accounts = HashMap::with_capacity(postings.len());
+    for posting in postings {
+        let account = add_account_balance(tx, posting).await?;
+        if account.owner == fx::FX_POSITION_OWNER {
+            check_position_account(&account)?;
+        }
+        accounts.insert(account.id, account);
+    }
+    let from_account = accounts
+        .remove(&arg.from_account_id)
+        .expect("sender was updated");
+    let to_account = accounts
+        .remove(&arg.to_account_id)
+        .expect("receiver was updated");
 
-    let result = TransferTxResult {
+    Ok(TransferTxResult {
         transfer,
         from_account,
         to_account,
         from_entry,
         to_entry,
-    };
-
-    Ok(result)
+    })
 }
 
 mod tests {
     use super::*;
-    use crate::{db::account_sql::get_account, db::create_connection_pool, utils::*};
+    use crate::{
+        db::account_sql::{get_account, update_account},
+        db::create_connection_pool,
+        utils::*,
+    };
 
     #[tokio::test]
     async fn test_transfer_tx() {
         dotenv::dotenv().ok();
         let pool = create_connection_pool(Some(10)).await.unwrap();
         let from_account = random_account(&pool).await.unwrap();
-        let to_account = random_account(&pool).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, from_account.currency);
+        let from_account =
+            update_account(&pool, TxOptions::default(), from_account.id, money(1000))
+                .await
+                .unwrap();
+        let to_account = random_account_in(&pool, from_account.currency)
+            .await
+            .unwrap();
 
         println!(
             ">> -- from_account balance before tx: {}",
@@ -162,15 +617,18 @@ mod tests {
             let pool = pool.clone();
             let from_account = from_account.clone();
             let to_account = to_account.clone();
-            let amount = random_int(10, from_account.balance);
+            let amount = money(random_int(10, from_account.balance.minor_units / n));
 
             let handle = tokio::spawn(async move {
                 let result = transfer_tx(
                     &pool,
+                    TxOptions::default(),
                     TransferTxParams {
                         from_account_id: from_account.id,
                         to_account_id: to_account.id,
                         amount,
+                        to_currency: None,
+                        quote_id: None,
                     },
                 )
                 .await
@@ -187,15 +645,28 @@ mod tests {
             let transfer = transfer.unwrap();
             assert_eq!(transfer.from_account.id, from_account.id);
             assert_eq!(transfer.to_account.id, to_account.id);
-            assert_eq!(transfer.from_entry.amount, -transfer.to_entry.amount);
-            assert_eq!(transfer.from_entry.amount, -transfer.transfer.amount);
+            assert_eq!(transfer.from_entry.account_id, from_account.id);
+            assert_eq!(transfer.to_entry.account_id, to_account.id);
+            assert_eq!(transfer.from_entry.transfer_id, Some(transfer.transfer.id));
+            assert_eq!(transfer.to_entry.transfer_id, Some(transfer.transfer.id));
+            assert_eq!(
+                transfer.from_entry.amount,
+                transfer.to_entry.amount.checked_neg().unwrap()
+            );
+            assert_eq!(
+                transfer.from_entry.amount,
+                transfer.transfer.amount.checked_neg().unwrap()
+            );
             assert_eq!(transfer.to_entry.amount, transfer.transfer.amount);
             assert_ne!(transfer.transfer.id, 0);
 
             // check account balances
             assert_eq!(
-                transfer.from_account.balance + transfer.to_account.balance,
-                from_account.balance + to_account.balance
+                transfer
+                    .from_account
+                    .balance
+                    .checked_add(transfer.to_account.balance),
+                from_account.balance.checked_add(to_account.balance)
             );
         }
 
@@ -215,9 +686,19 @@ mod tests {
         dotenv::dotenv().ok();
         let pool = create_connection_pool(Some(10)).await.unwrap();
         let from_account = random_account(&pool).await.unwrap();
-        let to_account = random_account(&pool).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, from_account.currency);
+        let from_account =
+            update_account(&pool, TxOptions::default(), from_account.id, money(1000))
+                .await
+                .unwrap();
+        let to_account = random_account_in(&pool, from_account.currency)
+            .await
+            .unwrap();
+        let to_account = update_account(&pool, TxOptions::default(), to_account.id, money(1000))
+            .await
+            .unwrap();
 
-        let amount = 10 as i64;
+        let amount = money(10);
 
         // run n concurrent transfer transactions
         let n = 10;
@@ -236,10 +717,13 @@ mod tests {
             let handle = tokio::spawn(async move {
                 let result = transfer_tx(
                     &pool,
+                    TxOptions::default(),
                     TransferTxParams {
                         from_account_id: from_account.id,
                         to_account_id: to_account.id,
                         amount,
+                        to_currency: None,
+                        quote_id: None,
                     },
                 )
                 .await
@@ -257,4 +741,604 @@ mod tests {
             assert!(transfer.is_ok());
         }
     }
+
+    #[tokio::test]
+    async fn test_transfer_tx_insufficient_funds() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let from_account = random_account(&pool).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, from_account.currency);
+        let from_account = update_account(&pool, TxOptions::default(), from_account.id, money(100))
+            .await
+            .unwrap();
+        let to_account = random_account_in(&pool, from_account.currency)
+            .await
+            .unwrap();
+        let failed = METRICS
+            .transfer_tx_duration_seconds
+            .with_label_values(&["error"])
+            .get_sample_count();
+
+        let err = transfer_tx(
+            &pool,
+            TxOptions::default(),
+            TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount: money(101),
+                to_currency: None,
+                quote_id: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::InsufficientFunds {
+                account_id: from_account.id,
+                available: money(100),
+            })
+        );
+        assert!(
+            METRICS
+                .transfer_tx_duration_seconds
+                .with_label_values(&["error"])
+                .get_sample_count()
+                > failed
+        );
+        assert_eq!(
+            get_account(&pool, from_account.id).await.unwrap().balance,
+            money(100)
+        );
+        assert_eq!(
+            get_account(&pool, to_account.id).await.unwrap().balance,
+            to_account.balance
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_same_account() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let account = random_account(&pool).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, account.currency);
+        let account = update_account(&pool, TxOptions::default(), account.id, money(100))
+            .await
+            .unwrap();
+
+        let err = transfer_tx(
+            &pool,
+            TxOptions::default(),
+            TransferTxParams {
+                from_account_id: account.id,
+                to_account_id: account.id,
+                amount: money(10),
+                to_currency: None,
+                quote_id: None,
+            },
+        )
+        .await
+        .unwrap_err();
+
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::SameAccount {
+                account_id: account.id
+            })
+        );
+        assert_eq!(
+            get_account(&pool, account.id).await.unwrap().balance,
+            money(100)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_cross_currency() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let gbp: Currency = "GBP".parse().unwrap();
+        let jpy: Currency = "JPY".parse().unwrap();
+        let from_account = random_account_in(&pool, gbp).await.unwrap();
+        let from_account = update_account(
+            &pool,
+            TxOptions::default(),
+            from_account.id,
+            Money::new(10_000, gbp),
+        )
+        .await
+        .unwrap();
+        let to_account = random_account_in(&pool, jpy).await.unwrap();
+        let rate = exchange_rate_sql::create_exchange_rate(
+            &pool,
+            CreateExchangeRateParams {
+                base_currency: gbp,
+                quote_currency: jpy,
+                rate: "189.6125".parse().unwrap(),
+                spread_bps: 50,
+                effective_from: Utc::now(),
+            },
+        )
+        .await
+        .unwrap();
+
+        let amount = Money::new(1_005, gbp);
+        let conversion = fx::convert(amount, &rate).unwrap();
+        let result = transfer_tx(
+            &pool,
+            TxOptions::default(),
+            TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount,
+                to_currency: Some(jpy),
+                quote_id: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        // 10.05 GBP is 1905.605625 JPY, less 9 JPY of spread.
+        assert_eq!(conversion.amount, Money::new(1_896, jpy));
+        assert_eq!(result.transfer.amount, amount);
+        assert_eq!(result.transfer.to_amount, conversion.amount);
+        assert_eq!(result.transfer.exchange_rate_id, Some(rate.id));
+        assert_eq!(result.transfer.fx_rate, Some(rate.rate));
+        assert_eq!(result.transfer.fx_spread, Money::new(9, jpy));
+        assert_eq!(result.transfer.fx_residue, "0.605625".parse().unwrap());
+        assert_eq!(result.from_entry.amount, Money::new(-1_005, gbp));
+        assert_eq!(result.to_entry.amount, conversion.amount);
+        assert_eq!(result.from_account.balance, Money::new(8_995, gbp));
+        assert_eq!(
+            result.to_account.balance,
+            to_account.balance.checked_add(conversion.amount).unwrap()
+        );
+
+        // Each currency balances against its position account.
+        let entries = sqlx::query!(
+            r#"SELECT a.owner, a.currency, e.amount
+            FROM entries e JOIN accounts a ON a.id = e.account_id
+            WHERE e.transfer_id = $1
+            ORDER BY e.id;"#,
+            result.transfer.id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let positions: Vec<_> = entries
+            .iter()
+            .filter(|entry| entry.owner == fx::FX_POSITION_OWNER)
+            .map(|entry| (entry.currency.as_str(), entry.amount))
+            .collect();
+        assert_eq!(positions, vec![("GBP", 1_005), ("JPY", -1_896)]);
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_cross_currency_errors() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let kwd: Currency = "KWD".parse().unwrap();
+        let cny: Currency = "CNY".parse().unwrap();
+        let from_account = random_account_in(&pool, kwd).await.unwrap();
+        let from_account = update_account(
+            &pool,
+            TxOptions::default(),
+            from_account.id,
+            Money::new(10_000, kwd),
+        )
+        .await
+        .unwrap();
+        let to_account = random_account_in(&pool, cny).await.unwrap();
+        let params = TransferTxParams {
+            from_account_id: from_account.id,
+            to_account_id: to_account.id,
+            amount: Money::new(1_000, kwd),
+            to_currency: None,
+            quote_id: None,
+        };
+
+        // Converting has to be asked for.
+        let err = transfer_tx(&pool, TxOptions::default(), params.clone())
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<MoneyError>(),
+            Some(&MoneyError::CurrencyMismatch {
+                expected: cny,
+                found: kwd,
+            })
+        );
+
+        // No test publishes a KWD to CNY rate.
+        let params = TransferTxParams {
+            to_currency: Some(cny),
+            ..params
+        };
+        let err = transfer_tx(&pool, TxOptions::default(), params)
+            .await
+            .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::ExchangeRateUnavailable { from: kwd, to: cny })
+        );
+        assert_eq!(
+            get_account(&pool, from_account.id).await.unwrap().balance,
+            from_account.balance
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_with_quote() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let cad: Currency = "CAD".parse().unwrap();
+        let aud: Currency = "AUD".parse().unwrap();
+        let create_rate = |rate: &str| {
+            exchange_rate_sql::create_exchange_rate(
+                &pool,
+                CreateExchangeRateParams {
+                    base_currency: cad,
+                    quote_currency: aud,
+                    rate: rate.parse().unwrap(),
+                    spread_bps: 0,
+                    effective_from: Utc::now(),
+                },
+            )
+        };
+        let owner = random_user(&pool).await.unwrap();
+        let create_quote = |exchange_rate_id, expires_at| {
+            fx_quote_sql::create_fx_quote(
+                &pool,
+                CreateFxQuoteParams {
+                    id: Uuid::new_v4(),
+                    owner: owner.username.clone(),
+                    exchange_rate_id,
+                    expires_at,
+                },
+            )
+        };
+        let mut senders = vec![];
+        for _ in 0..2 {
+            let account = random_account_in(&pool, cad).await.unwrap();
+            let account = update_account(
+                &pool,
+                TxOptions::default(),
+                account.id,
+                Money::new(10_000, cad),
+            )
+            .await
+            .unwrap();
+            senders.push(account);
+        }
+        let to_account = random_account_in(&pool, aud).await.unwrap();
+
+        let quoted = create_rate("1.0125").await.unwrap();
+        let quote = create_quote(quoted.id, Utc::now() + chrono::Duration::minutes(1))
+            .await
+            .unwrap();
+        assert_eq!(quote.rate, quoted.rate);
+        assert_eq!(quote.transfer_id, None);
+        // A newer rate does not change the quoted one.
+        create_rate("0.9875").await.unwrap();
+
+        // Two transfers race for the quote; exactly one gets it.
+        let handles = senders.iter().map(|sender| {
+            let pool = pool.clone();
+            let params = TransferTxParams {
+                from_account_id: sender.id,
+                to_account_id: to_account.id,
+                amount: Money::new(1_000, cad),
+                to_currency: Some(aud),
+                quote_id: Some(quote.id),
+            };
+            tokio::spawn(async move { transfer_tx(&pool, TxOptions::default(), params).await })
+        });
+        let results: Vec<_> = futures::future::join_all(handles)
+            .await
+            .into_iter()
+            .map(|result| result.unwrap())
+            .collect();
+        let (used, rejected): (Vec<_>, Vec<_>) = results.into_iter().partition(|res| res.is_ok());
+        assert_eq!(used.len(), 1);
+        let result = used.into_iter().next().unwrap().unwrap();
+        assert_eq!(result.transfer.exchange_rate_id, Some(quoted.id));
+        assert_eq!(result.transfer.to_amount, Money::new(1_012, aud));
+        let err = rejected.into_iter().next().unwrap().unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::FxQuoteUsed {
+                quote_id: quote.id,
+                transfer_id: result.transfer.id,
+            })
+        );
+        let quote = fx_quote_sql::get_fx_quote(&pool, quote.id).await.unwrap();
+        assert_eq!(quote.transfer_id, Some(result.transfer.id));
+        assert!(quote.used_at.is_some());
+
+        let expired = create_quote(quoted.id, Utc::now() - chrono::Duration::seconds(1))
+            .await
+            .unwrap();
+        let sender = senders
+            .iter()
+            .find(|sender| sender.id != result.from_account.id)
+            .unwrap();
+        let err = transfer_tx(
+            &pool,
+            TxOptions::default(),
+            TransferTxParams {
+                from_account_id: sender.id,
+                to_account_id: to_account.id,
+                amount: Money::new(1_000, cad),
+                to_currency: Some(aud),
+                quote_id: Some(expired.id),
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::FxQuoteExpired {
+                quote_id: expired.id,
+                expired_at: expired.expires_at,
+            })
+        );
+        assert_eq!(
+            get_account(&pool, sender.id).await.unwrap().balance,
+            sender.balance
+        );
+        let expired = fx_quote_sql::get_fx_quote(&pool, expired.id).await.unwrap();
+        assert_eq!(expired.transfer_id, None);
+    }
+
+    #[tokio::test]
+    async fn test_fx_position_accounts_stay_active() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let chf: Currency = "CHF".parse().unwrap();
+        let kwd: Currency = "KWD".parse().unwrap();
+        let banker = random_user(&pool).await.unwrap();
+        let position_id: i64 = sqlx::query_scalar(
+            "SELECT id FROM accounts WHERE owner = 'fx-position' AND currency = $1",
+        )
+        .bind(kwd.as_str())
+        .fetch_one(&pool)
+        .await
+        .unwrap();
+
+        let err = update_account_status(
+            &pool,
+            TxOptions::default(),
+            UpdateAccountStatusParams {
+                id: position_id,
+                status: AccountStatus::Frozen,
+                reason: "reconciliation".to_string(),
+                changed_by: banker.username,
+            },
+        )
+        .await
+        .unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::SystemAccount {
+                account_id: position_id
+            })
+        );
+
+        // A position frozen behind the store's back blocks conversions. All
+        // of it happens in a transaction that is rolled back, so other tests
+        // never see the frozen position.
+        let from_account = random_account_in(&pool, chf).await.unwrap();
+        update_account(
+            &pool,
+            TxOptions::default(),
+            from_account.id,
+            Money::new(1_000, chf),
+        )
+        .await
+        .unwrap();
+        let to_account = random_account_in(&pool, kwd).await.unwrap();
+        let mut tx = pool.begin().await.unwrap();
+        sqlx::query(
+            "INSERT INTO exchange_rates (base_currency, quote_currency, rate, effective_from)
+            VALUES ($1, $2, 0.35, now())",
+        )
+        .bind(chf.as_str())
+        .bind(kwd.as_str())
+        .execute(&mut *tx)
+        .await
+        .unwrap();
+        sqlx::query("UPDATE accounts SET status = 'frozen' WHERE id = $1")
+            .bind(position_id)
+            .execute(&mut *tx)
+            .await
+            .unwrap();
+
+        let err = transfer(
+            &mut tx,
+            TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount: Money::new(100, chf),
+                to_currency: Some(kwd),
+                quote_id: None,
+            },
+        )
+        .await
+        .unwrap_err();
+        tx.rollback().await.unwrap();
+
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::AccountUnavailable {
+                account_id: position_id,
+                status: AccountStatus::Frozen,
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn test_update_account_status() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let account = random_account(&pool).await.unwrap();
+        let account = update_account(
+            &pool,
+            TxOptions::default(),
+            account.id,
+            Money::new(100, account.currency),
+        )
+        .await
+        .unwrap();
+        let update = |status| {
+            update_account_status(
+                &pool,
+                TxOptions::default(),
+                UpdateAccountStatusParams {
+                    id: account.id,
+                    status,
+                    reason: "customer request".to_string(),
+                    changed_by: account.owner.clone(),
+                },
+            )
+        };
+
+        let err = update(AccountStatus::Closed).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::AccountNotEmpty {
+                account_id: account.id,
+                balance: account.balance,
+            })
+        );
+        let err = update(AccountStatus::Pending).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::InvalidStatusTransition {
+                account_id: account.id,
+                from: AccountStatus::Active,
+                to: AccountStatus::Pending,
+            })
+        );
+
+        assert_eq!(
+            update(AccountStatus::Frozen).await.unwrap().status,
+            AccountStatus::Frozen
+        );
+        update_account(
+            &pool,
+            TxOptions::default(),
+            account.id,
+            Money::zero(account.currency),
+        )
+        .await
+        .unwrap();
+        let closed = update(AccountStatus::Closed).await.unwrap();
+        assert_eq!(closed.status, AccountStatus::Closed);
+        let err = update(AccountStatus::Active).await.unwrap_err();
+        assert!(matches!(
+            err.downcast_ref::<LedgerError>(),
+            Some(LedgerError::InvalidStatusTransition { .. })
+        ));
+
+        let changes = sqlx::query!(
+            r#"SELECT from_status AS "from_status: AccountStatus",
+                to_status AS "to_status: AccountStatus", reason, changed_by
+            FROM account_status_changes
+            WHERE account_id = $1
+            ORDER BY id;"#,
+            account.id
+        )
+        .fetch_all(&pool)
+        .await
+        .unwrap();
+        let changes: Vec<_> = changes
+            .iter()
+            .map(|change| (change.from_status, change.to_status))
+            .collect();
+        assert_eq!(
+            changes,
+            vec![
+                (AccountStatus::Active, AccountStatus::Frozen),
+                (AccountStatus::Frozen, AccountStatus::Closed),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_transfer_tx_account_status() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let from_account = random_account(&pool).await.unwrap();
+        let money = |minor_units| Money::new(minor_units, from_account.currency);
+        let from_account =
+            update_account(&pool, TxOptions::default(), from_account.id, money(1000))
+                .await
+                .unwrap();
+        let to_account = random_account_in(&pool, from_account.currency)
+            .await
+            .unwrap();
+        let empty = random_account_in(&pool, from_account.currency)
+            .await
+            .unwrap();
+        let empty = update_account(&pool, TxOptions::default(), empty.id, money(0))
+            .await
+            .unwrap();
+        let set_status = |id, status| {
+            update_account_status(
+                &pool,
+                TxOptions::default(),
+                UpdateAccountStatusParams {
+                    id,
+                    status,
+                    reason: "test".to_string(),
+                    changed_by: from_account.owner.clone(),
+                },
+            )
+        };
+        let transfer = |from_account_id, to_account_id| {
+            transfer_tx(
+                &pool,
+                TxOptions::default(),
+                TransferTxParams {
+                    from_account_id,
+                    to_account_id,
+                    amount: money(10),
+                    to_currency: None,
+                    quote_id: None,
+                },
+            )
+        };
+
+        // A frozen account can receive funds but not send them.
+        set_status(from_account.id, AccountStatus::Frozen)
+            .await
+            .unwrap();
+        let err = transfer(from_account.id, to_account.id).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::AccountUnavailable {
+                account_id: from_account.id,
+                status: AccountStatus::Frozen,
+            })
+        );
+        transfer(to_account.id, from_account.id).await.unwrap();
+        set_status(from_account.id, AccountStatus::Active)
+            .await
+            .unwrap();
+
+        // A closed account can do neither.
+        set_status(empty.id, AccountStatus::Closed).await.unwrap();
+        let err = transfer(from_account.id, empty.id).await.unwrap_err();
+        assert_eq!(
+            err.downcast_ref::<LedgerError>(),
+            Some(&LedgerError::AccountUnavailable {
+                account_id: empty.id,
+                status: AccountStatus::Closed,
+            })
+        );
+        assert_eq!(
+            get_account(&pool, from_account.id).await.unwrap().balance,
+            money(1010)
+        );
+    }
 }
@@ -1,32 +1,82 @@
+use crate::decimal::Decimal;
+use crate::fx::Conversion;
 use crate::models::Transfer;
+use crate::money::Money;
 use crate::prelude::*;
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use tracing::instrument;
 
 #[derive(Debug, Clone)]
 pub struct CreateTransferParams {
     pub from_account_id: i64,
     pub to_account_id: i64,
-    pub amount: i64,
+    /// Must be in the currency of the sending account.
+    pub amount: Money,
+    /// How `amount` is converted into the receiver's currency, if it is.
+    pub conversion: Option<Conversion>,
 }
 
+#[instrument(skip(transaction))]
 pub async fn create_transfer(
     transaction: &mut sqlx::Transaction<'_, sqlx::Postgres>,
     arg: CreateTransferParams,
-) -> SQLResult<Transfer> {
-    sqlx::query_as!(
+) -> Result<Transfer> {
+    let transfer = sqlx::query_as!(
         Transfer,
-        "INSERT INTO transfers (from_account_id, to_account_id, amount) VALUES ($1, $2, $3) RETURNING *;",
+        r#"WITH t AS (
+            INSERT INTO transfers (from_account_id, to_account_id, amount, to_amount,
+                exchange_rate_id, fx_rate, fx_spread, fx_residue)
+            VALUES ($1, $2, $3, $4, $5, $6::text::numeric, $7, $8::text::numeric)
+            RETURNING *
+        )
+        SELECT t.id AS "id!", t.from_account_id AS "from_account_id!",
+            t.to_account_id AS "to_account_id!", (t.amount, a.currency) AS "amount!: Money",
+            (t.to_amount, b.currency) AS "to_amount!: Money",
+            t.exchange_rate_id AS "exchange_rate_id?", t.fx_rate::text AS "fx_rate?: Decimal",
+            (t.fx_spread, b.currency) AS "fx_spread!: Money",
+            t.fx_residue::text AS "fx_residue!: Decimal", t.created_at AS "created_at!"
+        FROM t
+            JOIN accounts a ON a.id = t.from_account_id
+            JOIN accounts b ON b.id = t.to_account_id;"#,
         arg.from_account_id,
         arg.to_account_id,
-        arg.amount
+        arg.amount.minor_units,
+        arg.conversion
+            .map_or(arg.amount, |conversion| conversion.amount)
+            .minor_units,
+        arg.conversion.map(|conversion| conversion.exchange_rate_id),
+        arg.conversion.map(|conversion| conversion.rate.to_string()),
+        arg.conversion
+            .map_or(0, |conversion| conversion.spread.minor_units),
+        arg.conversion
+            .map_or(Decimal::ZERO, |conversion| conversion.residue)
+            .to_string()
     )
     .fetch_one(&mut **transaction)
-    .await
+    .await?;
+    // Rolls the insert back with the caller's transaction.
+    arg.amount.same_currency(transfer.amount)?;
+    if let Some(conversion) = arg.conversion {
+        conversion.amount.same_currency(transfer.to_amount)?;
+    }
+    Ok(transfer)
 }
 
+#[instrument(skip(pool))]
 pub async fn get_transfer(pool: &sqlx::PgPool, id: i64) -> Result<Transfer> {
     let transfer = sqlx::query_as!(
         Transfer,
-        "SELECT * FROM transfers WHERE id = $1 LIMIT 1;",
+        r#"SELECT t.id, t.from_account_id, t.to_account_id,
+            (t.amount, a.currency) AS "amount!: Money",
+            (t.to_amount, b.currency) AS "to_amount!: Money",
+            t.exchange_rate_id, t.fx_rate::text AS "fx_rate?: Decimal",
+            (t.fx_spread, b.currency) AS "fx_spread!: Money",
+            t.fx_residue::text AS "fx_residue!: Decimal", t.created_at
+        FROM transfers t
+            JOIN accounts a ON a.id = t.from_account_id
+            JOIN accounts b ON b.id = t.to_account_id
+        WHERE t.id = $1 LIMIT 1;"#,
         id
     )
     .fetch_one(pool)
@@ -34,11 +84,58 @@ pub async fn get_transfer(pool: &sqlx::PgPool, id: i64) -> Result<Transfer> {
     Ok(transfer)
 }
 
-pub async fn list_transfers(pool: &sqlx::PgPool, account_id: i64) -> Result<Vec<Transfer>> {
+#[derive(Debug, Clone, Copy, PartialEq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TransferDirection {
+    Incoming,
+    Outgoing,
+}
+
+#[derive(Debug, Clone)]
+pub struct ListTransfersParams {
+    /// `None` lists transfers between all accounts and ignores `direction`.
+    pub account_id: Option<i64>,
+    /// Restricts the result to one side of the transfer; `None` returns both.
+    pub direction: Option<TransferDirection>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+    pub limit: i64,
+    pub offset: i64,
+}
+
+#[instrument(skip(pool))]
+pub async fn list_transfers(
+    pool: &sqlx::PgPool,
+    arg: ListTransfersParams,
+) -> Result<Vec<Transfer>> {
+    let outgoing = arg.direction != Some(TransferDirection::Incoming);
+    let incoming = arg.direction != Some(TransferDirection::Outgoing);
+
     let transfers = sqlx::query_as!(
         Transfer,
-        "SELECT * FROM transfers WHERE from_account_id = $1 OR to_account_id = $1 ORDER BY id;",
-        account_id
+        r#"SELECT t.id, t.from_account_id, t.to_account_id,
+            (t.amount, a.currency) AS "amount!: Money",
+            (t.to_amount, b.currency) AS "to_amount!: Money",
+            t.exchange_rate_id, t.fx_rate::text AS "fx_rate?: Decimal",
+            (t.fx_spread, b.currency) AS "fx_spread!: Money",
+            t.fx_residue::text AS "fx_residue!: Decimal", t.created_at
+        FROM transfers t
+            JOIN accounts a ON a.id = t.from_account_id
+            JOIN accounts b ON b.id = t.to_account_id
+        WHERE ($1::bigint IS NULL
+                OR ($2 AND t.from_account_id = $1)
+                OR ($3 AND t.to_account_id = $1))
+            AND ($4::timestamptz IS NULL OR t.created_at >= $4)
+            AND ($5::timestamptz IS NULL OR t.created_at < $5)
+        ORDER BY t.id
+        LIMIT $6 OFFSET $7;"#,
+        arg.account_id,
+        outgoing,
+        incoming,
+        arg.start_time,
+        arg.end_time,
+        arg.limit,
+        arg.offset
     )
     .fetch_all(pool)
     .await?;
@@ -47,7 +144,13 @@ pub async fn list_transfers(pool: &sqlx::PgPool, account_id: i64) -> Result<Vec<
 
 mod tests {
     use super::*;
-    use crate::{db::create_connection_pool, utils::*};
+    use crate::{
+        db::{
+            create_connection_pool,
+            entry_sql::{create_entry, CreateEntryParams},
+        },
+        utils::*,
+    };
 
     #[tokio::test]
     async fn test_create_transfer() {
@@ -57,9 +160,9 @@ mod tests {
             .expect("Failed to create connection pool");
 
         let from_account = random_account(&db).await.unwrap();
-        let to_account = random_account(&db).await.unwrap();
+        let to_account = random_account_in(&db, from_account.currency).await.unwrap();
 
-        let money = random_int(10, from_account.balance);
+        let money = Money::new(random_int(10, 100), from_account.currency);
         let mut tx = db.begin().await.unwrap();
 
         let transfer = create_transfer(
@@ -68,6 +171,7 @@ mod tests {
                 from_account_id: from_account.id,
                 to_account_id: to_account.id,
                 amount: money,
+                conversion: None,
             },
         )
         .await
@@ -76,6 +180,8 @@ mod tests {
         assert_eq!(transfer.from_account_id, from_account.id);
         assert_eq!(transfer.to_account_id, to_account.id);
         assert_eq!(transfer.amount, money);
+        assert_eq!(transfer.to_amount, money);
+        assert_eq!(transfer.exchange_rate_id, None);
     }
 
     #[tokio::test]
@@ -86,9 +192,9 @@ mod tests {
             .expect("Failed to create connection pool");
 
         let from_account = random_account(&db).await.unwrap();
-        let to_account = random_account(&db).await.unwrap();
+        let to_account = random_account_in(&db, from_account.currency).await.unwrap();
 
-        let money = random_int(10, from_account.balance);
+        let money = Money::new(random_int(10, 100), from_account.currency);
         let transfer = random_transfer(&db, from_account.id, to_account.id, money)
             .await
             .unwrap();
@@ -96,4 +202,89 @@ mod tests {
         let got = get_transfer(&db, transfer.id).await.unwrap();
         assert_eq!(got, transfer);
     }
+
+    #[tokio::test]
+    async fn test_list_transfers() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let account1 = random_account(&db).await.unwrap();
+        let account2 = random_account_in(&db, account1.currency).await.unwrap();
+        let money = || Money::new(random_money(), account1.currency);
+
+        let outgoing = random_transfer(&db, account1.id, account2.id, money())
+            .await
+            .unwrap();
+        let incoming = random_transfer(&db, account2.id, account1.id, money())
+            .await
+            .unwrap();
+
+        let mut arg = ListTransfersParams {
+            account_id: Some(account1.id),
+            direction: None,
+            start_time: None,
+            end_time: None,
+            limit: 5,
+            offset: 0,
+        };
+
+        let got = list_transfers(&db, arg.clone()).await.unwrap();
+        assert_eq!(got, vec![outgoing.clone(), incoming.clone()]);
+
+        arg.direction = Some(TransferDirection::Outgoing);
+        let got = list_transfers(&db, arg.clone()).await.unwrap();
+        assert_eq!(got, vec![outgoing.clone()]);
+
+        arg.direction = Some(TransferDirection::Incoming);
+        let got = list_transfers(&db, arg.clone()).await.unwrap();
+        assert_eq!(got, vec![incoming.clone()]);
+
+        arg.direction = None;
+        arg.start_time = Some(incoming.created_at);
+        let got = list_transfers(&db, arg).await.unwrap();
+        assert_eq!(got, vec![incoming]);
+    }
+
+    #[tokio::test]
+    async fn test_unbalanced_transfer_rejected() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let from_account = random_account(&db).await.unwrap();
+        let to_account = random_account_in(&db, from_account.currency).await.unwrap();
+        let amount = Money::new(random_int(10, 100), from_account.currency);
+
+        let mut tx = db.begin().await.unwrap();
+        let transfer = create_transfer(
+            &mut tx,
+            CreateTransferParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount,
+                conversion: None,
+            },
+        )
+        .await
+        .unwrap();
+
+        // Debit without the matching credit.
+        create_entry(
+            &mut tx,
+            CreateEntryParams {
+                account_id: from_account.id,
+                amount: amount.checked_neg().unwrap(),
+                transfer_id: Some(transfer.id),
+            },
+        )
+        .await
+        .unwrap();
+
+        let err: ServerError = tx.commit().await.unwrap_err().into();
+        assert_eq!(err.client_error(), ClientError::ConstraintViolation);
+        assert!(get_transfer(&db, transfer.id).await.is_err());
+    }
 }
@@ -0,0 +1,106 @@
+use crate::models::{Role, User};
+use crate::prelude::*;
+use tracing::instrument;
+
+#[derive(Debug, Clone)]
+pub struct CreateUserParams {
+    pub username: String,
+    pub hashed_password: String,
+    pub full_name: String,
+    pub email: String,
+    pub role: Role,
+}
+
+#[instrument(skip(pool, arg), fields(username = %arg.username))]
+pub async fn create_user(pool: &sqlx::PgPool, arg: CreateUserParams) -> Result<User> {
+    let user = sqlx::query_as!(
+        User,
+        r#"INSERT INTO users (username, hashed_password, full_name, email, role)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING username, hashed_password, full_name, email, password_changed_at,
+            created_at, role AS "role: Role";"#,
+        arg.username,
+        arg.hashed_password,
+        arg.full_name,
+        arg.email,
+        arg.role as Role
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(user)
+}
+
+#[instrument(skip(pool))]
+pub async fn get_user(pool: &sqlx::PgPool, username: &str) -> Result<User> {
+    let user = sqlx::query_as!(
+        User,
+        r#"SELECT username, hashed_password, full_name, email, password_changed_at,
+            created_at, role AS "role: Role"
+        FROM users WHERE username = $1 LIMIT 1;"#,
+        username
+    )
+    .fetch_one(pool)
+    .await?;
+    Ok(user)
+}
+
+mod tests {
+    use super::*;
+    use crate::{db::create_connection_pool, utils::*};
+
+    #[tokio::test]
+    async fn test_create_user() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let arg = CreateUserParams {
+            username: random_owner(),
+            hashed_password: random_string(32),
+            full_name: random_owner(),
+            email: random_email(),
+            role: Role::Depositor,
+        };
+
+        let user = create_user(&db, arg.clone()).await.unwrap();
+        assert_eq!(user.username, arg.username);
+        assert_eq!(user.hashed_password, arg.hashed_password);
+        assert_eq!(user.full_name, arg.full_name);
+        assert_eq!(user.email, arg.email);
+        assert_eq!(user.role, arg.role);
+    }
+
+    #[tokio::test]
+    async fn test_create_user_duplicate_username() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let user = random_user(&db).await.unwrap();
+        let arg = CreateUserParams {
+            username: user.username,
+            hashed_password: random_string(32),
+            full_name: random_owner(),
+            email: random_email(),
+            role: Role::Depositor,
+        };
+
+        let err: ServerError = create_user(&db, arg).await.unwrap_err().into();
+        assert_eq!(err.client_error(), ClientError::DuplicateResource);
+    }
+
+    #[tokio::test]
+    async fn test_get_user() {
+        dotenv::dotenv().ok();
+        let db = create_connection_pool(Some(10))
+            .await
+            .expect("Failed to create connection pool");
+
+        let user = random_user(&db).await.unwrap();
+        let user2 = get_user(&db, &user.username).await.unwrap();
+
+        assert_eq!(user, user2);
+    }
+}
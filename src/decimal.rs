@@ -0,0 +1,190 @@
+use serde::{de, Deserialize, Deserializer, Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{PgArgumentBuffer, PgTypeInfo, PgValueRef},
+    Decode, Encode, Postgres, Type,
+};
+use std::{fmt, str::FromStr};
+
+/// A fixed-point decimal with ten fractional digits, such as an exchange rate.
+///
+/// Postgres stores it as `numeric(18,10)`. sqlx is built without a decimal
+/// type, so values are exchanged as text: read with `rate::text`, bind as
+/// `$1::text::numeric`. It serializes as a decimal string.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Default)]
+pub struct Decimal(i64);
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ParseFixedError {
+    Invalid,
+    Overflow,
+}
+
+impl Decimal {
+    pub const PLACES: u32 = 10;
+    pub const SCALE: i64 = 10_i64.pow(Decimal::PLACES);
+    pub const ZERO: Decimal = Decimal(0);
+
+    /// The decimal whose value is `scaled / 10^PLACES`.
+    pub const fn from_scaled(scaled: i64) -> Decimal {
+        Decimal(scaled)
+    }
+
+    pub fn scaled(self) -> i64 {
+        self.0
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.0 > 0
+    }
+}
+
+/// Parses a decimal string such as `"-12.05"` into an integer scaled by
+/// `10^places`. At most `places` fractional digits are accepted.
+pub fn parse_fixed(s: &str, places: u32) -> Result<i64, ParseFixedError> {
+    let (negative, digits) = match s.strip_prefix('-') {
+        Some(digits) => (true, digits),
+        None => (false, s),
+    };
+    let (whole, fraction) = digits.split_once('.').unwrap_or((digits, ""));
+    let is_digits = |s: &str| s.bytes().all(|byte| byte.is_ascii_digit());
+    if whole.is_empty()
+        || !is_digits(whole)
+        || !is_digits(fraction)
+        || (digits.contains('.') && fraction.is_empty())
+        || fraction.len() > places as usize
+    {
+        return Err(ParseFixedError::Invalid);
+    }
+
+    // Accumulate negatively so that i64::MIN is representable.
+    let mut value: i64 = 0;
+    let padding = places as usize - fraction.len();
+    for byte in whole.bytes().chain(fraction.bytes()) {
+        value = value
+            .checked_mul(10)
+            .and_then(|value| value.checked_sub(i64::from(byte - b'0')))
+            .ok_or(ParseFixedError::Overflow)?;
+    }
+    for _ in 0..padding {
+        value = value.checked_mul(10).ok_or(ParseFixedError::Overflow)?;
+    }
+    if negative {
+        Ok(value)
+    } else {
+        value.checked_neg().ok_or(ParseFixedError::Overflow)
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ParseFixedError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        parse_fixed(s, Decimal::PLACES).map(Decimal)
+    }
+}
+
+/// Prints the shortest exact form, e.g. `1.085` or `2`.
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        let scale = Decimal::SCALE as u64;
+        let fraction = format!("{:0width$}", abs % scale, width = Decimal::PLACES as usize);
+        let fraction = fraction.trim_end_matches('0');
+        if fraction.is_empty() {
+            write!(f, "{sign}{}", abs / scale)
+        } else {
+            write!(f, "{sign}{}.{fraction}", abs / scale)
+        }
+    }
+}
+
+impl fmt::Display for ParseFixedError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseFixedError::Invalid => write!(f, "invalid decimal"),
+            ParseFixedError::Overflow => write!(f, "decimal is out of range"),
+        }
+    }
+}
+
+impl std::error::Error for ParseFixedError {}
+
+impl Serialize for Decimal {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for Decimal {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let s = String::deserialize(deserializer)?;
+        s.parse().map_err(|_| {
+            de::Error::custom(format!(
+                "{s:?} is not a decimal with at most {} decimal places",
+                Decimal::PLACES
+            ))
+        })
+    }
+}
+
+impl Type<Postgres> for Decimal {
+    fn type_info() -> PgTypeInfo {
+        <&str as Type<Postgres>>::type_info()
+    }
+
+    fn compatible(ty: &PgTypeInfo) -> bool {
+        <&str as Type<Postgres>>::compatible(ty)
+    }
+}
+
+impl Encode<'_, Postgres> for Decimal {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <String as Encode<Postgres>>::encode(self.to_string(), buf)
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Decimal {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(<&str as Decode<Postgres>>::decode(value)?.parse()?)
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_decimal() {
+        assert_eq!("1.085".parse(), Ok(Decimal::from_scaled(10_850_000_000)));
+        assert_eq!("0.0000000001".parse(), Ok(Decimal::from_scaled(1)));
+        assert_eq!("-2".parse(), Ok(Decimal::from_scaled(-2 * Decimal::SCALE)));
+        // Postgres prints numeric(18,10) with all ten places.
+        assert_eq!(
+            "1.0850000000".parse::<Decimal>().unwrap().to_string(),
+            "1.085"
+        );
+
+        assert_eq!(
+            "1.00000000001".parse::<Decimal>(),
+            Err(ParseFixedError::Invalid)
+        );
+        assert_eq!("1e3".parse::<Decimal>(), Err(ParseFixedError::Invalid));
+        assert_eq!(
+            "1000000000".parse::<Decimal>(),
+            Err(ParseFixedError::Overflow)
+        );
+    }
+
+    #[test]
+    fn test_format_decimal() {
+        assert_eq!(Decimal::ZERO.to_string(), "0");
+        assert_eq!(Decimal::from_scaled(Decimal::SCALE).to_string(), "1");
+        assert_eq!(Decimal::from_scaled(-5).to_string(), "-0.0000000005");
+        assert_eq!(
+            serde_json::to_value(Decimal::from_scaled(12_500_000_000)).unwrap(),
+            serde_json::json!("1.25")
+        );
+    }
+}
@@ -1,27 +1,502 @@
-use axum::http::StatusCode;
+use crate::{
+    currency::Currency,
+    models::AccountStatus,
+    money::{Money, MoneyError},
+};
+use axum::extract::rejection::JsonRejection;
+use axum::http::{header, StatusCode};
 use axum::response::{IntoResponse, Response};
+use axum::Json;
+use chrono::{DateTime, SecondsFormat, Utc};
 use serde::Serialize;
+use serde_json::{json, Map, Value};
+use uuid::Uuid;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 pub type Result<T> = std::result::Result<T, Error>;
 pub type SQLResult<T> = std::result::Result<T, sqlx::Error>;
 pub type ServerResult<T> = std::result::Result<T, ServerError>;
 
+const PROBLEM_JSON: &str = "application/problem+json";
+
 #[derive(Clone, Debug, Serialize, strum_macros::AsRefStr)]
 #[serde(tag = "type", content = "data")]
 pub enum ServerError {
-    CreateAccountFail,
+    Database(DatabaseError),
+    Ledger(LedgerError),
+    Money(MoneyError),
+    Validation(Vec<FieldError>),
+    /// A request body that could not be read; the message is safe to show.
+    BadRequest(String),
+    Unauthorized(&'static str),
+    Internal(String),
     ClientError(ClientError),
 }
 
+/// Classified `sqlx::Error`. `sqlx::Error` itself is neither `Clone` nor
+/// `Serialize`, so only the parts needed for the response are kept.
 #[derive(Clone, Debug, Serialize, strum_macros::AsRefStr)]
 #[serde(tag = "type", content = "data")]
+pub enum DatabaseError {
+    RowNotFound,
+    UniqueViolation { constraint: Option<String> },
+    ForeignKeyViolation { constraint: Option<String> },
+    CheckViolation { constraint: Option<String> },
+    SerializationFailure,
+    Deadlock,
+    PoolTimedOut,
+    Other(String),
+}
+
+/// Business-rule violations raised by the store layer.
+#[derive(Clone, Debug, PartialEq, Serialize, strum_macros::AsRefStr)]
+#[serde(tag = "type", content = "data")]
+pub enum LedgerError {
+    InsufficientFunds {
+        account_id: i64,
+        available: Money,
+    },
+    ExchangeRateUnavailable {
+        from: Currency,
+        to: Currency,
+    },
+    FxQuoteExpired {
+        quote_id: Uuid,
+        expired_at: DateTime<Utc>,
+    },
+    FxQuoteUsed {
+        quote_id: Uuid,
+        transfer_id: i64,
+    },
+    /// The account's status does not allow it to send or receive funds.
+    AccountUnavailable {
+        account_id: i64,
+        status: AccountStatus,
+    },
+    InvalidStatusTransition {
+        account_id: i64,
+        from: AccountStatus,
+        to: AccountStatus,
+    },
+    AccountNotEmpty {
+        account_id: i64,
+        balance: Money,
+    },
+    /// The account belongs to the system, e.g. an FX position account, and
+    /// its status cannot be changed.
+    SystemAccount {
+        account_id: i64,
+    },
+    /// A transfer names the same account as sender and receiver.
+    SameAccount {
+        account_id: i64,
+    },
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize)]
+pub struct FieldError {
+    pub field: String,
+    pub message: String,
+}
+
+/// Errors exposed to API clients. The snake_case variant name is the stable,
+/// machine-readable `code` of the problem-details body.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, strum_macros::IntoStaticStr)]
+#[strum(serialize_all = "snake_case")]
 pub enum ClientError {
     BadRequest,
+    ValidationFailed,
     Unauthorized,
     Forbidden,
     NotFound,
     Conflict,
+    DuplicateResource,
+    ResourceInUse,
+    ConstraintViolation,
+    TransactionConflict,
+    AccountPending,
+    AccountFrozen,
+    AccountClosed,
+    AccountNotEmpty,
+    InvalidStatusTransition,
+    InsufficientFunds,
+    CurrencyMismatch,
+    AmountOutOfRange,
+    ExchangeRateUnavailable,
+    FxQuoteExpired,
+    FxQuoteUsed,
+    IdempotencyKeyReused,
+    IdempotencyKeyInProgress,
+    ServiceUnavailable,
+    InternalError,
+}
+
+/// RFC 7807 problem-details body.
+#[derive(Debug, Serialize)]
+pub struct ProblemDetails {
+    #[serde(rename = "type")]
+    pub problem_type: String,
+    pub title: &'static str,
+    pub status: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+    pub code: &'static str,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub request_id: Option<String>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub errors: Vec<FieldError>,
+    /// Problem-specific extension members.
+    #[serde(flatten)]
+    pub extensions: Map<String, Value>,
+}
+
+impl FieldError {
+    pub fn new(field: impl Into<String>, message: impl Into<String>) -> Self {
+        Self {
+            field: field.into(),
+            message: message.into(),
+        }
+    }
+}
+
+impl ServerError {
+    pub fn validation(field: impl Into<String>, message: impl Into<String>) -> Self {
+        ServerError::Validation(vec![FieldError::new(field, message)])
+    }
+
+    pub fn client_error(&self) -> ClientError {
+        match self {
+            ServerError::Internal(_) => ClientError::InternalError,
+            ServerError::Validation(_) => ClientError::ValidationFailed,
+            ServerError::BadRequest(_) => ClientError::BadRequest,
+            ServerError::Ledger(LedgerError::InsufficientFunds { .. }) => {
+                ClientError::InsufficientFunds
+            }
+            ServerError::Ledger(LedgerError::ExchangeRateUnavailable { .. }) => {
+                ClientError::ExchangeRateUnavailable
+            }
+            ServerError::Ledger(LedgerError::FxQuoteExpired { .. }) => ClientError::FxQuoteExpired,
+            ServerError::Ledger(LedgerError::FxQuoteUsed { .. }) => ClientError::FxQuoteUsed,
+            ServerError::Ledger(LedgerError::AccountUnavailable { status, .. }) => match status {
+                AccountStatus::Frozen => ClientError::AccountFrozen,
+                AccountStatus::Closed => ClientError::AccountClosed,
+                AccountStatus::Pending => ClientError::AccountPending,
+                // Active accounts can send and receive; reporting one as
+                // unavailable is a bug, not something a client can act on.
+                AccountStatus::Active => ClientError::InternalError,
+            },
+            ServerError::Ledger(LedgerError::InvalidStatusTransition { .. }) => {
+                ClientError::InvalidStatusTransition
+            }
+            ServerError::Ledger(LedgerError::AccountNotEmpty { .. }) => {
+                ClientError::AccountNotEmpty
+            }
+            ServerError::Ledger(LedgerError::SystemAccount { .. }) => ClientError::Forbidden,
+            ServerError::Ledger(LedgerError::SameAccount { .. }) => ClientError::ValidationFailed,
+            ServerError::Money(money_error) => match money_error {
+                MoneyError::Overflow => ClientError::AmountOutOfRange,
+                MoneyError::CurrencyMismatch { .. } => ClientError::CurrencyMismatch,
+                MoneyError::InvalidAmount { .. } => ClientError::ValidationFailed,
+            },
+            ServerError::Unauthorized(_) => ClientError::Unauthorized,
+            ServerError::ClientError(client_error) => *client_error,
+            ServerError::Database(db_error) => match db_error {
+                DatabaseError::RowNotFound => ClientError::NotFound,
+                DatabaseError::UniqueViolation { .. } => ClientError::DuplicateResource,
+                DatabaseError::ForeignKeyViolation { .. } => ClientError::ResourceInUse,
+                DatabaseError::CheckViolation { .. } => ClientError::ConstraintViolation,
+                DatabaseError::SerializationFailure | DatabaseError::Deadlock => {
+                    ClientError::TransactionConflict
+                }
+                DatabaseError::PoolTimedOut => ClientError::ServiceUnavailable,
+                DatabaseError::Other(_) => ClientError::InternalError,
+            },
+        }
+    }
+
+    /// Client-safe explanation. Internal details are never exposed.
+    fn detail(&self) -> Option<String> {
+        match self {
+            ServerError::Database(DatabaseError::UniqueViolation { constraint })
+            | ServerError::Database(DatabaseError::ForeignKeyViolation { constraint })
+            | ServerError::Database(DatabaseError::CheckViolation { constraint }) => constraint
+                .as_ref()
+                .map(|constraint| format!("violates constraint \"{constraint}\"")),
+            ServerError::Unauthorized(reason) => Some(reason.to_string()),
+            ServerError::BadRequest(message) => Some(message.clone()),
+            ServerError::Money(money_error) => Some(money_error.to_string()),
+            ServerError::Ledger(LedgerError::InsufficientFunds { account_id, .. }) => Some(
+                format!("account {account_id} has insufficient funds for this transfer"),
+            ),
+            ServerError::Ledger(LedgerError::ExchangeRateUnavailable { from, to }) => {
+                Some(format!("no exchange rate from {from} to {to} is in effect"))
+            }
+            ServerError::Ledger(LedgerError::FxQuoteExpired {
+                quote_id,
+                expired_at,
+            }) => Some(format!(
+                "quote {quote_id} expired at {}",
+                expired_at.to_rfc3339_opts(SecondsFormat::Secs, true)
+            )),
+            ServerError::Ledger(LedgerError::FxQuoteUsed {
+                quote_id,
+                transfer_id,
+            }) => Some(format!(
+                "quote {quote_id} was already used by transfer {transfer_id}"
+            )),
+            ServerError::Ledger(LedgerError::AccountUnavailable { account_id, status })
+                if *status != AccountStatus::Active =>
+            {
+                Some(format!("account {account_id} is {status}"))
+            }
+            ServerError::Ledger(LedgerError::InvalidStatusTransition {
+                account_id,
+                from,
+                to,
+            }) => Some(format!(
+                "account {account_id} cannot go from {from} to {to}"
+            )),
+            ServerError::Ledger(LedgerError::AccountNotEmpty { account_id, .. }) => Some(format!(
+                "account {account_id} must have a zero balance to be closed"
+            )),
+            ServerError::Ledger(LedgerError::SystemAccount { account_id }) => Some(format!(
+                "account {account_id} is a system account and its status cannot change"
+            )),
+            ServerError::Database(DatabaseError::SerializationFailure)
+            | ServerError::Database(DatabaseError::Deadlock) => {
+                Some("concurrent update, please retry".to_string())
+            }
+            ServerError::ClientError(ClientError::IdempotencyKeyReused) => {
+                Some("idempotency key was already used for a different request".to_string())
+            }
+            ServerError::ClientError(ClientError::IdempotencyKeyInProgress) => {
+                Some("a request with this idempotency key is still being processed".to_string())
+            }
+            _ => None,
+        }
+    }
+
+    pub fn to_problem(&self, request_id: Option<String>) -> ProblemDetails {
+        let client_error = self.client_error();
+        let errors = match self {
+            ServerError::Validation(errors) => errors.clone(),
+            ServerError::Ledger(LedgerError::SameAccount { .. }) => vec![FieldError::new(
+                "to_account_id",
+                "must differ from from_account_id",
+            )],
+            _ => vec![],
+        };
+        let mut extensions = Map::new();
+        if let ServerError::Ledger(LedgerError::InsufficientFunds { available, .. }) = self {
+            extensions.insert("available_balance".to_string(), json!(available));
+        }
+        if let ServerError::Ledger(LedgerError::AccountNotEmpty { balance, .. }) = self {
+            extensions.insert("balance".to_string(), json!(balance));
+        }
+
+        ProblemDetails {
+            problem_type: format!("/problems/{}", client_error.code().replace('_', "-")),
+            title: client_error.title(),
+            status: client_error.status().as_u16(),
+            detail: self.detail(),
+            code: client_error.code(),
+            request_id,
+            errors,
+            extensions,
+        }
+    }
+}
+
+impl ClientError {
+    pub fn code(&self) -> &'static str {
+        self.into()
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            ClientError::BadRequest => StatusCode::BAD_REQUEST,
+            ClientError::ValidationFailed
+            | ClientError::ConstraintViolation
+            | ClientError::InsufficientFunds
+            | ClientError::CurrencyMismatch
+            | ClientError::AmountOutOfRange
+            | ClientError::ExchangeRateUnavailable
+            | ClientError::FxQuoteExpired => StatusCode::UNPROCESSABLE_ENTITY,
+            ClientError::Unauthorized => StatusCode::UNAUTHORIZED,
+            ClientError::Forbidden => StatusCode::FORBIDDEN,
+            ClientError::NotFound => StatusCode::NOT_FOUND,
+            ClientError::Conflict
+            | ClientError::DuplicateResource
+            | ClientError::ResourceInUse
+            | ClientError::TransactionConflict
+            | ClientError::AccountPending
+            | ClientError::AccountFrozen
+            | ClientError::AccountClosed
+            | ClientError::AccountNotEmpty
+            | ClientError::InvalidStatusTransition
+            | ClientError::FxQuoteUsed
+            | ClientError::IdempotencyKeyReused
+            | ClientError::IdempotencyKeyInProgress => StatusCode::CONFLICT,
+            ClientError::ServiceUnavailable => StatusCode::SERVICE_UNAVAILABLE,
+            ClientError::InternalError => StatusCode::INTERNAL_SERVER_ERROR,
+        }
+    }
+
+    pub fn title(&self) -> &'static str {
+        match self {
+            ClientError::BadRequest => "Bad Request",
+            ClientError::ValidationFailed => "Validation Failed",
+            ClientError::Unauthorized => "Unauthorized",
+            ClientError::Forbidden => "Forbidden",
+            ClientError::NotFound => "Not Found",
+            ClientError::Conflict => "Conflict",
+            ClientError::DuplicateResource => "Duplicate Resource",
+            ClientError::ResourceInUse => "Resource In Use",
+            ClientError::ConstraintViolation => "Constraint Violation",
+            ClientError::TransactionConflict => "Transaction Conflict",
+            ClientError::AccountPending => "Account Pending",
+            ClientError::AccountFrozen => "Account Frozen",
+            ClientError::AccountClosed => "Account Closed",
+            ClientError::AccountNotEmpty => "Account Not Empty",
+            ClientError::InvalidStatusTransition => "Invalid Status Transition",
+            ClientError::InsufficientFunds => "Insufficient Funds",
+            ClientError::CurrencyMismatch => "Currency Mismatch",
+            ClientError::AmountOutOfRange => "Amount Out Of Range",
+            ClientError::ExchangeRateUnavailable => "Exchange Rate Unavailable",
+            ClientError::FxQuoteExpired => "FX Quote Expired",
+            ClientError::FxQuoteUsed => "FX Quote Used",
+            ClientError::IdempotencyKeyReused => "Idempotency Key Reused",
+            ClientError::IdempotencyKeyInProgress => "Idempotency Key In Progress",
+            ClientError::ServiceUnavailable => "Service Unavailable",
+            ClientError::InternalError => "Internal Server Error",
+        }
+    }
+}
+
+impl From<sqlx::Error> for DatabaseError {
+    fn from(err: sqlx::Error) -> Self {
+        match &err {
+            sqlx::Error::RowNotFound => DatabaseError::RowNotFound,
+            sqlx::Error::PoolTimedOut => DatabaseError::PoolTimedOut,
+            sqlx::Error::Database(db_err) => {
+                let constraint = db_err.constraint().map(str::to_string);
+                match db_err.code().as_deref() {
+                    Some("23505") => DatabaseError::UniqueViolation { constraint },
+                    Some("23503") => DatabaseError::ForeignKeyViolation { constraint },
+                    Some("23514") => DatabaseError::CheckViolation { constraint },
+                    Some("40001") => DatabaseError::SerializationFailure,
+                    Some("40P01") => DatabaseError::Deadlock,
+                    _ => DatabaseError::Other(err.to_string()),
+                }
+            }
+            _ => DatabaseError::Other(err.to_string()),
+        }
+    }
+}
+
+impl core::fmt::Display for LedgerError {
+    fn fmt(&self, fmt: &mut core::fmt::Formatter) -> core::result::Result<(), core::fmt::Error> {
+        match self {
+            LedgerError::InsufficientFunds {
+                account_id,
+                available,
+            } => write!(
+                fmt,
+                "account {account_id} has insufficient funds: {available} available"
+            ),
+            LedgerError::ExchangeRateUnavailable { from, to } => {
+                write!(fmt, "no exchange rate from {from} to {to}")
+            }
+            LedgerError::FxQuoteExpired {
+                quote_id,
+                expired_at,
+            } => write!(fmt, "quote {quote_id} expired at {expired_at}"),
+            LedgerError::FxQuoteUsed {
+                quote_id,
+                transfer_id,
+            } => write!(fmt, "quote {quote_id} was used by transfer {transfer_id}"),
+            LedgerError::AccountUnavailable { account_id, status } => {
+                write!(fmt, "account {account_id} is {status}")
+            }
+            LedgerError::InvalidStatusTransition {
+                account_id,
+                from,
+                to,
+            } => write!(fmt, "account {account_id} cannot go from {from} to {to}"),
+            LedgerError::AccountNotEmpty {
+                account_id,
+                balance,
+            } => write!(fmt, "account {account_id} has a balance of {balance}"),
+            LedgerError::SystemAccount { account_id } => {
+                write!(fmt, "account {account_id} is a system account")
+            }
+            LedgerError::SameAccount { account_id } => {
+                write!(fmt, "cannot transfer from account {account_id} to itself")
+            }
+        }
+    }
+}
+
+impl std::error::Error for LedgerError {}
+
+impl From<MoneyError> for ServerError {
+    fn from(err: MoneyError) -> Self {
+        ServerError::Money(err)
+    }
+}
+
+impl From<sqlx::Error> for ServerError {
+    fn from(err: sqlx::Error) -> Self {
+        ServerError::Database(err.into())
+    }
+}
+
+impl From<Error> for ServerError {
+    fn from(err: Error) -> Self {
+        let err = match err.downcast::<sqlx::Error>() {
+            Ok(sqlx_err) => return (*sqlx_err).into(),
+            Err(err) => err,
+        };
+        let err = match err.downcast::<LedgerError>() {
+            Ok(ledger_err) => return ServerError::Ledger(*ledger_err),
+            Err(err) => err,
+        };
+        match err.downcast::<MoneyError>() {
+            Ok(money_err) => ServerError::Money(*money_err),
+            Err(err) => ServerError::Internal(err.to_string()),
+        }
+    }
+}
+
+/// A body that is valid JSON but does not match the request type fails
+/// validation on the offending field, e.g. an unsupported `currency`. Anything
+/// else about the body is a bad request.
+impl From<JsonRejection> for ServerError {
+    fn from(rejection: JsonRejection) -> Self {
+        type PathError = serde_path_to_error::Error<serde_json::Error>;
+
+        let data_error = match &rejection {
+            JsonRejection::JsonDataError(err) => std::error::Error::source(err)
+                .and_then(|err| err.source())
+                .and_then(|err| err.downcast_ref::<PathError>()),
+            _ => None,
+        };
+        let Some(err) = data_error else {
+            return ServerError::BadRequest(rejection.body_text());
+        };
+
+        let field = match err.path().to_string() {
+            path if path == "." => "body".to_string(),
+            path => path,
+        };
+        let message = err.inner().to_string();
+        // serde_json appends the position, which means nothing to the caller.
+        let message = match message.rsplit_once(" at line ") {
+            Some((message, _)) if err.inner().line() > 0 => message.to_string(),
+            _ => message,
+        };
+        ServerError::validation(field, message)
+    }
 }
 
 impl core::fmt::Display for ServerError {
@@ -32,14 +507,14 @@ impl core::fmt::Display for ServerError {
 
 impl IntoResponse for ServerError {
     fn into_response(self) -> Response {
-        println!("->> {:<12} - {self:?}", "INTO_RES");
+        if self.client_error().status().is_server_error() {
+            tracing::error!(error = ?self, "request failed");
+        } else {
+            tracing::debug!(error = ?self, "request rejected");
+        }
 
-        let mut response = match self.clone() {
-            ServerError::CreateAccountFail => {
-                (StatusCode::INTERNAL_SERVER_ERROR, "Create Account Fail").into_response()
-            }
-            ServerError::ClientError(client_error) => client_error.into_response(),
-        };
+        let problem = self.to_problem(None);
+        let mut response = problem.into_response();
         response.extensions_mut().insert(self);
 
         response
@@ -48,18 +523,112 @@ impl IntoResponse for ServerError {
 
 impl IntoResponse for ClientError {
     fn into_response(self) -> Response {
-        println!("->> {:<12} - {self:?}", "INTO_RES");
-
-        let mut response = match &self {
-            ClientError::BadRequest => (StatusCode::BAD_REQUEST, "Bad Request").into_response(),
-            ClientError::Unauthorized => (StatusCode::UNAUTHORIZED, "Unauthorized").into_response(),
-            ClientError::Forbidden => todo!(),
-            ClientError::NotFound => todo!(),
-            ClientError::Conflict => todo!(),
+        ServerError::ClientError(self).into_response()
+    }
+}
+
+impl IntoResponse for ProblemDetails {
+    fn into_response(self) -> Response {
+        let status = StatusCode::from_u16(self.status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+
+        (status, [(header::CONTENT_TYPE, PROBLEM_JSON)], Json(self)).into_response()
+    }
+}
+
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_row_not_found_maps_to_404() {
+        let err: ServerError = Error::from(sqlx::Error::RowNotFound).into();
+
+        assert_eq!(err.client_error(), ClientError::NotFound);
+        assert_eq!(err.client_error().status(), StatusCode::NOT_FOUND);
+    }
+
+    #[test]
+    fn test_unknown_error_maps_to_internal() {
+        let err: ServerError = Error::from("boom").into();
+
+        assert_eq!(err.client_error(), ClientError::InternalError);
+        assert_eq!(err.to_problem(None).detail, None);
+    }
+
+    #[test]
+    fn test_insufficient_funds_maps_to_422() {
+        let err: ServerError = Error::from(LedgerError::InsufficientFunds {
+            account_id: 1,
+            available: Money::new(42, "USD".parse().unwrap()),
+        })
+        .into();
+        let problem = err.to_problem(None);
+
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.code, "insufficient_funds");
+        assert_eq!(problem.extensions["available_balance"], json!("0.42"));
+    }
+
+    #[test]
+    fn test_account_unavailable_maps_by_status() {
+        let unavailable = |status| -> ServerError {
+            Error::from(LedgerError::AccountUnavailable {
+                account_id: 1,
+                status,
+            })
+            .into()
         };
 
-        response.extensions_mut().insert(self);
+        let problem = unavailable(AccountStatus::Pending).to_problem(None);
+        assert_eq!(problem.status, 409);
+        assert_eq!(problem.code, "account_pending");
+        assert_eq!(
+            unavailable(AccountStatus::Frozen).client_error(),
+            ClientError::AccountFrozen
+        );
+        assert_eq!(
+            unavailable(AccountStatus::Closed).client_error(),
+            ClientError::AccountClosed
+        );
 
-        response
+        let problem = unavailable(AccountStatus::Active).to_problem(None);
+        assert_eq!(problem.status, 500);
+        assert_eq!(problem.code, "internal_error");
+        assert_eq!(problem.detail, None);
+    }
+
+    #[test]
+    fn test_money_errors_map_to_422() {
+        let usd = "USD".parse().unwrap();
+        let eur = "EUR".parse().unwrap();
+        let err: ServerError = Error::from(MoneyError::CurrencyMismatch {
+            expected: usd,
+            found: eur,
+        })
+        .into();
+        let problem = err.to_problem(None);
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.code, "currency_mismatch");
+        assert_eq!(
+            problem.detail.as_deref(),
+            Some("expected an amount in USD, not EUR")
+        );
+
+        let err: ServerError = Error::from(MoneyError::Overflow).into();
+        assert_eq!(err.client_error(), ClientError::AmountOutOfRange);
+    }
+
+    #[test]
+    fn test_problem_details() {
+        let err = ServerError::validation("page_size", "must be between 5 and 10");
+        let problem = err.to_problem(Some("req-1".to_string()));
+
+        assert_eq!(problem.status, 422);
+        assert_eq!(problem.code, "validation_failed");
+        assert_eq!(problem.problem_type, "/problems/validation-failed");
+        assert_eq!(problem.request_id.as_deref(), Some("req-1"));
+        assert_eq!(
+            problem.errors,
+            vec![FieldError::new("page_size", "must be between 5 and 10")]
+        );
     }
 }
@@ -0,0 +1,161 @@
+use crate::{
+    currency::Currency,
+    decimal::Decimal,
+    error::{Error, LedgerError},
+    models::{ExchangeRate, FxQuote},
+    money::{Money, MoneyError},
+};
+use chrono::{DateTime, Utc};
+
+/// Owner of the position accounts that take the other side of conversions,
+/// one account per currency. Created by the `add_exchange_rates` migration.
+pub const FX_POSITION_OWNER: &str = "fx-position";
+
+/// An amount converted at an exchange rate.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Conversion {
+    pub exchange_rate_id: i64,
+    pub rate: Decimal,
+    /// Credited to the receiver, in the quote currency.
+    pub amount: Money,
+    /// Margin kept, in the quote currency.
+    pub spread: Money,
+    /// Fraction of a quote minor unit lost to rounding down.
+    pub residue: Decimal,
+}
+
+/// Converts `amount` from the rate's base currency into its quote currency.
+///
+/// The amount is converted at the mid-market rate and rounded down to a whole
+/// minor unit; the spread is then taken from that, also rounded down. What was
+/// rounded away is reported as the residue, to ten decimal places.
+pub fn convert(amount: Money, rate: &ExchangeRate) -> Result<Conversion, MoneyError> {
+    if amount.currency != rate.base_currency {
+        return Err(MoneyError::CurrencyMismatch {
+            expected: rate.base_currency,
+            found: amount.currency,
+        });
+    }
+    let quote = rate.quote_currency;
+    let base_scale = 10_i128.pow(amount.currency.minor_units());
+    let quote_scale = 10_i128.pow(quote.minor_units());
+
+    // The exact value in quote minor units, scaled by Decimal::SCALE.
+    let exact = i128::from(amount.minor_units)
+        .checked_mul(i128::from(rate.rate.scaled()))
+        .and_then(|value| value.checked_mul(quote_scale))
+        .ok_or(MoneyError::Overflow)?
+        .div_euclid(base_scale);
+    let scale = i128::from(Decimal::SCALE);
+    let gross = exact.div_euclid(scale);
+    let residue = exact.rem_euclid(scale);
+    let spread = gross * i128::from(rate.spread_bps) / 10_000;
+
+    let minor_units = |value: i128| i64::try_from(value).map_err(|_| MoneyError::Overflow);
+    Ok(Conversion {
+        exchange_rate_id: rate.id,
+        rate: rate.rate,
+        amount: Money::new(minor_units(gross - spread)?, quote),
+        spread: Money::new(minor_units(spread)?, quote),
+        residue: Decimal::from_scaled(minor_units(residue)?),
+    })
+}
+
+/// Checks that `quote` converts `from` into `to` and can still be used at `now`.
+pub fn check_quote(
+    quote: &FxQuote,
+    from: Currency,
+    to: Currency,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    if quote.base_currency != from {
+        return Err(MoneyError::CurrencyMismatch {
+            expected: quote.base_currency,
+            found: from,
+        }
+        .into());
+    }
+    if quote.quote_currency != to {
+        return Err(MoneyError::CurrencyMismatch {
+            expected: quote.quote_currency,
+            found: to,
+        }
+        .into());
+    }
+    if let Some(transfer_id) = quote.transfer_id {
+        return Err(LedgerError::FxQuoteUsed {
+            quote_id: quote.id,
+            transfer_id,
+        }
+        .into());
+    }
+    if quote.expires_at <= now {
+        return Err(LedgerError::FxQuoteExpired {
+            quote_id: quote.id,
+            expired_at: quote.expires_at,
+        }
+        .into());
+    }
+    Ok(())
+}
+
+mod tests {
+    use super::*;
+    use chrono::Utc;
+
+    fn rate(base: &str, quote: &str, rate: &str, spread_bps: i32) -> ExchangeRate {
+        ExchangeRate {
+            id: 1,
+            base_currency: base.parse().unwrap(),
+            quote_currency: quote.parse().unwrap(),
+            rate: rate.parse().unwrap(),
+            spread_bps,
+            effective_from: Utc::now(),
+            created_at: Utc::now(),
+        }
+    }
+
+    fn money(amount: &str, currency: &str) -> Money {
+        Money::parse(amount, currency.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_convert() {
+        // 10.00 EUR at 1.0857 is 10.857 USD: 10.85 credited, 0.7 cents rounded away.
+        let conversion = convert(money("10.00", "EUR"), &rate("EUR", "USD", "1.0857", 0)).unwrap();
+        assert_eq!(conversion.amount, money("10.85", "USD"));
+        assert_eq!(conversion.spread, money("0", "USD"));
+        assert_eq!(conversion.residue, "0.7".parse().unwrap());
+
+        // 1.50% of 10.85 USD is 16.275 cents, of which 16 are kept.
+        let conversion =
+            convert(money("10.00", "EUR"), &rate("EUR", "USD", "1.0857", 150)).unwrap();
+        assert_eq!(conversion.amount, money("10.69", "USD"));
+        assert_eq!(conversion.spread, money("0.16", "USD"));
+
+        // Currencies with different minor units.
+        let conversion = convert(money("1000", "JPY"), &rate("JPY", "KWD", "0.00205", 0)).unwrap();
+        assert_eq!(conversion.amount, money("2.050", "KWD"));
+        let conversion = convert(money("2.05", "USD"), &rate("USD", "JPY", "149.5", 0)).unwrap();
+        assert_eq!(conversion.amount, money("306", "JPY"));
+        assert_eq!(conversion.residue, "0.475".parse().unwrap());
+    }
+
+    #[test]
+    fn test_convert_errors() {
+        let eur_usd = rate("EUR", "USD", "1.0857", 0);
+        assert_eq!(
+            convert(money("10.00", "GBP"), &eur_usd),
+            Err(MoneyError::CurrencyMismatch {
+                expected: eur_usd.base_currency,
+                found: "GBP".parse().unwrap(),
+            })
+        );
+
+        let max = Money::new(i64::MAX, eur_usd.base_currency);
+        assert_eq!(
+            convert(max, &rate("EUR", "USD", "100", 0)),
+            Err(MoneyError::Overflow)
+        );
+    }
+}
@@ -1 +1,63 @@
+use crate::{currency::Currency, money::Money, prelude::*};
+use chrono::{DateTime, Utc};
+
 pub mod account;
+pub mod entry;
+pub mod fx;
+pub mod health;
+pub mod report;
+pub mod session;
+pub mod token;
+pub mod transfer;
+pub mod user;
+
+const MIN_PAGE_SIZE: i64 = 5;
+const MAX_PAGE_SIZE: i64 = 10;
+
+fn validate_id(field: &str, id: i64) -> ServerResult<()> {
+    if id < 1 {
+        return Err(ServerError::validation(field, "must be a positive integer"));
+    }
+    Ok(())
+}
+
+/// Validates paging bounds and converts them into a `(limit, offset)` pair.
+fn page_bounds(page_id: i64, page_size: i64) -> ServerResult<(i64, i64)> {
+    let mut errors = vec![];
+    if page_id < 1 {
+        errors.push(FieldError::new("page_id", "must be a positive integer"));
+    }
+    if !(MIN_PAGE_SIZE..=MAX_PAGE_SIZE).contains(&page_size) {
+        errors.push(FieldError::new(
+            "page_size",
+            format!("must be between {MIN_PAGE_SIZE} and {MAX_PAGE_SIZE}"),
+        ));
+    }
+    if !errors.is_empty() {
+        return Err(ServerError::Validation(errors));
+    }
+    let offset = (page_id - 1)
+        .checked_mul(page_size)
+        .ok_or_else(|| ServerError::validation("page_id", "is too large"))?;
+    Ok((page_size, offset))
+}
+
+fn validate_time_range(
+    start_time: Option<DateTime<Utc>>,
+    end_time: Option<DateTime<Utc>>,
+) -> ServerResult<()> {
+    if let (Some(start_time), Some(end_time)) = (start_time, end_time) {
+        if start_time >= end_time {
+            return Err(ServerError::validation(
+                "start_time",
+                "must be before end_time",
+            ));
+        }
+    }
+    Ok(())
+}
+
+/// Parses a decimal request amount in `currency`, e.g. `"12.05"`.
+fn parse_amount(field: &str, amount: &str, currency: Currency) -> ServerResult<Money> {
+    Money::parse(amount, currency).map_err(|err| ServerError::validation(field, err.to_string()))
+}
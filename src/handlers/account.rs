@@ -1,40 +1,209 @@
+use super::{page_bounds, parse_amount, validate_id};
 use crate::{
-    db::account_sql::{create_account, CreateAccountParams},
-    models::Account,
+    api::{
+        auth::AuthPayload,
+        authz::{get_owned_account, get_viewable_account},
+        json::Json,
+        policy::{Banker, RequireRole},
+    },
+    auth::token::Payload,
+    currency::Currency,
+    db::{
+        account_sql::{CreateAccountParams, ListAccountsParams},
+        store::{Store, UpdateAccountStatusParams},
+    },
+    models::{Account, AccountStatus, Role},
+    money::Money,
     prelude::*,
 };
 use axum::{
     async_trait,
-    extract::{FromRef, FromRequestParts, State},
+    extract::{FromRef, FromRequestParts, Path, Query, State},
     http::{request::Parts, StatusCode},
     response::IntoResponse,
-    Json,
 };
 use serde::Deserialize;
 use serde_json::Value;
-use sqlx::PgPool;
+use std::sync::Arc;
 
 #[derive(Debug, Deserialize)]
 pub struct CreateAccountRequest {
-    pub owner: String,
-    pub currency: String,
+    pub currency: Currency,
 }
 
-pub async fn create_account_handler(
-    State(pool): State<PgPool>,
+pub async fn create_account_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
     arg: Json<CreateAccountRequest>,
 ) -> ServerResult<Json<Account>> {
-    if arg.owner.is_empty() || arg.currency.is_empty() {
-        return Err(ServerError::ClientError(ClientError::BadRequest));
+    let params = CreateAccountParams {
+        owner: auth.username,
+        balance: Money::zero(arg.currency),
+    };
+
+    let account = store.create_account(params).await?;
+
+    Ok(Json(account))
+}
+
+pub async fn get_account_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(id): Path<i64>,
+) -> ServerResult<Json<Account>> {
+    validate_id("id", id)?;
+
+    let account = get_viewable_account(&*store, &auth, id).await?;
+
+    Ok(Json(account))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListAccountsRequest {
+    pub page_id: i64,
+    pub page_size: i64,
+    /// Bankers may filter by owner or omit it to list every account.
+    pub owner: Option<String>,
+}
+
+pub async fn list_accounts_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Query(arg): Query<ListAccountsRequest>,
+) -> ServerResult<Json<Vec<Account>>> {
+    let (limit, offset) = page_bounds(arg.page_id, arg.page_size)?;
+
+    let owner = match auth.role {
+        Role::Banker => arg.owner,
+        Role::Depositor => Some(auth.username),
+    };
+
+    let params = ListAccountsParams {
+        owner,
+        limit,
+        offset,
+    };
+
+    let accounts = store.list_accounts(params).await?;
+
+    Ok(Json(accounts))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateAccountStatusRequest {
+    /// Why the status changes, recorded in the audit trail.
+    pub reason: String,
+}
+
+pub async fn freeze_account_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    banker: RequireRole<Banker>,
+    Path(id): Path<i64>,
+    arg: Json<UpdateAccountStatusRequest>,
+) -> ServerResult<Json<Account>> {
+    validate_id("id", id)?;
+    validate_reason(&arg.reason)?;
+
+    let account = update_account_status(
+        &*store,
+        id,
+        AccountStatus::Frozen,
+        banker.payload,
+        &arg.reason,
+    )
+    .await?;
+
+    Ok(Json(account))
+}
+
+pub async fn unfreeze_account_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    banker: RequireRole<Banker>,
+    Path(id): Path<i64>,
+    arg: Json<UpdateAccountStatusRequest>,
+) -> ServerResult<Json<Account>> {
+    validate_id("id", id)?;
+    validate_reason(&arg.reason)?;
+
+    let account = update_account_status(
+        &*store,
+        id,
+        AccountStatus::Active,
+        banker.payload,
+        &arg.reason,
+    )
+    .await?;
+
+    Ok(Json(account))
+}
+
+/// Owners may close their own accounts; bankers may close any account. Only
+/// an account with a zero balance can be closed.
+pub async fn close_account_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(id): Path<i64>,
+    arg: Json<UpdateAccountStatusRequest>,
+) -> ServerResult<Json<Account>> {
+    validate_id("id", id)?;
+    validate_reason(&arg.reason)?;
+
+    get_viewable_account(&*store, &auth, id).await?;
+
+    let account =
+        update_account_status(&*store, id, AccountStatus::Closed, auth, &arg.reason).await?;
+
+    Ok(Json(account))
+}
+
+fn validate_reason(reason: &str) -> ServerResult<()> {
+    if reason.trim().is_empty() {
+        return Err(ServerError::validation("reason", "must not be empty"));
     }
+    Ok(())
+}
 
-    let params = CreateAccountParams {
-        owner: arg.owner.clone(),
-        balance: 0,
-        currency: arg.currency.clone(),
+async fn update_account_status<S: Store>(
+    store: &S,
+    id: i64,
+    status: AccountStatus,
+    auth: Payload,
+    reason: &str,
+) -> ServerResult<Account> {
+    let params = UpdateAccountStatusParams {
+        id,
+        status,
+        reason: reason.trim().to_string(),
+        changed_by: auth.username,
     };
 
-    let account = create_account(&pool, params).await.unwrap();
+    Ok(store.update_account_status(params).await?)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateOverdraftLimitRequest {
+    /// A decimal amount in the currency of the account.
+    pub overdraft_limit: String,
+}
+
+pub async fn update_overdraft_limit_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    _banker: RequireRole<Banker>,
+    Path(id): Path<i64>,
+    arg: Json<UpdateOverdraftLimitRequest>,
+) -> ServerResult<Json<Account>> {
+    validate_id("id", id)?;
+
+    let account = store.get_account(id).await?;
+    let overdraft_limit = parse_amount("overdraft_limit", &arg.overdraft_limit, account.currency)?;
+    if overdraft_limit.is_negative() {
+        return Err(ServerError::validation(
+            "overdraft_limit",
+            "must not be negative",
+        ));
+    }
+
+    let account = store.update_overdraft_limit(id, overdraft_limit).await?;
 
     Ok(Json(account))
 }
@@ -45,3 +214,265 @@ where
 {
     (StatusCode::INTERNAL_SERVER_ERROR, err.to_string())
 }
+
+mod tests {
+    use super::*;
+    use crate::{
+        api::router::routes,
+        db::{exchange_rate_sql::CreateExchangeRateParams, store::TransferTxParams},
+        fx::FX_POSITION_OWNER,
+        utils::*,
+    };
+    use axum::http::Method;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_create_account_handler() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+
+        let req = json_request(
+            Method::POST,
+            "/accounts",
+            Some(&token),
+            Some(json!({ "currency": "EUR" })),
+        );
+        let (status, _, body) = send(routes(state.clone()), req).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["owner"], json!(user.username));
+        assert_eq!(body["currency"], json!("EUR"));
+        assert_eq!(body["balance"], json!("0.00"));
+    }
+
+    #[tokio::test]
+    async fn test_list_accounts_handler() {
+        let state = mem_app_state();
+        let depositor = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let other = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let banker = random_store_user(&*state.store, Role::Banker)
+            .await
+            .unwrap();
+        random_store_account(&*state.store, &depositor.username, "USD", 0)
+            .await
+            .unwrap();
+        random_store_account(&*state.store, &other.username, "USD", 0)
+            .await
+            .unwrap();
+
+        // Depositors only ever see their own accounts, whatever they ask for.
+        let uri = format!("/accounts?page_id=1&page_size=5&owner={}", other.username);
+        let token = access_token(&state, &depositor);
+        let req = json_request(Method::GET, &uri, Some(&token), None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["owner"], json!(depositor.username));
+
+        let token = access_token(&state, &banker);
+        let req = json_request(Method::GET, &uri, Some(&token), None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body.as_array().unwrap().len(), 1);
+        assert_eq!(body[0]["owner"], json!(other.username));
+
+        let uri = format!("/accounts?page_id={}&page_size=10", i64::MAX / 2);
+        let req = json_request(Method::GET, &uri, Some(&token), None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("page_id"));
+    }
+
+    #[tokio::test]
+    async fn test_account_status_handlers() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let banker = random_store_user(&*state.store, Role::Banker)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &user.username, "USD", 10)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &user.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+        let banker_token = access_token(&state, &banker);
+        let post = |token: &str, uri: String, reason: &str| {
+            json_request(
+                Method::POST,
+                &uri,
+                Some(token),
+                Some(json!({ "reason": reason })),
+            )
+        };
+        let transfer = || {
+            json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from_account.id,
+                    "to_account_id": to_account.id,
+                    "amount": "0.05",
+                    "currency": "USD",
+                })),
+            )
+        };
+        let freeze = format!("/accounts/{}/freeze", from_account.id);
+        let unfreeze = format!("/accounts/{}/unfreeze", from_account.id);
+        let close = |id: i64| format!("/accounts/{id}/close");
+
+        let req = post(&token, freeze.clone(), "lost card");
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let req = post(&banker_token, freeze.clone(), " ");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("reason"));
+
+        let req = post(&banker_token, freeze.clone(), "lost card");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!("frozen"));
+        let (status, _, body) = send(routes(state.clone()), transfer()).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("account_frozen"));
+        let req = post(&banker_token, freeze.clone(), "lost card");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("invalid_status_transition"));
+
+        let req = post(&banker_token, unfreeze, "card found");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!("active"));
+        let (status, _, _) = send(routes(state.clone()), transfer()).await;
+        assert_eq!(status, StatusCode::OK);
+
+        // Accounts with a balance or history are kept; empty ones can close.
+        let req = post(&token, close(from_account.id), "moving banks");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("account_not_empty"));
+        assert_eq!(body["balance"], json!("0.05"));
+        let req = post(&token, close(to_account.id), "moving banks");
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let other = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let empty = random_store_account(&*state.store, &user.username, "USD", 0)
+            .await
+            .unwrap();
+        let req = post(
+            &access_token(&state, &other),
+            close(empty.id),
+            "moving banks",
+        );
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+        let req = post(&token, close(empty.id), "moving banks");
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!("closed"));
+
+        let req = json_request(
+            Method::POST,
+            "/transfers",
+            Some(&token),
+            Some(json!({
+                "from_account_id": from_account.id,
+                "to_account_id": empty.id,
+                "amount": "0.05",
+                "currency": "USD",
+            })),
+        );
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("account_closed"));
+        let req = post(
+            &banker_token,
+            format!("/accounts/{}/unfreeze", empty.id),
+            "oops",
+        );
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+
+        let uri = format!("/accounts/{}", empty.id);
+        let req = json_request(Method::DELETE, &uri, Some(&token), None);
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::METHOD_NOT_ALLOWED);
+    }
+
+    #[tokio::test]
+    async fn test_fx_position_status_cannot_change() {
+        let state = mem_app_state();
+        let banker = random_store_user(&*state.store, Role::Banker)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &banker.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &banker.username, "EUR", 0)
+            .await
+            .unwrap();
+        state
+            .store
+            .create_exchange_rate(CreateExchangeRateParams {
+                base_currency: from_account.currency,
+                quote_currency: to_account.currency,
+                rate: "0.9".parse().unwrap(),
+                spread_bps: 0,
+                effective_from: chrono::Utc::now(),
+            })
+            .await
+            .unwrap();
+        state
+            .store
+            .transfer_tx(TransferTxParams {
+                from_account_id: from_account.id,
+                to_account_id: to_account.id,
+                amount: Money::new(10, from_account.currency),
+                to_currency: Some(to_account.currency),
+                quote_id: None,
+            })
+            .await
+            .unwrap();
+        let positions = state
+            .store
+            .list_accounts(ListAccountsParams {
+                owner: Some(FX_POSITION_OWNER.to_string()),
+                limit: 10,
+                offset: 0,
+            })
+            .await
+            .unwrap();
+        assert_eq!(positions.len(), 2);
+
+        let token = access_token(&state, &banker);
+        for action in ["freeze", "close"] {
+            let req = json_request(
+                Method::POST,
+                &format!("/accounts/{}/{action}", positions[0].id),
+                Some(&token),
+                Some(json!({ "reason": "reconciliation" })),
+            );
+            let (status, _, body) = send(routes(state.clone()), req).await;
+            assert_eq!(status, StatusCode::FORBIDDEN);
+            assert_eq!(body["code"], json!("forbidden"));
+        }
+        let position = state.store.get_account(positions[0].id).await.unwrap();
+        assert_eq!(position.status, AccountStatus::Active);
+    }
+}
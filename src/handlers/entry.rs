@@ -0,0 +1,60 @@
+use super::{page_bounds, validate_id, validate_time_range};
+use crate::{
+    api::{auth::AuthPayload, authz::get_viewable_account},
+    db::{entry_sql::ListEntriesParams, store::Store},
+    models::Entry,
+    prelude::*,
+};
+use axum::{
+    extract::{Path, Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub async fn get_entry_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(id): Path<i64>,
+) -> ServerResult<Json<Entry>> {
+    validate_id("id", id)?;
+
+    let entry = store.get_entry(id).await?;
+    get_viewable_account(&*store, &auth, entry.account_id).await?;
+
+    Ok(Json(entry))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListEntriesRequest {
+    pub page_id: i64,
+    pub page_size: i64,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+pub async fn list_entries_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(account_id): Path<i64>,
+    Query(arg): Query<ListEntriesRequest>,
+) -> ServerResult<Json<Vec<Entry>>> {
+    validate_id("account_id", account_id)?;
+    let (limit, offset) = page_bounds(arg.page_id, arg.page_size)?;
+    validate_time_range(arg.start_time, arg.end_time)?;
+
+    get_viewable_account(&*store, &auth, account_id).await?;
+
+    let params = ListEntriesParams {
+        account_id,
+        start_time: arg.start_time,
+        end_time: arg.end_time,
+        limit,
+        offset,
+    };
+
+    let entries = store.list_entries(params).await?;
+
+    Ok(Json(entries))
+}
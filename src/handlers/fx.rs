@@ -0,0 +1,171 @@
+use crate::{
+    api::{
+        auth::AuthPayload,
+        json::Json,
+        policy::{Banker, RequireRole},
+        state::AppState,
+    },
+    currency::Currency,
+    db::{
+        exchange_rate_sql::CreateExchangeRateParams, fx_quote_sql::CreateFxQuoteParams,
+        store::Store,
+    },
+    decimal::Decimal,
+    models::{ExchangeRate, FxQuote},
+    prelude::*,
+};
+use axum::extract::{Path, State};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateExchangeRateRequest {
+    pub base_currency: Currency,
+    pub quote_currency: Currency,
+    /// Units of `quote_currency` per unit of `base_currency`, e.g. `"1.0857"`.
+    pub rate: Decimal,
+    #[serde(default)]
+    pub spread_bps: i32,
+    /// Defaults to now.
+    pub effective_from: Option<DateTime<Utc>>,
+}
+
+pub async fn create_exchange_rate_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    _banker: RequireRole<Banker>,
+    arg: Json<CreateExchangeRateRequest>,
+) -> ServerResult<Json<ExchangeRate>> {
+    let mut errors = vec![];
+    if arg.base_currency == arg.quote_currency {
+        errors.push(FieldError::new(
+            "quote_currency",
+            "must differ from base_currency",
+        ));
+    }
+    if !arg.rate.is_positive() {
+        errors.push(FieldError::new("rate", "must be positive"));
+    }
+    if !(0..=10_000).contains(&arg.spread_bps) {
+        errors.push(FieldError::new("spread_bps", "must be between 0 and 10000"));
+    }
+    if !errors.is_empty() {
+        return Err(ServerError::Validation(errors));
+    }
+
+    let params = CreateExchangeRateParams {
+        base_currency: arg.base_currency,
+        quote_currency: arg.quote_currency,
+        rate: arg.rate,
+        spread_bps: arg.spread_bps,
+        effective_from: arg.effective_from.unwrap_or_else(Utc::now),
+    };
+
+    let rate = store.create_exchange_rate(params).await?;
+
+    Ok(Json(rate))
+}
+
+/// The rate currently in effect from `base` to `quote`.
+pub async fn get_exchange_rate_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(_auth): AuthPayload,
+    Path((base_currency, quote_currency)): Path<(Currency, Currency)>,
+) -> ServerResult<Json<ExchangeRate>> {
+    let rate = store
+        .find_exchange_rate(base_currency, quote_currency, Utc::now())
+        .await?
+        .ok_or(sqlx::Error::RowNotFound)?;
+
+    Ok(Json(rate))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct CreateFxQuoteRequest {
+    pub base_currency: Currency,
+    pub quote_currency: Currency,
+}
+
+/// Locks the rate currently in effect for one transfer by the caller, which
+/// passes the quote's id as `quote_id` before it expires.
+pub async fn create_fx_quote_handler<S: Store>(
+    State(state): State<AppState<S>>,
+    AuthPayload(auth): AuthPayload,
+    arg: Json<CreateFxQuoteRequest>,
+) -> ServerResult<Json<FxQuote>> {
+    let now = Utc::now();
+    let rate = state
+        .store
+        .find_exchange_rate(arg.base_currency, arg.quote_currency, now)
+        .await?
+        .ok_or(ServerError::Ledger(LedgerError::ExchangeRateUnavailable {
+            from: arg.base_currency,
+            to: arg.quote_currency,
+        }))?;
+
+    let params = CreateFxQuoteParams {
+        id: Uuid::new_v4(),
+        owner: auth.username,
+        exchange_rate_id: rate.id,
+        expires_at: now + state.fx_quote_ttl,
+    };
+
+    let quote = state.store.create_fx_quote(params).await?;
+
+    Ok(Json(quote))
+}
+
+mod tests {
+    use super::*;
+    use crate::{api::router::routes, models::Role, utils::*};
+    use axum::http::{Method, StatusCode};
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_exchange_rate_handlers() {
+        let state = mem_app_state();
+        let banker = random_store_user(&*state.store, Role::Banker)
+            .await
+            .unwrap();
+        let depositor = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let create =
+            |token: &str, body| json_request(Method::POST, "/fx/rates", Some(token), Some(body));
+        let body = json!({
+            "base_currency": "EUR",
+            "quote_currency": "USD",
+            "rate": "1.0857",
+            "spread_bps": 150,
+        });
+
+        let token = access_token(&state, &depositor);
+        let (status, _, _) = send(routes(state.clone()), create(&token, body.clone())).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let token = access_token(&state, &banker);
+        let (status, _, created) = send(routes(state.clone()), create(&token, body)).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(created["rate"], json!("1.0857"));
+        assert_eq!(created["spread_bps"], json!(150));
+
+        let req = create(
+            &token,
+            json!({ "base_currency": "EUR", "quote_currency": "EUR", "rate": "0" }),
+        );
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"].as_array().unwrap().len(), 2);
+
+        let token = access_token(&state, &depositor);
+        let req = json_request(Method::GET, "/fx/rates/EUR/USD", Some(&token), None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body, created);
+
+        let req = json_request(Method::GET, "/fx/rates/USD/EUR", Some(&token), None);
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+}
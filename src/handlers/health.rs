@@ -0,0 +1,156 @@
+use crate::{
+    api::state::AppState,
+    db::{migrate::expected_version, store::Store},
+};
+use axum::{extract::State, http::StatusCode, Json};
+use serde::Serialize;
+
+#[derive(Debug, Serialize)]
+pub struct HealthResponse {
+    pub status: &'static str,
+}
+
+/// Liveness: the process is up and serving requests.
+pub async fn healthz_handler() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+#[derive(Debug, Serialize)]
+pub struct ReadinessResponse {
+    pub status: &'static str,
+    pub draining: bool,
+    pub database: CheckResult,
+    pub migrations: CheckResult,
+}
+
+#[derive(Debug, Serialize)]
+pub struct CheckResult {
+    pub ok: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+impl CheckResult {
+    fn from_result(result: Result<(), String>) -> Self {
+        match result {
+            Ok(()) => CheckResult {
+                ok: true,
+                error: None,
+            },
+            Err(error) => CheckResult {
+                ok: false,
+                error: Some(error),
+            },
+        }
+    }
+}
+
+/// Readiness: the database answers and is migrated to the version this build
+/// expects, and the server is not shutting down. Answers 503 otherwise so load
+/// balancers stop routing here.
+pub async fn readyz_handler<S: Store>(
+    State(state): State<AppState<S>>,
+) -> (StatusCode, Json<ReadinessResponse>) {
+    let draining = state.shutdown.is_draining();
+    let database = state.store.ping().await.map_err(|err| err.to_string());
+    let migrations = match database {
+        Ok(()) => check_migrations(&*state.store).await,
+        Err(_) => Err("database is unavailable".to_string()),
+    };
+
+    let ready = !draining && database.is_ok() && migrations.is_ok();
+    let (status_code, status) = match ready {
+        true => (StatusCode::OK, "ready"),
+        false => (StatusCode::SERVICE_UNAVAILABLE, "not_ready"),
+    };
+
+    (
+        status_code,
+        Json(ReadinessResponse {
+            status,
+            draining,
+            database: CheckResult::from_result(database),
+            migrations: CheckResult::from_result(migrations),
+        }),
+    )
+}
+
+async fn check_migrations<S: Store>(store: &S) -> Result<(), String> {
+    let current = store
+        .schema_version()
+        .await
+        .map_err(|err| err.to_string())?;
+    let expected = expected_version();
+    if current != expected {
+        return Err(format!(
+            "database is at migration {}, expected {}",
+            display_version(current),
+            display_version(expected)
+        ));
+    }
+    Ok(())
+}
+
+fn display_version(version: Option<i64>) -> String {
+    version.map_or_else(|| "none".to_string(), |version| version.to_string())
+}
+
+#[derive(Debug, Serialize)]
+pub struct VersionResponse {
+    pub name: &'static str,
+    pub version: &'static str,
+    pub git_commit: &'static str,
+    pub build_profile: &'static str,
+    pub migration_version: Option<i64>,
+}
+
+pub async fn version_handler() -> Json<VersionResponse> {
+    Json(VersionResponse {
+        name: env!("CARGO_PKG_NAME"),
+        version: env!("CARGO_PKG_VERSION"),
+        git_commit: env!("GIT_COMMIT"),
+        build_profile: env!("BUILD_PROFILE"),
+        migration_version: expected_version(),
+    })
+}
+
+mod tests {
+    use super::*;
+    use crate::{api::router::routes, utils::*};
+    use axum::http::Method;
+    use serde_json::json;
+
+    #[tokio::test]
+    async fn test_readyz_handler() {
+        let state = mem_app_state();
+
+        let req = json_request(Method::GET, "/readyz", None, None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["status"], json!("ready"));
+        assert_eq!(body["migrations"]["ok"], json!(true));
+
+        state.shutdown.begin_drain();
+
+        let req = json_request(Method::GET, "/readyz", None, None);
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::SERVICE_UNAVAILABLE);
+        assert_eq!(body["status"], json!("not_ready"));
+        assert_eq!(body["draining"], json!(true));
+
+        // Liveness is unaffected by draining.
+        let req = json_request(Method::GET, "/healthz", None, None);
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+    }
+
+    #[tokio::test]
+    async fn test_version_handler() {
+        let req = json_request(Method::GET, "/version", None, None);
+        let (status, _, body) = send(routes(mem_app_state()), req).await;
+
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["version"], json!(env!("CARGO_PKG_VERSION")));
+        assert_eq!(body["migration_version"], json!(expected_version()));
+    }
+}
@@ -0,0 +1,48 @@
+use super::validate_time_range;
+use crate::{
+    api::policy::{Banker, RequireRole},
+    db::{
+        report_sql::{BalanceReport, TransferVolumeReport, TransferVolumeReportParams},
+        store::Store,
+    },
+    prelude::*,
+};
+use axum::{
+    extract::{Query, State},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+
+pub async fn balance_report_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    _banker: RequireRole<Banker>,
+) -> ServerResult<Json<Vec<BalanceReport>>> {
+    let report = store.balance_report().await?;
+
+    Ok(Json(report))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TransferVolumeReportRequest {
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+pub async fn transfer_volume_report_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    _banker: RequireRole<Banker>,
+    Query(arg): Query<TransferVolumeReportRequest>,
+) -> ServerResult<Json<Vec<TransferVolumeReport>>> {
+    validate_time_range(arg.start_time, arg.end_time)?;
+
+    let params = TransferVolumeReportParams {
+        start_time: arg.start_time,
+        end_time: arg.end_time,
+    };
+
+    let report = store.transfer_volume_report(params).await?;
+
+    Ok(Json(report))
+}
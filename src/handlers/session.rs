@@ -0,0 +1,96 @@
+use crate::{api::auth::AuthPayload, db::store::Store, models::Session, prelude::*};
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequestParts, Path, State},
+    http::{header::USER_AGENT, request::Parts},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use std::{net::SocketAddr, sync::Arc};
+use uuid::Uuid;
+
+/// Device information recorded with a session.
+#[derive(Debug, Clone)]
+pub struct ClientMetadata {
+    pub user_agent: String,
+    pub client_ip: String,
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientMetadata
+where
+    S: Send + Sync,
+{
+    type Rejection = ServerError;
+
+    async fn from_request_parts(parts: &mut Parts, _state: &S) -> ServerResult<Self> {
+        let user_agent = parts
+            .headers
+            .get(USER_AGENT)
+            .and_then(|value| value.to_str().ok())
+            .unwrap_or_default()
+            .to_string();
+
+        let client_ip = parts
+            .extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ConnectInfo(addr)| addr.ip().to_string())
+            .unwrap_or_default();
+
+        Ok(ClientMetadata {
+            user_agent,
+            client_ip,
+        })
+    }
+}
+
+/// A `Session` without its refresh token.
+#[derive(Debug, Serialize)]
+pub struct SessionResponse {
+    pub id: Uuid,
+    pub username: String,
+    pub user_agent: String,
+    pub client_ip: String,
+    pub is_blocked: bool,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<Session> for SessionResponse {
+    fn from(session: Session) -> Self {
+        Self {
+            id: session.id,
+            username: session.username,
+            user_agent: session.user_agent,
+            client_ip: session.client_ip,
+            is_blocked: session.is_blocked,
+            expires_at: session.expires_at,
+            created_at: session.created_at,
+        }
+    }
+}
+
+pub async fn list_sessions_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+) -> ServerResult<Json<Vec<SessionResponse>>> {
+    let sessions = store.list_active_sessions(&auth.username).await?;
+
+    Ok(Json(sessions.into_iter().map(Into::into).collect()))
+}
+
+pub async fn revoke_session_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(id): Path<Uuid>,
+) -> ServerResult<Json<SessionResponse>> {
+    let session = store.get_session(id).await?;
+    if session.username != auth.username {
+        return Err(ServerError::ClientError(ClientError::Forbidden));
+    }
+
+    let session = store.block_session(id).await?;
+
+    Ok(Json(session.into()))
+}
@@ -0,0 +1,65 @@
+use crate::{
+    api::{json::Json, state::AppState},
+    auth::token::TokenType,
+    db::store::Store,
+    prelude::*,
+};
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Deserialize)]
+pub struct RenewAccessTokenRequest {
+    pub refresh_token: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct RenewAccessTokenResponse {
+    pub access_token: String,
+    pub access_token_expires_at: DateTime<Utc>,
+}
+
+pub async fn renew_access_token_handler<S: Store>(
+    State(state): State<AppState<S>>,
+    Json(arg): Json<RenewAccessTokenRequest>,
+) -> ServerResult<Json<RenewAccessTokenResponse>> {
+    let refresh_payload = state
+        .token_maker
+        .verify_token(&arg.refresh_token, TokenType::Refresh)
+        .map_err(|err| ServerError::Unauthorized(err.as_str()))?;
+
+    let session = match state.store.get_session(refresh_payload.id).await {
+        Ok(session) => session,
+        Err(err) => match ServerError::from(err) {
+            ServerError::Database(DatabaseError::RowNotFound) => {
+                return Err(ServerError::Unauthorized("session not found"))
+            }
+            err => return Err(err),
+        },
+    };
+
+    if session.is_blocked {
+        return Err(ServerError::Unauthorized("session is blocked"));
+    }
+    if session.username != refresh_payload.username || session.refresh_token != arg.refresh_token {
+        return Err(ServerError::Unauthorized("mismatched session token"));
+    }
+    if session.expires_at <= Utc::now() {
+        return Err(ServerError::Unauthorized("session has expired"));
+    }
+
+    // Roles may have changed since login, so the current one is read back.
+    let user = state.store.get_user(&session.username).await?;
+
+    let (access_token, access_payload) = state.token_maker.create_token(
+        &user.username,
+        user.role,
+        TokenType::Access,
+        state.access_token_duration,
+    )?;
+
+    Ok(Json(RenewAccessTokenResponse {
+        access_token,
+        access_token_expires_at: access_payload.expired_at,
+    }))
+}
@@ -0,0 +1,639 @@
+use super::{page_bounds, validate_id, validate_time_range};
+use crate::{
+    api::{
+        auth::AuthPayload,
+        authz::{
+            authorize_account_owner, authorize_fx_quote_owner, authorize_transfer_view,
+            get_viewable_account,
+        },
+        json::Json,
+        policy::{Banker, RequireRole},
+    },
+    currency::Currency,
+    db::{
+        store::{Store, TransferTxParams, TransferTxResult},
+        transfer_sql::{ListTransfersParams, TransferDirection},
+    },
+    models::{Account, Transfer},
+    money::Money,
+    prelude::*,
+};
+use axum::extract::{Path, Query, State};
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
+use std::sync::Arc;
+use uuid::Uuid;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateTransferRequest {
+    pub from_account_id: i64,
+    pub to_account_id: i64,
+    /// A decimal amount in `currency`, e.g. `"12.05"`.
+    pub amount: String,
+    pub currency: Currency,
+    /// Credits the receiver in this currency, converting `amount` at the
+    /// current exchange rate. Defaults to `currency`.
+    pub to_currency: Option<Currency>,
+    /// Converts at the rate locked by this quote from `POST /fx/quotes`
+    /// instead. `to_currency` defaults to the quote's currency.
+    pub quote_id: Option<Uuid>,
+}
+
+pub async fn create_transfer_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    arg: Json<CreateTransferRequest>,
+) -> ServerResult<Json<TransferTxResult>> {
+    validate_id("from_account_id", arg.from_account_id)?;
+    validate_id("to_account_id", arg.to_account_id)?;
+    let mut errors = vec![];
+    if arg.from_account_id == arg.to_account_id {
+        errors.push(FieldError::new(
+            "to_account_id",
+            "must differ from from_account_id",
+        ));
+    }
+    let amount = Money::parse(&arg.amount, arg.currency);
+    match &amount {
+        Ok(amount) if !amount.is_positive() => {
+            errors.push(FieldError::new("amount", "must be positive"));
+        }
+        Err(err) => errors.push(FieldError::new("amount", err.to_string())),
+        Ok(_) => {}
+    }
+    if !errors.is_empty() {
+        return Err(ServerError::Validation(errors));
+    }
+    let amount = amount?;
+
+    let from_account =
+        valid_account(&*store, arg.from_account_id, "currency", arg.currency).await?;
+    authorize_account_owner(&auth, &from_account)?;
+    let quote = match arg.quote_id {
+        Some(quote_id) => {
+            let quote = store.get_fx_quote(quote_id).await?;
+            authorize_fx_quote_owner(&auth, &quote)?;
+            Some(quote)
+        }
+        None => None,
+    };
+    let to_currency = arg
+        .to_currency
+        .or(quote.as_ref().map(|quote| quote.quote_currency));
+    let (field, to_currency) = match to_currency {
+        Some(to_currency) => ("to_currency", to_currency),
+        None => ("currency", arg.currency),
+    };
+    if let Some(quote) = &quote {
+        if (quote.base_currency, quote.quote_currency) != (arg.currency, to_currency) {
+            return Err(ServerError::validation(
+                "quote_id",
+                format!(
+                    "quote {} converts {} to {}, not {} to {to_currency}",
+                    quote.id, quote.base_currency, quote.quote_currency, arg.currency
+                ),
+            ));
+        }
+    }
+    valid_account(&*store, arg.to_account_id, field, to_currency).await?;
+
+    let params = TransferTxParams {
+        from_account_id: arg.from_account_id,
+        to_account_id: arg.to_account_id,
+        amount,
+        to_currency: Some(to_currency),
+        quote_id: arg.quote_id,
+    };
+
+    let result = store.transfer_tx(params).await?;
+
+    Ok(Json(result))
+}
+
+pub async fn get_transfer_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(id): Path<i64>,
+) -> ServerResult<Json<Transfer>> {
+    validate_id("id", id)?;
+
+    let transfer = store.get_transfer(id).await?;
+    authorize_transfer_view(&*store, &auth, &transfer).await?;
+
+    Ok(Json(transfer))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListTransfersRequest {
+    pub page_id: i64,
+    pub page_size: i64,
+    pub direction: Option<TransferDirection>,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+pub async fn list_transfers_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    AuthPayload(auth): AuthPayload,
+    Path(account_id): Path<i64>,
+    Query(arg): Query<ListTransfersRequest>,
+) -> ServerResult<Json<Vec<Transfer>>> {
+    validate_id("account_id", account_id)?;
+    let (limit, offset) = page_bounds(arg.page_id, arg.page_size)?;
+    validate_time_range(arg.start_time, arg.end_time)?;
+
+    get_viewable_account(&*store, &auth, account_id).await?;
+
+    let params = ListTransfersParams {
+        account_id: Some(account_id),
+        direction: arg.direction,
+        start_time: arg.start_time,
+        end_time: arg.end_time,
+        limit,
+        offset,
+    };
+
+    let transfers = store.list_transfers(params).await?;
+
+    Ok(Json(transfers))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ListAllTransfersRequest {
+    pub page_id: i64,
+    pub page_size: i64,
+    pub start_time: Option<DateTime<Utc>>,
+    pub end_time: Option<DateTime<Utc>>,
+}
+
+pub async fn list_all_transfers_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    _banker: RequireRole<Banker>,
+    Query(arg): Query<ListAllTransfersRequest>,
+) -> ServerResult<Json<Vec<Transfer>>> {
+    let (limit, offset) = page_bounds(arg.page_id, arg.page_size)?;
+    validate_time_range(arg.start_time, arg.end_time)?;
+
+    let params = ListTransfersParams {
+        account_id: None,
+        direction: None,
+        start_time: arg.start_time,
+        end_time: arg.end_time,
+        limit,
+        offset,
+    };
+
+    let transfers = store.list_transfers(params).await?;
+
+    Ok(Json(transfers))
+}
+
+/// Checks that the account exists and holds funds in the requested currency.
+async fn valid_account<S: Store>(
+    store: &S,
+    id: i64,
+    field: &str,
+    currency: Currency,
+) -> ServerResult<Account> {
+    let account = store.get_account(id).await?;
+
+    if account.currency != currency {
+        return Err(ServerError::validation(
+            field,
+            format!("account {id} holds {}, not {currency}", account.currency),
+        ));
+    }
+
+    Ok(account)
+}
+
+mod tests {
+    use super::*;
+    use crate::{
+        api::{router::routes, state::AppState},
+        db::{exchange_rate_sql::CreateExchangeRateParams, mem_store::MemStore},
+        models::Role,
+        utils::*,
+    };
+    use axum::http::{Method, StatusCode};
+    use serde_json::{json, Value};
+
+    #[tokio::test]
+    async fn test_create_transfer_handler() {
+        let state = mem_app_state();
+        let sender = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let receiver = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &sender.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &receiver.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &sender);
+
+        let transfer = |amount: &str| {
+            json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from_account.id,
+                    "to_account_id": to_account.id,
+                    "amount": amount,
+                    "currency": "USD",
+                })),
+            )
+        };
+
+        let (status, _, body) = send(routes(state.clone()), transfer("0.60")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["from_account"]["balance"], json!("0.40"));
+        assert_eq!(body["to_account"]["balance"], json!("0.60"));
+        assert_eq!(body["from_entry"]["amount"], json!("-0.60"));
+
+        let (status, _, body) = send(routes(state.clone()), transfer("0.60")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], json!("insufficient_funds"));
+        assert_eq!(body["available_balance"], json!("0.40"));
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_returns_result() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &user.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &user.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+        let req = json_request(
+            Method::POST,
+            "/transfers",
+            Some(&token),
+            Some(json!({
+                "from_account_id": from_account.id,
+                "to_account_id": to_account.id,
+                "amount": "0.25",
+                "currency": "USD",
+            })),
+        );
+
+        let (status, _, body) = send(routes(state.clone()), req).await;
+
+        assert_eq!(status, StatusCode::OK);
+        let transfer_id = body["transfer"]["id"].clone();
+        assert_eq!(body["transfer"]["from_account_id"], json!(from_account.id));
+        assert_eq!(body["transfer"]["to_account_id"], json!(to_account.id));
+        assert_eq!(body["transfer"]["amount"], json!("0.25"));
+        assert_eq!(body["from_entry"]["account_id"], json!(from_account.id));
+        assert_eq!(body["from_entry"]["amount"], json!("-0.25"));
+        assert_eq!(body["from_entry"]["transfer_id"], transfer_id);
+        assert_eq!(body["to_entry"]["account_id"], json!(to_account.id));
+        assert_eq!(body["to_entry"]["amount"], json!("0.25"));
+        assert_eq!(body["to_entry"]["transfer_id"], transfer_id);
+        assert_eq!(body["from_account"]["id"], json!(from_account.id));
+        assert_eq!(body["from_account"]["balance"], json!("0.75"));
+        assert_eq!(body["to_account"]["id"], json!(to_account.id));
+        assert_eq!(body["to_account"]["balance"], json!("0.25"));
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_validates_request() {
+        let state = mem_app_state();
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &user.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &user.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &user);
+        let transfer = |from: i64, to: i64, amount: &str| {
+            json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from,
+                    "to_account_id": to,
+                    "amount": amount,
+                    "currency": "USD",
+                })),
+            )
+        };
+
+        for amount in ["0", "-0.10"] {
+            let (status, _, body) = send(
+                routes(state.clone()),
+                transfer(from_account.id, to_account.id, amount),
+            )
+            .await;
+            assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+            assert_eq!(body["errors"][0]["field"], json!("amount"));
+        }
+
+        let (status, _, body) = send(
+            routes(state.clone()),
+            transfer(from_account.id, from_account.id, "0.10"),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("to_account_id"));
+
+        let (status, _, body) =
+            send(routes(state.clone()), transfer(999, to_account.id, "0.10")).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], json!("not_found"));
+
+        // Nothing was booked by the rejected requests.
+        let from_account = state.store.get_account(from_account.id).await.unwrap();
+        assert_eq!(from_account.balance.amount(), "1.00");
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_converts_currency() {
+        let state = mem_app_state();
+        let sender = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &sender.username, "EUR", 1000)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &sender.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &sender);
+        let transfer = |to_currency: &str| {
+            json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from_account.id,
+                    "to_account_id": to_account.id,
+                    "amount": "10.00",
+                    "currency": "EUR",
+                    "to_currency": to_currency,
+                })),
+            )
+        };
+
+        let (status, _, body) = send(routes(state.clone()), transfer("USD")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], json!("exchange_rate_unavailable"));
+
+        let (status, _, body) = send(routes(state.clone()), transfer("GBP")).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("to_currency"));
+
+        state
+            .store
+            .create_exchange_rate(CreateExchangeRateParams {
+                base_currency: from_account.currency,
+                quote_currency: to_account.currency,
+                rate: "1.0857".parse().unwrap(),
+                spread_bps: 150,
+                effective_from: Utc::now(),
+            })
+            .await
+            .unwrap();
+        let (status, _, body) = send(routes(state.clone()), transfer("USD")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["transfer"]["amount"], json!("10.00"));
+        assert_eq!(body["transfer"]["to_amount"], json!("10.69"));
+        assert_eq!(body["transfer"]["fx_rate"], json!("1.0857"));
+        assert_eq!(body["transfer"]["fx_spread"], json!("0.16"));
+        assert_eq!(body["transfer"]["fx_residue"], json!("0.7"));
+        assert_eq!(body["from_account"]["balance"], json!("0.00"));
+        assert_eq!(body["to_account"]["balance"], json!("10.69"));
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_with_quote() {
+        let state = mem_app_state();
+        let sender = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let other = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &sender.username, "EUR", 5000)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &other.username, "USD", 0)
+            .await
+            .unwrap();
+        let create_rate = |rate: &str| {
+            state.store.create_exchange_rate(CreateExchangeRateParams {
+                base_currency: from_account.currency,
+                quote_currency: to_account.currency,
+                rate: rate.parse().unwrap(),
+                spread_bps: 0,
+                effective_from: Utc::now(),
+            })
+        };
+        let token = access_token(&state, &sender);
+        let quote = |state: &AppState<MemStore>, token: &str| {
+            let req = json_request(
+                Method::POST,
+                "/fx/quotes",
+                Some(token),
+                Some(json!({ "base_currency": "EUR", "quote_currency": "USD" })),
+            );
+            send(routes(state.clone()), req)
+        };
+        let transfer = |quote_id: &Value| {
+            json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from_account.id,
+                    "to_account_id": to_account.id,
+                    "amount": "10.00",
+                    "currency": "EUR",
+                    "quote_id": quote_id,
+                })),
+            )
+        };
+
+        let (status, _, body) = quote(&state, &token).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], json!("exchange_rate_unavailable"));
+
+        create_rate("1.0857").await.unwrap();
+        let (status, _, quoted) = quote(&state, &token).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(quoted["rate"], json!("1.0857"));
+        assert!(quoted["expires_at"].is_string());
+        create_rate("1.2").await.unwrap();
+
+        // Only the caller can use their quote.
+        let other_token = access_token(&state, &other);
+        let (_, _, others) = quote(&state, &other_token).await;
+        let (status, _, _) = send(routes(state.clone()), transfer(&others["id"])).await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        // The quoted rate applies, not the newer one.
+        let (status, _, body) = send(routes(state.clone()), transfer(&quoted["id"])).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(body["transfer"]["fx_rate"], json!("1.0857"));
+        assert_eq!(body["transfer"]["to_amount"], json!("10.85"));
+
+        let (status, _, body) = send(routes(state.clone()), transfer(&quoted["id"])).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("fx_quote_used"));
+
+        let expiring = AppState {
+            fx_quote_ttl: chrono::Duration::zero(),
+            ..state.clone()
+        };
+        let (_, _, expired) = quote(&expiring, &token).await;
+        let (status, _, body) = send(routes(state.clone()), transfer(&expired["id"])).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["code"], json!("fx_quote_expired"));
+
+        let (status, _, body) = send(routes(state.clone()), transfer(&json!(Uuid::new_v4()))).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+        assert_eq!(body["code"], json!("not_found"));
+
+        let (_, _, quoted) = quote(&state, &token).await;
+        let req = json_request(
+            Method::POST,
+            "/transfers",
+            Some(&token),
+            Some(json!({
+                "from_account_id": from_account.id,
+                "to_account_id": to_account.id,
+                "amount": "10.00",
+                "currency": "EUR",
+                "to_currency": "GBP",
+                "quote_id": quoted["id"],
+            })),
+        );
+        let (status, _, body) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("quote_id"));
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_rejects_invalid_requests() {
+        let state = mem_app_state();
+        let owner = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let other = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let usd = random_store_account(&*state.store, &owner.username, "USD", 100)
+            .await
+            .unwrap();
+        let eur = random_store_account(&*state.store, &other.username, "EUR", 100)
+            .await
+            .unwrap();
+        let other_usd = random_store_account(&*state.store, &other.username, "USD", 100)
+            .await
+            .unwrap();
+
+        let transfer = |token: Option<&str>, from: i64, to: i64| {
+            json_request(
+                Method::POST,
+                "/transfers",
+                token,
+                Some(json!({
+                    "from_account_id": from,
+                    "to_account_id": to,
+                    "amount": "0.10",
+                    "currency": "USD",
+                })),
+            )
+        };
+        let token = access_token(&state, &owner);
+
+        let (status, _, _) =
+            send(routes(state.clone()), transfer(None, usd.id, other_usd.id)).await;
+        assert_eq!(status, StatusCode::UNAUTHORIZED);
+
+        let (status, _, body) = send(
+            routes(state.clone()),
+            transfer(Some(&token), usd.id, eur.id),
+        )
+        .await;
+        assert_eq!(status, StatusCode::UNPROCESSABLE_ENTITY);
+        assert_eq!(body["errors"][0]["field"], json!("currency"));
+
+        let (status, _, _) = send(
+            routes(state.clone()),
+            transfer(Some(&token), other_usd.id, usd.id),
+        )
+        .await;
+        assert_eq!(status, StatusCode::FORBIDDEN);
+
+        let (status, _, _) = send(routes(state.clone()), transfer(Some(&token), usd.id, 999)).await;
+        assert_eq!(status, StatusCode::NOT_FOUND);
+    }
+
+    #[tokio::test]
+    async fn test_create_transfer_handler_idempotency_key() {
+        let state = mem_app_state();
+        let sender = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &sender.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &sender.username, "USD", 0)
+            .await
+            .unwrap();
+        let token = access_token(&state, &sender);
+
+        let transfer = |amount: &str| {
+            let mut req = json_request(
+                Method::POST,
+                "/transfers",
+                Some(&token),
+                Some(json!({
+                    "from_account_id": from_account.id,
+                    "to_account_id": to_account.id,
+                    "amount": amount,
+                    "currency": "USD",
+                })),
+            );
+            req.headers_mut()
+                .insert("idempotency-key", "retry-1".parse().unwrap());
+            req
+        };
+
+        let (status, _, first) = send(routes(state.clone()), transfer("0.10")).await;
+        assert_eq!(status, StatusCode::OK);
+
+        let (status, headers, retry) = send(routes(state.clone()), transfer("0.10")).await;
+        assert_eq!(status, StatusCode::OK);
+        assert_eq!(headers["idempotent-replayed"], "true");
+        assert_eq!(retry, first);
+        assert_eq!(
+            state
+                .store
+                .get_account(from_account.id)
+                .await
+                .unwrap()
+                .balance
+                .amount(),
+            "0.90"
+        );
+
+        let (status, _, body) = send(routes(state.clone()), transfer("0.20")).await;
+        assert_eq!(status, StatusCode::CONFLICT);
+        assert_eq!(body["code"], json!("idempotency_key_reused"));
+    }
+}
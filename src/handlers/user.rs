@@ -0,0 +1,189 @@
+use super::session::ClientMetadata;
+use crate::{
+    api::{json::Json, state::AppState},
+    auth::{
+        password::{hash_password, verify_password},
+        token::TokenType,
+    },
+    db::{session_sql::CreateSessionParams, store::Store, user_sql::CreateUserParams},
+    models::{Role, User},
+    prelude::*,
+};
+use axum::extract::State;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use uuid::Uuid;
+
+const MIN_PASSWORD_LENGTH: usize = 6;
+const MAX_USERNAME_LENGTH: usize = 32;
+
+#[derive(Debug, Deserialize)]
+pub struct CreateUserRequest {
+    pub username: String,
+    pub password: String,
+    pub full_name: String,
+    pub email: String,
+}
+
+/// A `User` without its password hash.
+#[derive(Debug, Serialize)]
+pub struct UserResponse {
+    pub username: String,
+    pub role: Role,
+    pub full_name: String,
+    pub email: String,
+    pub password_changed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+impl From<User> for UserResponse {
+    fn from(user: User) -> Self {
+        Self {
+            username: user.username,
+            role: user.role,
+            full_name: user.full_name,
+            email: user.email,
+            password_changed_at: user.password_changed_at,
+            created_at: user.created_at,
+        }
+    }
+}
+
+pub async fn create_user_handler<S: Store>(
+    State(store): State<Arc<S>>,
+    Json(arg): Json<CreateUserRequest>,
+) -> ServerResult<Json<UserResponse>> {
+    let mut errors = vec![];
+    if arg.username.len() < 3
+        || arg.username.len() > MAX_USERNAME_LENGTH
+        || !arg
+            .username
+            .chars()
+            .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_')
+    {
+        errors.push(FieldError::new(
+            "username",
+            format!("must be 3 to {MAX_USERNAME_LENGTH} lowercase letters, digits or underscores"),
+        ));
+    }
+    if arg.password.len() < MIN_PASSWORD_LENGTH {
+        errors.push(FieldError::new(
+            "password",
+            format!("must be at least {MIN_PASSWORD_LENGTH} characters"),
+        ));
+    }
+    if arg.full_name.trim().is_empty() {
+        errors.push(FieldError::new("full_name", "must not be empty"));
+    }
+    if !valid_email(&arg.email) {
+        errors.push(FieldError::new("email", "must be a valid email address"));
+    }
+    if !errors.is_empty() {
+        return Err(ServerError::Validation(errors));
+    }
+
+    let password = arg.password;
+    let hashed_password = tokio::task::spawn_blocking(move || hash_password(&password))
+        .await
+        .map_err(|err| ServerError::Internal(err.to_string()))??;
+
+    let params = CreateUserParams {
+        username: arg.username,
+        hashed_password,
+        full_name: arg.full_name,
+        email: arg.email,
+        role: Role::Depositor,
+    };
+
+    let user = store.create_user(params).await?;
+
+    Ok(Json(user.into()))
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LoginUserRequest {
+    pub username: String,
+    pub password: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LoginUserResponse {
+    pub session_id: Uuid,
+    pub access_token: String,
+    pub access_token_expires_at: DateTime<Utc>,
+    pub refresh_token: String,
+    pub refresh_token_expires_at: DateTime<Utc>,
+    pub user: UserResponse,
+}
+
+pub async fn login_user_handler<S: Store>(
+    State(state): State<AppState<S>>,
+    client: ClientMetadata,
+    Json(arg): Json<LoginUserRequest>,
+) -> ServerResult<Json<LoginUserResponse>> {
+    const INVALID_CREDENTIALS: ServerError =
+        ServerError::Unauthorized("invalid username or password");
+
+    let user = match state.store.get_user(&arg.username).await {
+        Ok(user) => user,
+        Err(err) => match ServerError::from(err) {
+            ServerError::Database(DatabaseError::RowNotFound) => return Err(INVALID_CREDENTIALS),
+            err => return Err(err),
+        },
+    };
+
+    let password = arg.password;
+    let hashed_password = user.hashed_password.clone();
+    let valid = tokio::task::spawn_blocking(move || verify_password(&password, &hashed_password))
+        .await
+        .map_err(|err| ServerError::Internal(err.to_string()))?;
+    if !valid {
+        return Err(INVALID_CREDENTIALS);
+    }
+
+    let (access_token, access_payload) = state.token_maker.create_token(
+        &user.username,
+        user.role,
+        TokenType::Access,
+        state.access_token_duration,
+    )?;
+
+    let (refresh_token, refresh_payload) = state.token_maker.create_token(
+        &user.username,
+        user.role,
+        TokenType::Refresh,
+        state.refresh_token_duration,
+    )?;
+
+    // The refresh token id doubles as the session id.
+    let session = state
+        .store
+        .create_session(CreateSessionParams {
+            id: refresh_payload.id,
+            username: user.username.clone(),
+            refresh_token: refresh_token.clone(),
+            user_agent: client.user_agent,
+            client_ip: client.client_ip,
+            expires_at: refresh_payload.expired_at,
+        })
+        .await?;
+
+    Ok(Json(LoginUserResponse {
+        session_id: session.id,
+        access_token,
+        access_token_expires_at: access_payload.expired_at,
+        refresh_token,
+        refresh_token_expires_at: refresh_payload.expired_at,
+        user: user.into(),
+    }))
+}
+
+fn valid_email(email: &str) -> bool {
+    match email.split_once('@') {
+        Some((local, domain)) => {
+            !local.is_empty() && domain.contains('.') && !domain.starts_with('.')
+        }
+        None => false,
+    }
+}
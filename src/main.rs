@@ -1,25 +1,197 @@
 #![allow(unused)]
 
 mod api;
+mod auth;
+mod cli;
+mod config;
+mod currency;
 mod db;
+mod decimal;
 mod error;
+mod fx;
 mod handlers;
+mod metrics;
 mod models;
+mod money;
 mod prelude;
+mod telemetry;
 mod utils;
 
 use api::router;
+use std::sync::Arc;
 
-use crate::api::server::Server;
+use crate::{
+    api::{server::Server, shutdown::Shutdown, state::AppState},
+    auth::token::TokenMaker,
+    cli::{Command, MigrateCommand},
+    config::{Config, LogConfig, LogFormat},
+    db::{
+        migrate::{migrate_down, migrate_up, migration_status},
+        store::{PgStore, Store},
+        TxOptions,
+    },
+    prelude::*,
+};
+use opentelemetry_sdk::trace::TracerProvider;
+use sqlx::PgPool;
+use tracing_subscriber::{layer::SubscriberExt, util::SubscriberInitExt, EnvFilter, Layer};
 
 #[tokio::main]
 async fn main() {
     dotenv::dotenv().ok();
-    let db = db::create_connection_pool(Some(10))
+    let cli = cli::parse(std::env::args().skip(1)).unwrap_or_else(|err| {
+        eprintln!("{err}\n\n{}", cli::USAGE);
+        std::process::exit(2);
+    });
+
+    match cli.command {
+        Command::Migrate(command) => {
+            // Migrations only need the database settings, not the token key
+            // and the rest of the server configuration.
+            let database =
+                Config::load_database(cli.config.as_deref()).unwrap_or_else(|err| exit_with(err));
+            let db = db::connect(&database).await.unwrap_or_else(|err| {
+                exit_with(format!("Failed to connect to the database: {err}"))
+            });
+            migrate(&db, command)
+                .await
+                .unwrap_or_else(|err| exit_with(err));
+        }
+        Command::Serve { migrate } => {
+            let config = Config::load(cli.config.as_deref()).unwrap_or_else(|err| exit_with(err));
+            let tracer_provider = init_tracing(&config);
+
+            let db = db::connect(&config.database).await.unwrap_or_else(|err| {
+                exit_with(format!("Failed to connect to the database: {err}"))
+            });
+            if migrate || config.database.run_migrations {
+                migrate_up(&db)
+                    .await
+                    .unwrap_or_else(|err| exit_with(format!("Failed to run migrations: {err}")));
+            }
+            serve(db, config).await;
+
+            if let Some(tracer_provider) = tracer_provider {
+                // Flushing blocks until the batch exporter, a runtime task, is done.
+                let flushed = tokio::task::spawn_blocking(move || tracer_provider.shutdown()).await;
+                if let Ok(Err(err)) = flushed {
+                    eprintln!("Failed to flush traces: {err}");
+                }
+            }
+        }
+    }
+}
+
+/// Reports an error the operator has to fix, without a panic and backtrace.
+fn exit_with(err: impl std::fmt::Display) -> ! {
+    eprintln!("{err}");
+    std::process::exit(1);
+}
+
+/// Logs to stdout and, when a collector is configured, exports traces over
+/// OTLP. The returned provider must be shut down to flush pending spans.
+fn init_tracing(config: &Config) -> Option<TracerProvider> {
+    // Validated with the rest of the config.
+    let filter = EnvFilter::try_new(&config.log.filter).expect("valid log filter");
+    let fmt = match config.log.format {
+        LogFormat::Text => tracing_subscriber::fmt::layer().boxed(),
+        LogFormat::Json => tracing_subscriber::fmt::layer()
+            .json()
+            .with_current_span(true)
+            .with_span_list(false)
+            .boxed(),
+    };
+
+    let tracer_provider =
+        telemetry::tracer_provider(&config.telemetry).expect("Failed to create OTLP exporter");
+    tracing_subscriber::registry()
+        .with(fmt.with_filter(filter))
+        .with(tracer_provider.as_ref().map(telemetry::layer))
+        .init();
+    tracer_provider
+}
+
+async fn migrate(db: &PgPool, command: MigrateCommand) -> Result<()> {
+    match command {
+        MigrateCommand::Up => {
+            migrate_up(db)
+                .await
+                .map_err(|err| format!("Failed to run migrations: {err}"))?;
+            println!("Database is up to date");
+        }
+        MigrateCommand::Down => match migrate_down(db)
+            .await
+            .map_err(|err| format!("Failed to revert migration: {err}"))?
+        {
+            Some(version) => println!("Reverted migration {version}"),
+            None => println!("No migrations to revert"),
+        },
+        MigrateCommand::Status => {
+            for migration in migration_status(db)
+                .await
+                .map_err(|err| format!("Failed to read migration status: {err}"))?
+            {
+                let state = match (migration.applied, migration.checksum_mismatch) {
+                    (true, false) => "applied",
+                    (true, true) => "applied (checksum mismatch)",
+                    (false, _) => "pending",
+                };
+                println!(
+                    "{:05} {:<40} {state}",
+                    migration.version, migration.description
+                );
+            }
+        }
+    }
+    Ok(())
+}
+
+async fn serve(db: PgPool, config: Config) {
+    let token_maker =
+        TokenMaker::new(&config.token.symmetric_key).expect("Failed to create token maker");
+    let tx_options = TxOptions {
+        isolation_level: config.database.isolation_level,
+        max_retries: config.database.max_tx_retries,
+        ..TxOptions::default()
+    };
+
+    let store = PgStore::new(db.clone(), tx_options);
+    let currencies = store
+        .list_currencies()
         .await
-        .expect("Failed to create connection pool");
+        .expect("Failed to load currencies");
+    tracing::info!(count = currencies.len(), "loaded currencies");
+    currency::register(currencies);
+
+    let state = AppState {
+        store: Arc::new(store),
+        token_maker: Arc::new(token_maker),
+        access_token_duration: chrono::Duration::seconds(config.token.access_token_duration_secs),
+        refresh_token_duration: chrono::Duration::seconds(config.token.refresh_token_duration_secs),
+        idempotency_key_ttl: chrono::Duration::seconds(config.server.idempotency_key_ttl_secs),
+        fx_quote_ttl: chrono::Duration::seconds(config.server.fx_quote_ttl_secs),
+        shutdown: Shutdown::new(),
+    };
+    let shutdown = state.shutdown.clone();
+
+    let router = api::router::routes(state);
 
-    let router = api::router::routes(db);
+    let server = Server::builder()
+        .router(router)
+        .bind_address(config.server.bind_address)
+        .request_timeout(config.server.request_timeout())
+        .pre_stop_delay(config.server.shutdown_pre_stop_delay())
+        .drain_timeout(config.server.shutdown_drain_timeout())
+        .shutdown(shutdown)
+        .build()
+        .await;
+    tracing::info!("listening on {}", server.local_addr());
+    let remaining = server.run().await;
 
-    Server::builder().router(router).build().await.run().await;
+    // Abandoned requests may still hold connections, and `close` waits for
+    // every one of them to be returned.
+    match tokio::time::timeout(remaining, db.close()).await {
+        Ok(()) => tracing::info!("database pool closed"),
+        Err(_) => tracing::warn!("drain timeout elapsed before the database pool closed"),
+    }
 }
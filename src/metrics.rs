@@ -0,0 +1,145 @@
+use lazy_static::lazy_static;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, HistogramVec, IntCounter, IntCounterVec, IntGaugeVec, Opts,
+    Registry, TextEncoder,
+};
+
+lazy_static! {
+    /// Process-wide metrics, scraped from `GET /metrics`.
+    pub static ref METRICS: Metrics = Metrics::new();
+}
+
+pub struct Metrics {
+    registry: Registry,
+    /// Labelled by method, matched route and status code.
+    pub http_requests_total: IntCounterVec,
+    pub http_request_duration_seconds: HistogramVec,
+    /// Labelled by state: `max`, `open`, `idle` or `in_use`.
+    pub db_pool_connections: IntGaugeVec,
+    /// Time spent waiting for a pool connection to begin a transaction.
+    pub db_pool_acquire_duration_seconds: Histogram,
+    pub db_tx_retries_total: IntCounter,
+    pub db_tx_rollbacks_total: IntCounter,
+    /// Labelled by outcome: `ok` or `error`.
+    pub transfer_tx_duration_seconds: HistogramVec,
+    /// Transferred amount in minor units, labelled by currency.
+    pub transfer_volume_total: IntCounterVec,
+}
+
+/// A snapshot of connection pool usage.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PoolStats {
+    pub max: u32,
+    pub open: u32,
+    pub idle: usize,
+}
+
+impl Metrics {
+    fn new() -> Metrics {
+        let registry = Registry::new_custom(Some("simplebank".to_string()), None)
+            .expect("valid metrics prefix");
+
+        let http_requests_total = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let http_request_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "http_request_duration_seconds",
+                "HTTP request latency in seconds",
+            ),
+            &["method", "route", "status"],
+        )
+        .unwrap();
+        let db_pool_connections = IntGaugeVec::new(
+            Opts::new("db_pool_connections", "Database pool connections by state"),
+            &["state"],
+        )
+        .unwrap();
+        let db_pool_acquire_duration_seconds = Histogram::with_opts(HistogramOpts::new(
+            "db_pool_acquire_duration_seconds",
+            "Time spent waiting for a database connection to begin a transaction",
+        ))
+        .unwrap();
+        let db_tx_retries_total = IntCounter::new(
+            "db_tx_retries_total",
+            "Transactions retried after a serialization failure or deadlock",
+        )
+        .unwrap();
+        let db_tx_rollbacks_total =
+            IntCounter::new("db_tx_rollbacks_total", "Transactions rolled back").unwrap();
+        let transfer_tx_duration_seconds = HistogramVec::new(
+            HistogramOpts::new(
+                "transfer_tx_duration_seconds",
+                "Transfer transaction duration in seconds, including retries",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let transfer_volume_total = IntCounterVec::new(
+            Opts::new(
+                "transfer_volume_total",
+                "Amount transferred in minor units of the currency",
+            ),
+            &["currency"],
+        )
+        .unwrap();
+
+        registry
+            .register(Box::new(http_requests_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(http_request_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_connections.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_pool_acquire_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_tx_retries_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(db_tx_rollbacks_total.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(transfer_tx_duration_seconds.clone()))
+            .unwrap();
+        registry
+            .register(Box::new(transfer_volume_total.clone()))
+            .unwrap();
+
+        Metrics {
+            registry,
+            http_requests_total,
+            http_request_duration_seconds,
+            db_pool_connections,
+            db_pool_acquire_duration_seconds,
+            db_tx_retries_total,
+            db_tx_rollbacks_total,
+            transfer_tx_duration_seconds,
+            transfer_volume_total,
+        }
+    }
+
+    pub fn observe_pool(&self, stats: PoolStats) {
+        let idle = stats.idle as i64;
+        let open = i64::from(stats.open);
+        let gauge = |state: &str| self.db_pool_connections.with_label_values(&[state]);
+        gauge("max").set(i64::from(stats.max));
+        gauge("open").set(open);
+        gauge("idle").set(idle);
+        gauge("in_use").set((open - idle).max(0));
+    }
+
+    /// Renders every metric in the Prometheus text exposition format.
+    pub fn encode(&self) -> String {
+        let mut buffer = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buffer)
+            .expect("metrics encode as text");
+        String::from_utf8(buffer).expect("metrics text is UTF-8")
+    }
+}
@@ -1,29 +1,173 @@
+use crate::{currency::Currency, decimal::Decimal, money::Money};
 use chrono::{DateTime, Utc};
-use serde::Serialize;
+use serde::{Deserialize, Serialize};
 use sqlx::prelude::FromRow;
+use uuid::Uuid;
 
 #[derive(Debug, FromRow, PartialEq, Clone, Serialize)]
 pub struct Account {
     pub id: i64,
     pub owner: String,
-    pub balance: i64,
-    pub currency: String,
+    pub balance: Money,
+    pub currency: Currency,
     pub created_at: DateTime<Utc>,
+    pub status: AccountStatus,
+    /// How far below zero the balance may go.
+    pub overdraft_limit: Money,
 }
 
-#[derive(Debug, FromRow, PartialEq, Clone)]
+/// Where an account is in its lifecycle. Accounts open as `Active`; `Pending`
+/// is for accounts awaiting approval.
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, strum_macros::Display,
+)]
+#[sqlx(type_name = "account_status", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+#[strum(serialize_all = "lowercase")]
+pub enum AccountStatus {
+    Pending,
+    Active,
+    /// Held by the bank: may receive funds but not send them.
+    Frozen,
+    /// Final. Closed accounts have a zero balance and take part in no transfers.
+    Closed,
+}
+
+impl AccountStatus {
+    /// Whether an account may move from this status to `to`.
+    pub fn can_become(self, to: AccountStatus) -> bool {
+        use AccountStatus::*;
+        matches!(
+            (self, to),
+            (Pending, Active | Closed) | (Active, Frozen | Closed) | (Frozen, Active | Closed)
+        )
+    }
+
+    /// Whether the account may be debited.
+    pub fn can_send(self) -> bool {
+        self == AccountStatus::Active
+    }
+
+    /// Whether the account may be credited.
+    pub fn can_receive(self) -> bool {
+        self != AccountStatus::Closed
+    }
+}
+
+/// A row of the ISO 4217 `currencies` table.
+#[derive(Debug, FromRow, PartialEq, Clone, Copy, Serialize)]
+pub struct CurrencyInfo {
+    pub code: Currency,
+    pub numeric_code: i16,
+    /// Digits after the decimal separator.
+    pub minor_units: i16,
+    /// Disabled currencies are rejected in new requests.
+    pub enabled: bool,
+}
+
+#[derive(Debug, FromRow, PartialEq, Clone, Serialize)]
 pub struct Entry {
     pub id: i64,
     pub account_id: i64,
-    pub amount: i64,
+    /// In the currency of the account.
+    pub amount: Money,
     pub created_at: DateTime<Utc>,
+    /// The transfer this entry books; `None` for deposits and adjustments.
+    pub transfer_id: Option<i64>,
 }
 
-#[derive(Debug, FromRow, PartialEq, Clone)]
+#[derive(Debug, FromRow, PartialEq, Clone, Serialize)]
 pub struct Transfer {
     pub id: i64,
     pub from_account_id: i64,
     pub to_account_id: i64,
-    pub amount: i64,
+    /// Debited from the sender, in the currency of the sending account.
+    pub amount: Money,
+    /// Credited to the receiver, in the currency of the receiving account.
+    pub to_amount: Money,
+    /// The rate `amount` was converted at; `None` if both accounts share a
+    /// currency.
+    pub exchange_rate_id: Option<i64>,
+    pub fx_rate: Option<Decimal>,
+    /// Margin kept on the conversion, in the receiver's currency.
+    pub fx_spread: Money,
+    /// Fraction of a minor unit of the receiver's currency lost to rounding.
+    pub fx_residue: Decimal,
+    pub created_at: DateTime<Utc>,
+}
+
+/// A rate from `base_currency` to `quote_currency`, in force from
+/// `effective_from` until a later rate for the same pair takes over.
+#[derive(Debug, FromRow, PartialEq, Clone, Serialize)]
+pub struct ExchangeRate {
+    pub id: i64,
+    pub base_currency: Currency,
+    pub quote_currency: Currency,
+    /// Mid-market units of the quote currency per unit of the base currency.
+    pub rate: Decimal,
+    /// Margin kept on each conversion, in basis points.
+    pub spread_bps: i32,
+    pub effective_from: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+/// An exchange rate locked for one transfer of `owner` until `expires_at`.
+#[derive(Debug, FromRow, PartialEq, Clone, Serialize)]
+pub struct FxQuote {
+    pub id: Uuid,
+    pub owner: String,
+    pub exchange_rate_id: i64,
+    pub base_currency: Currency,
+    pub quote_currency: Currency,
+    pub rate: Decimal,
+    pub spread_bps: i32,
+    pub expires_at: DateTime<Utc>,
+    /// The transfer that used the quote, if any.
+    pub transfer_id: Option<i64>,
+    pub used_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, PartialEq, Clone)]
+pub struct User {
+    pub username: String,
+    pub hashed_password: String,
+    pub full_name: String,
+    pub email: String,
+    pub password_changed_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+    pub role: Role,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type)]
+#[sqlx(type_name = "user_role", rename_all = "lowercase")]
+#[serde(rename_all = "lowercase")]
+pub enum Role {
+    /// Bank staff: may view and freeze any account and run reports.
+    Banker,
+    /// Customer: restricted to their own accounts.
+    Depositor,
+}
+
+#[derive(Debug, FromRow, PartialEq, Clone)]
+pub struct Session {
+    pub id: Uuid,
+    pub username: String,
+    pub refresh_token: String,
+    pub user_agent: String,
+    pub client_ip: String,
+    pub is_blocked: bool,
+    pub expires_at: DateTime<Utc>,
+    pub created_at: DateTime<Utc>,
+}
+
+#[derive(Debug, FromRow, PartialEq, Clone)]
+pub struct IdempotencyKey {
+    pub scope: String,
+    pub key: String,
+    pub request_hash: String,
+    pub response_status: Option<i16>,
+    pub response_content_type: Option<String>,
+    pub response_body: Option<Vec<u8>>,
     pub created_at: DateTime<Utc>,
 }
@@ -0,0 +1,236 @@
+use crate::{
+    currency::Currency,
+    decimal::{parse_fixed, ParseFixedError},
+};
+use serde::{Serialize, Serializer};
+use sqlx::{
+    encode::IsNull,
+    error::BoxDynError,
+    postgres::{
+        types::{Oid, PgRecordDecoder},
+        PgArgumentBuffer, PgTypeInfo, PgValueRef,
+    },
+    Decode, Encode, Postgres, Type,
+};
+use std::fmt;
+
+/// The anonymous `record` type of row values such as `(amount, currency)`.
+const RECORD_OID: Oid = Oid(2249);
+
+/// An amount in the minor units of its currency, e.g. cents for USD.
+///
+/// Arithmetic is checked: it fails on overflow or when the operands are in
+/// different currencies instead of wrapping or silently mixing them. Amounts
+/// serialize as decimal strings in the currency's precision (`"12.05"`); the
+/// currency itself is reported next to them.
+///
+/// In Postgres an amount is a `bigint` column next to a `currency` column.
+/// It is read from the pair as a row value, `(amount, currency)`, and bound
+/// as its `bigint` minor units.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    pub minor_units: i64,
+    pub currency: Currency,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, strum_macros::AsRefStr)]
+#[serde(tag = "type", content = "data")]
+pub enum MoneyError {
+    Overflow,
+    CurrencyMismatch { expected: Currency, found: Currency },
+    InvalidAmount { amount: String, currency: Currency },
+}
+
+impl Money {
+    pub fn new(minor_units: i64, currency: Currency) -> Money {
+        Money {
+            minor_units,
+            currency,
+        }
+    }
+
+    pub fn zero(currency: Currency) -> Money {
+        Money::new(0, currency)
+    }
+
+    /// Parses a decimal amount such as `"12.05"` with at most as many
+    /// fractional digits as the currency has minor units.
+    pub fn parse(amount: &str, currency: Currency) -> Result<Money, MoneyError> {
+        parse_fixed(amount, currency.minor_units())
+            .map(|minor_units| Money::new(minor_units, currency))
+            .map_err(|err| match err {
+                ParseFixedError::Overflow => MoneyError::Overflow,
+                ParseFixedError::Invalid => MoneyError::InvalidAmount {
+                    amount: amount.to_string(),
+                    currency,
+                },
+            })
+    }
+
+    pub fn checked_add(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        self.minor_units
+            .checked_add(other.minor_units)
+            .map(|minor_units| Money::new(minor_units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_sub(self, other: Money) -> Result<Money, MoneyError> {
+        self.same_currency(other)?;
+        self.minor_units
+            .checked_sub(other.minor_units)
+            .map(|minor_units| Money::new(minor_units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn checked_neg(self) -> Result<Money, MoneyError> {
+        self.minor_units
+            .checked_neg()
+            .map(|minor_units| Money::new(minor_units, self.currency))
+            .ok_or(MoneyError::Overflow)
+    }
+
+    pub fn is_positive(self) -> bool {
+        self.minor_units > 0
+    }
+
+    pub fn is_negative(self) -> bool {
+        self.minor_units < 0
+    }
+
+    /// Fails unless `other` is in this amount's currency.
+    pub fn same_currency(self, other: Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch {
+                expected: self.currency,
+                found: other.currency,
+            });
+        }
+        Ok(())
+    }
+
+    /// The amount as a decimal string without the currency, e.g. `12.05`.
+    pub fn amount(self) -> String {
+        self.currency.format_amount(self.minor_units)
+    }
+}
+
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{} {}", self.amount(), self.currency)
+    }
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::Overflow => write!(f, "amount is out of range"),
+            MoneyError::CurrencyMismatch { expected, found } => {
+                write!(f, "expected an amount in {expected}, not {found}")
+            }
+            MoneyError::InvalidAmount { amount, currency } => write!(
+                f,
+                "{amount:?} is not a decimal amount with at most {} decimal places",
+                currency.minor_units()
+            ),
+        }
+    }
+}
+
+impl std::error::Error for MoneyError {}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.amount())
+    }
+}
+
+impl Type<Postgres> for Money {
+    fn type_info() -> PgTypeInfo {
+        PgTypeInfo::with_oid(RECORD_OID)
+    }
+}
+
+impl Encode<'_, Postgres> for Money {
+    fn encode_by_ref(&self, buf: &mut PgArgumentBuffer) -> IsNull {
+        <i64 as Encode<Postgres>>::encode(self.minor_units, buf)
+    }
+
+    fn produces(&self) -> Option<PgTypeInfo> {
+        Some(<i64 as Type<Postgres>>::type_info())
+    }
+}
+
+impl<'r> Decode<'r, Postgres> for Money {
+    fn decode(value: PgValueRef<'r>) -> Result<Self, BoxDynError> {
+        let mut record = PgRecordDecoder::new(value)?;
+        let minor_units = record.try_decode::<i64>()?;
+        let currency = record.try_decode::<Currency>()?;
+        Ok(Money::new(minor_units, currency))
+    }
+}
+
+mod tests {
+    use super::*;
+
+    fn money(amount: &str, currency: &str) -> Money {
+        Money::parse(amount, currency.parse().unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_parse_money() {
+        let usd: Currency = "USD".parse().unwrap();
+        assert_eq!(money("12.05", "USD"), Money::new(1205, usd));
+        assert_eq!(money("12.5", "USD"), Money::new(1250, usd));
+        assert_eq!(money("-12", "USD"), Money::new(-1200, usd));
+        assert_eq!(money("1205", "JPY").minor_units, 1205);
+        assert_eq!(money("1.205", "KWD").minor_units, 1205);
+        assert_eq!(money("-92233720368547758.08", "USD").minor_units, i64::MIN);
+
+        for amount in ["", "-", "1.", ".5", "1.234", "1,00", "+1", "1e3", " 1"] {
+            assert_eq!(
+                Money::parse(amount, usd),
+                Err(MoneyError::InvalidAmount {
+                    amount: amount.to_string(),
+                    currency: usd,
+                })
+            );
+        }
+        assert_eq!(
+            Money::parse("92233720368547758.08", usd),
+            Err(MoneyError::Overflow)
+        );
+        assert!(Money::parse("1.5", "JPY".parse().unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        let ten = money("10.00", "USD");
+        let three = money("3.00", "USD");
+        assert_eq!(ten.checked_add(three), Ok(money("13.00", "USD")));
+        assert_eq!(three.checked_sub(ten), Ok(money("-7.00", "USD")));
+        assert_eq!(ten.checked_neg(), Ok(money("-10.00", "USD")));
+
+        let max = Money::new(i64::MAX, ten.currency);
+        assert_eq!(max.checked_add(three), Err(MoneyError::Overflow));
+        let min = Money::new(i64::MIN, ten.currency);
+        assert_eq!(min.checked_sub(three), Err(MoneyError::Overflow));
+        assert_eq!(min.checked_neg(), Err(MoneyError::Overflow));
+
+        let eur = money("3.00", "EUR");
+        assert_eq!(
+            ten.checked_add(eur),
+            Err(MoneyError::CurrencyMismatch {
+                expected: ten.currency,
+                found: eur.currency,
+            })
+        );
+    }
+
+    #[test]
+    fn test_serialize_money() {
+        let body = serde_json::to_value(money("-0.05", "USD")).unwrap();
+        assert_eq!(body, serde_json::json!("-0.05"));
+        assert_eq!(money("1205", "JPY").to_string(), "1205 JPY");
+    }
+}
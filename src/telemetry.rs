@@ -0,0 +1,268 @@
+use crate::{config::TelemetryConfig, prelude::*};
+use axum::http::HeaderMap;
+use opentelemetry::{global, trace::TracerProvider as _, Context, KeyValue};
+use opentelemetry_http::{HeaderExtractor, HttpClient};
+use opentelemetry_otlp::{Protocol, SpanExporter, WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::{
+    export::trace::SpanExporter as ExportSpans,
+    propagation::TraceContextPropagator,
+    runtime,
+    trace::{Sampler, TracerProvider},
+    Resource,
+};
+use tracing::{Level, Subscriber};
+use tracing_subscriber::{filter::Targets, registry::LookupSpan, Layer};
+
+const TRACES_PATH: &str = "/v1/traces";
+
+/// Builds the OTLP trace pipeline, or `None` when no endpoint is configured.
+/// The provider must be shut down on exit to flush buffered spans.
+pub fn tracer_provider(config: &TelemetryConfig) -> Result<Option<TracerProvider>> {
+    if config.otlp_endpoint.is_empty() {
+        return Ok(None);
+    }
+    let exporter = span_exporter(config, reqwest::Client::new())?;
+    Ok(Some(build_provider(exporter, config)))
+}
+
+/// An OTLP/HTTP protobuf exporter posting to the configured collector.
+pub fn span_exporter(
+    config: &TelemetryConfig,
+    client: impl HttpClient + 'static,
+) -> Result<SpanExporter> {
+    let endpoint = format!(
+        "{}{TRACES_PATH}",
+        config.otlp_endpoint.trim_end_matches('/')
+    );
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_http_client(client)
+        .with_protocol(Protocol::HttpBinary)
+        .with_endpoint(endpoint)
+        .build()?;
+    Ok(exporter)
+}
+
+/// Batches spans to `exporter`, sampling new traces at the configured ratio
+/// and following the caller's decision for propagated ones.
+pub fn build_provider(
+    exporter: impl ExportSpans + 'static,
+    config: &TelemetryConfig,
+) -> TracerProvider {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+
+    TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            config.sampling_ratio,
+        ))))
+        .with_resource(Resource::new([KeyValue::new(
+            "service.name",
+            config.service_name.clone(),
+        )]))
+        .build()
+}
+
+/// Exports spans at INFO and above, plus sqlx's per-query DEBUG events so
+/// each statement shows up inside the db span that ran it.
+pub fn layer<S>(provider: &TracerProvider) -> impl Layer<S>
+where
+    S: Subscriber + for<'span> LookupSpan<'span>,
+{
+    let filter = Targets::new()
+        .with_default(Level::INFO)
+        .with_target("sqlx::query", Level::DEBUG);
+
+    tracing_opentelemetry::layer()
+        .with_tracer(provider.tracer(env!("CARGO_PKG_NAME")))
+        .with_filter(filter)
+}
+
+/// The trace context a caller propagated in `traceparent`, if any.
+pub fn remote_context(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+mod tests {
+    use super::*;
+    use crate::{
+        api::router::routes,
+        db::{create_connection_pool, store::PgStore, TxOptions},
+        models::Role,
+        utils::*,
+    };
+    use axum::{
+        async_trait,
+        http::{HeaderValue, Method, Request, Response, StatusCode},
+    };
+    use opentelemetry::trace::{SpanId, TraceId};
+    use opentelemetry_http::{Bytes, HttpError};
+    use opentelemetry_proto::tonic::{
+        collector::trace::v1::ExportTraceServiceRequest, common::v1::any_value, trace::v1::Span,
+    };
+    use prost::Message;
+    use serde_json::json;
+    use std::sync::{Arc, Mutex};
+    use tracing_subscriber::layer::SubscriberExt;
+
+    const TRACEPARENT: &str = "00-0af7651916cd43dd8448eb211c80319c-b7ad6b7169203331-01";
+
+    /// Stands in for an OTLP collector: records export requests in memory
+    /// instead of sending them anywhere.
+    #[derive(Debug, Clone, Default)]
+    struct FakeCollector {
+        requests: Arc<Mutex<Vec<Request<Vec<u8>>>>>,
+    }
+
+    #[async_trait]
+    impl HttpClient for FakeCollector {
+        async fn send(
+            &self,
+            request: Request<Vec<u8>>,
+        ) -> std::result::Result<Response<Bytes>, HttpError> {
+            self.requests.lock().unwrap().push(request);
+            Ok(Response::builder()
+                .status(StatusCode::OK)
+                .body(Bytes::new())?)
+        }
+    }
+
+    impl FakeCollector {
+        fn spans(&self) -> Vec<Span> {
+            let requests = self.requests.lock().unwrap();
+            requests
+                .iter()
+                .inspect(|request| {
+                    assert_eq!(request.uri(), "http://collector.test:4318/v1/traces");
+                })
+                .flat_map(|request| {
+                    ExportTraceServiceRequest::decode(request.body().as_slice())
+                        .unwrap()
+                        .resource_spans
+                })
+                .flat_map(|resource_spans| resource_spans.scope_spans)
+                .flat_map(|scope_spans| scope_spans.spans)
+                .collect()
+        }
+    }
+
+    fn telemetry_config(sampling_ratio: f64) -> TelemetryConfig {
+        TelemetryConfig {
+            otlp_endpoint: "http://collector.test:4318/".to_string(),
+            sampling_ratio,
+            ..TelemetryConfig::default()
+        }
+    }
+
+    fn has_ancestor(spans: &[Span], span: &Span, ancestor: &Span) -> bool {
+        let mut parent_id = span.parent_span_id.clone();
+        while let Some(parent) = spans.iter().find(|span| span.span_id == parent_id) {
+            if parent.span_id == ancestor.span_id {
+                return true;
+            }
+            parent_id = parent.parent_span_id.clone();
+        }
+        false
+    }
+
+    fn find<'a>(spans: &'a [Span], name: &str) -> &'a Span {
+        spans
+            .iter()
+            .find(|span| span.name == name)
+            .unwrap_or_else(|| panic!("no {name} span exported"))
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_export_transfer_trace() {
+        dotenv::dotenv().ok();
+        let pool = create_connection_pool(Some(10)).await.unwrap();
+        let state = app_state(PgStore::new(pool, TxOptions::default()));
+        let user = random_store_user(&*state.store, Role::Depositor)
+            .await
+            .unwrap();
+        let from_account = random_store_account(&*state.store, &user.username, "USD", 100)
+            .await
+            .unwrap();
+        let to_account = random_store_account(&*state.store, &user.username, "USD", 0)
+            .await
+            .unwrap();
+
+        let collector = FakeCollector::default();
+        let config = telemetry_config(1.0);
+        let provider = build_provider(span_exporter(&config, collector.clone()).unwrap(), &config);
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let mut req = json_request(
+            Method::POST,
+            "/transfers",
+            Some(&access_token(&state, &user)),
+            Some(json!({
+                "from_account_id": from_account.id,
+                "to_account_id": to_account.id,
+                "amount": "0.10",
+                "currency": "USD",
+            })),
+        );
+        req.headers_mut()
+            .insert("traceparent", HeaderValue::from_static(TRACEPARENT));
+        let (status, _, _) = send(routes(state.clone()), req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        for result in provider.force_flush() {
+            result.unwrap();
+        }
+        let spans = collector.spans();
+
+        // The request continues the caller's trace.
+        let request = find(&spans, "request");
+        let trace_id = TraceId::from_hex("0af7651916cd43dd8448eb211c80319c").unwrap();
+        let parent_id = SpanId::from_hex("b7ad6b7169203331").unwrap();
+        assert_eq!(request.trace_id, trace_id.to_bytes());
+        assert_eq!(request.parent_span_id, parent_id.to_bytes());
+
+        let transfer_tx = find(&spans, "transfer_tx");
+        assert!(has_ancestor(&spans, transfer_tx, request));
+        let create_transfer = find(&spans, "create_transfer");
+        assert!(has_ancestor(&spans, create_transfer, transfer_tx));
+
+        // sqlx records each statement as an event on the span that ran it.
+        let statement = create_transfer
+            .events
+            .iter()
+            .flat_map(|event| &event.attributes)
+            .find(|attribute| attribute.key == "db.statement")
+            .and_then(|attribute| attribute.value.as_ref()?.value.as_ref())
+            .expect("create_transfer span has a db.statement event");
+        match statement {
+            any_value::Value::StringValue(sql) => {
+                // sqlx logs statements reformatted across lines.
+                let sql = sql.split_whitespace().collect::<Vec<_>>().join(" ");
+                assert!(sql.contains("INSERT INTO transfers"), "{sql}");
+            }
+            other => panic!("unexpected db.statement {other:?}"),
+        }
+
+        provider.shutdown().unwrap();
+    }
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_sampling_ratio_zero_exports_nothing() {
+        let collector = FakeCollector::default();
+        let config = telemetry_config(0.0);
+        let provider = build_provider(span_exporter(&config, collector.clone()).unwrap(), &config);
+        let subscriber = tracing_subscriber::registry().with(layer(&provider));
+        let _guard = tracing::subscriber::set_default(subscriber);
+
+        let req = json_request(Method::GET, "/healthz", None, None);
+        let (status, _, _) = send(routes(mem_app_state()), req).await;
+        assert_eq!(status, StatusCode::OK);
+
+        for result in provider.force_flush() {
+            result.unwrap();
+        }
+        assert!(collector.spans().is_empty());
+
+        provider.shutdown().unwrap();
+    }
+}
@@ -1,10 +1,34 @@
 use crate::{
-    models::{Entry, Transfer},
+    api::{shutdown::Shutdown, state::AppState},
+    auth::token::{TokenMaker, TokenType},
+    currency::Currency,
+    db::{mem_store::MemStore, store::Store, user_sql::CreateUserParams},
+    models::{Entry, Role, Session, Transfer, User},
     prelude::*,
 };
+use axum::{
+    body::{to_bytes, Body},
+    http::{
+        header::{AUTHORIZATION, CONTENT_TYPE},
+        HeaderMap, Method, Request, StatusCode,
+    },
+    Router,
+};
 use rand::Rng;
+use serde_json::Value;
+use std::sync::Arc;
+use tower::ServiceExt;
 
-use crate::{db::account_sql::CreateAccountParams, models::Account};
+use crate::{
+    db::{
+        account_sql::{create_account, get_account, CreateAccountParams},
+        entry_sql::{create_entry, CreateEntryParams},
+        transfer_sql::{create_transfer, CreateTransferParams},
+        TxOptions,
+    },
+    models::Account,
+    money::Money,
+};
 
 pub fn random_int(min: i64, max: i64) -> i64 {
     let mut rng = rand::thread_rng();
@@ -15,7 +39,7 @@ pub fn random_string(len: usize) -> String {
     let mut rng = rand::thread_rng();
     let s: String = (0..len)
         .map(|_| {
-            let c: char = rng.gen_range(b'a'..b'z') as char;
+            let c: char = rng.gen_range(b'a'..=b'z') as char;
             c
         })
         .collect();
@@ -26,72 +50,231 @@ pub fn random_owner() -> String {
     random_string(6)
 }
 
+pub fn random_email() -> String {
+    format!("{}@email.com", random_string(6))
+}
+
 pub fn random_money() -> i64 {
     random_int(0, 1000)
 }
 
-pub fn random_currency() -> String {
-    let currencies = vec!["USD", "EUR", "JPY", "CNY", "KRW"];
+pub fn random_currency() -> Currency {
+    let currencies = ["USD", "EUR", "JPY", "CNY", "KRW"];
     let mut rng = rand::thread_rng();
     let idx = rng.gen_range(0..currencies.len());
-    currencies[idx].to_string()
+    currencies[idx].parse().unwrap()
 }
 
-pub async fn random_account(pool: &sqlx::PgPool) -> Result<Account> {
+pub async fn random_user(pool: &sqlx::PgPool) -> Result<User> {
     let mut tx = pool.begin().await?;
 
-    let arg = CreateAccountParams {
-        owner: random_owner(),
-        balance: random_money(),
-        currency: random_currency(),
-    };
-
-    let account: Account = sqlx::query_as(
-        "INSERT INTO accounts (owner, balance, currency) VALUES ($1, $2, $3) RETURNING *;",
+    let user: User = sqlx::query_as(
+        "INSERT INTO users (username, hashed_password, full_name, email) VALUES ($1, $2, $3, $4) RETURNING *;",
     )
-    .bind(arg.owner)
-    .bind(arg.balance)
-    .bind(arg.currency)
+    .bind(random_owner())
+    .bind(random_string(32))
+    .bind(random_owner())
+    .bind(random_email())
     .fetch_one(&mut *tx)
     .await?;
 
     tx.commit().await?;
-    Ok(account)
+    Ok(user)
+}
+
+pub async fn random_account(pool: &sqlx::PgPool) -> Result<Account> {
+    random_account_in(pool, random_currency()).await
+}
+
+pub async fn random_account_in(pool: &sqlx::PgPool, currency: Currency) -> Result<Account> {
+    let user = random_user(pool).await?;
+    create_account(
+        pool,
+        TxOptions::default(),
+        CreateAccountParams {
+            owner: user.username,
+            balance: Money::new(random_money(), currency),
+        },
+    )
+    .await
+}
+
+pub async fn random_account_for_owner(pool: &sqlx::PgPool, owner: &str) -> Result<Account> {
+    create_account(
+        pool,
+        TxOptions::default(),
+        CreateAccountParams {
+            owner: owner.to_string(),
+            balance: Money::new(random_money(), random_currency()),
+        },
+    )
+    .await
 }
 
 pub async fn random_entry(pool: &sqlx::PgPool, account_id: i64) -> Result<Entry> {
+    let account = get_account(pool, account_id).await?;
     let mut tx = pool.begin().await?;
 
-    let amount = random_money();
-
-    let entry: Entry =
-        sqlx::query_as("INSERT INTO entries (account_id, amount) VALUES ($1, $2) RETURNING *;")
-            .bind(account_id)
-            .bind(amount)
-            .fetch_one(&mut *tx)
-            .await?;
+    let entry = create_entry(
+        &mut tx,
+        CreateEntryParams {
+            account_id,
+            amount: Money::new(random_money(), account.currency),
+            transfer_id: None,
+        },
+    )
+    .await?;
 
     tx.commit().await?;
     Ok(entry)
 }
 
+/// Books a transfer and its entries without touching balances. Both accounts
+/// must be in the currency of `amount`.
 pub async fn random_transfer(
     pool: &sqlx::PgPool,
     from_account_id: i64,
     to_account_id: i64,
-    amount: i64,
+    amount: Money,
 ) -> Result<Transfer> {
     let mut tx = pool.begin().await?;
 
-    let transfer: Transfer = sqlx::query_as(
-        "INSERT INTO transfers (from_account_id, to_account_id, amount) VALUES ($1, $2, $3) RETURNING *;",
+    let transfer = create_transfer(
+        &mut tx,
+        CreateTransferParams {
+            from_account_id,
+            to_account_id,
+            amount,
+            conversion: None,
+        },
     )
-    .bind(from_account_id)
-    .bind(to_account_id)
-    .bind(amount)
-    .fetch_one(&mut *tx)
     .await?;
 
+    // Transfers are rejected at commit unless booked by a balanced pair of entries.
+    for (account_id, amount) in [
+        (to_account_id, amount),
+        (from_account_id, amount.checked_neg()?),
+    ] {
+        create_entry(
+            &mut tx,
+            CreateEntryParams {
+                account_id,
+                amount,
+                transfer_id: Some(transfer.id),
+            },
+        )
+        .await?;
+    }
+
     tx.commit().await?;
     Ok(transfer)
 }
+
+pub async fn random_session(
+    pool: &sqlx::PgPool,
+    username: &str,
+    duration: chrono::Duration,
+) -> Result<Session> {
+    let session: Session = sqlx::query_as(
+        "INSERT INTO sessions (id, username, refresh_token, user_agent, client_ip, expires_at) VALUES ($1, $2, $3, $4, $5, $6) RETURNING *;",
+    )
+    .bind(uuid::Uuid::new_v4())
+    .bind(username)
+    .bind(random_string(32))
+    .bind(random_string(10))
+    .bind("127.0.0.1")
+    .bind(chrono::Utc::now() + duration)
+    .fetch_one(pool)
+    .await?;
+
+    Ok(session)
+}
+
+/// State over an empty `MemStore`, for exercising handlers without Postgres.
+pub fn mem_app_state() -> AppState<MemStore> {
+    app_state(MemStore::new())
+}
+
+pub fn app_state<S: Store>(store: S) -> AppState<S> {
+    AppState {
+        store: Arc::new(store),
+        token_maker: Arc::new(TokenMaker::new(&random_string(32)).unwrap()),
+        access_token_duration: chrono::Duration::minutes(15),
+        refresh_token_duration: chrono::Duration::days(1),
+        idempotency_key_ttl: chrono::Duration::days(1),
+        fx_quote_ttl: chrono::Duration::seconds(30),
+        shutdown: Shutdown::new(),
+    }
+}
+
+pub async fn random_store_user<S: Store>(store: &S, role: Role) -> Result<User> {
+    store
+        .create_user(CreateUserParams {
+            username: random_owner(),
+            hashed_password: random_string(32),
+            full_name: random_owner(),
+            email: random_email(),
+            role,
+        })
+        .await
+}
+
+/// An account holding `balance` minor units of `currency`.
+pub async fn random_store_account<S: Store>(
+    store: &S,
+    owner: &str,
+    currency: &str,
+    balance: i64,
+) -> Result<Account> {
+    store
+        .create_account(CreateAccountParams {
+            owner: owner.to_string(),
+            balance: Money::new(balance, currency.parse()?),
+        })
+        .await
+}
+
+pub fn access_token<S>(state: &AppState<S>, user: &User) -> String {
+    let (token, _) = state
+        .token_maker
+        .create_token(
+            &user.username,
+            user.role,
+            TokenType::Access,
+            state.access_token_duration,
+        )
+        .unwrap();
+    token
+}
+
+/// A JSON request, authenticated when `token` is given.
+pub fn json_request(
+    method: Method,
+    uri: &str,
+    token: Option<&str>,
+    body: Option<Value>,
+) -> Request<Body> {
+    let mut req = Request::builder()
+        .method(method)
+        .uri(uri)
+        .header(CONTENT_TYPE, "application/json");
+    if let Some(token) = token {
+        req = req.header(AUTHORIZATION, format!("Bearer {token}"));
+    }
+    let body = body.map_or_else(Body::empty, |body| Body::from(body.to_string()));
+    req.body(body).unwrap()
+}
+
+/// Sends `req` through `router` and decodes the JSON response body
+/// (`Value::Null` when empty).
+pub async fn send(router: Router, req: Request<Body>) -> (StatusCode, HeaderMap, Value) {
+    let res = router.oneshot(req).await.unwrap();
+    let (parts, body) = res.into_parts();
+    let body = to_bytes(body, usize::MAX).await.unwrap();
+    let body = if body.is_empty() {
+        Value::Null
+    } else {
+        serde_json::from_slice(&body).unwrap()
+    };
+    (parts.status, parts.headers, body)
+}